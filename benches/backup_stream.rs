@@ -0,0 +1,96 @@
+//! Streaming a backup through a pipe to a compressor thread, against
+//! writing the whole tar to a temp file first and compressing that.
+//!
+//! Uses 1 GB of test data by default; set `BACKUP_BENCH_MB` for a
+//! quicker run.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tar::Builder;
+use xz2::write::XzEncoder;
+
+const FILE_SIZE: usize = 64 * 1024 * 1024;
+const LEVEL: u32 = 1;
+
+/// Source files with mixed compressible and noisy content, like world data
+fn write_source(dir: &Path, total: usize) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let mut seed: u64 = 0x9E37_79B9_7F4A_7C15;
+    let mut written = 0;
+    let mut index = 0;
+    while written < total {
+        let size = FILE_SIZE.min(total - written);
+        let mut file = BufWriter::new(File::create(dir.join(format!("region_{}.bin", index)))?);
+        let mut chunk = [0u8; 4096];
+        for _ in 0..size.div_ceil(chunk.len()) {
+            for (i, byte) in chunk.iter_mut().enumerate() {
+                seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+                *byte = if i % 4 == 0 { (seed >> 56) as u8 } else { 0 };
+            }
+            file.write_all(&chunk)?;
+        }
+        written += size;
+        index += 1;
+    }
+    Ok(())
+}
+
+fn stream_to_pipe(source: &Path, dest: &Path) -> io::Result<()> {
+    let (mut reader, writer) = io::pipe()?;
+    let dest = dest.to_path_buf();
+    let compressor = std::thread::spawn(move || -> io::Result<()> {
+        let mut encoder = XzEncoder::new(File::create(&dest)?, LEVEL);
+        io::copy(&mut reader, &mut encoder)?;
+        encoder.finish()?;
+        Ok(())
+    });
+
+    let mut tar = Builder::new(writer);
+    tar.append_dir_all(".", source)?;
+    drop(tar.into_inner()?);
+    compressor
+        .join()
+        .unwrap_or_else(|_| Err(io::Error::other("Compressor thread panicked")))
+}
+
+fn via_temp_file(source: &Path, temp: &Path, dest: &Path) -> io::Result<()> {
+    let mut tar = Builder::new(BufWriter::new(File::create(temp)?));
+    tar.append_dir_all(".", source)?;
+    tar.into_inner()?.flush()?;
+
+    let mut encoder = XzEncoder::new(File::create(dest)?, LEVEL);
+    io::copy(&mut File::open(temp)?, &mut encoder)?;
+    encoder.finish()?;
+    fs::remove_file(temp)
+}
+
+fn bench_backup(c: &mut Criterion) {
+    let megabytes: usize = std::env::var("BACKUP_BENCH_MB")
+        .ok()
+        .and_then(|mb| mb.parse().ok())
+        .unwrap_or(1024);
+    let dir: PathBuf = std::env::temp_dir().join(format!("watcher-bench-{}", uuid::Uuid::new_v4()));
+    let source = dir.join("world");
+    write_source(&source, megabytes * 1024 * 1024).expect("write test data");
+    let dest = dir.join("backup.tar.xz");
+    let temp = dir.join("backup.tar");
+
+    let mut group = c.benchmark_group(format!("backup_{}mb", megabytes));
+    group.sample_size(10);
+    group.measurement_time(Duration::from_secs(60));
+    group.bench_function("stream_to_pipe", |b| {
+        b.iter(|| stream_to_pipe(&source, &dest).expect("streaming backup"))
+    });
+    group.bench_function("temp_file", |b| {
+        b.iter(|| via_temp_file(&source, &temp, &dest).expect("temp file backup"))
+    });
+    group.finish();
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+criterion_group!(benches, bench_backup);
+criterion_main!(benches);
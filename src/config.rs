@@ -1,13 +1,28 @@
+use crate::watcher::state::{AppState, LogLevel, LogSource, StateChangeEvent};
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Weekday};
+use notify::{EventKind, RecursiveMode, Watcher};
+use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch};
+use tokio::time::{timeout, Duration};
+use utoipa::ToSchema;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Config {
+    /// How this watcher identifies itself to dashboards and notifications
+    #[serde(default, alias = "server_name")]
+    pub identity: IdentityConfig,
     pub server: ServerConfig,
     pub telegram: TelegramConfig,
     pub resources: ResourceConfig,
     pub error_patterns: ErrorPatterns,
+    /// Regex groupings for `GET /api/logs/categories`
+    #[serde(default = "default_log_categories")]
+    pub log_categories: Vec<LogCategoryConfig>,
     pub restart_on: RestartConfig,
     #[serde(default)]
     pub backup: BackupConfig,
@@ -15,47 +30,700 @@ pub struct Config {
     pub web: WebConfig,
     #[serde(default)]
     pub remote_servers: Vec<RemoteServer>,
+    #[serde(default)]
+    pub config: ConfigConfig,
+    #[serde(default)]
+    pub state: StateConfig,
+    /// Also write watcher log entries to disk as JSON lines
+    #[serde(default)]
+    pub log_file: Option<WatcherLogFileConfig>,
+}
+
+/// Also accepts a plain string, the value of the old `server_name`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(from = "IdentityRepr")]
+pub struct IdentityConfig {
+    #[serde(default = "default_identity_name")]
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+}
+
+fn default_identity_name() -> String {
+    "Server".to_string()
+}
+
+impl Default for IdentityConfig {
+    fn default() -> Self {
+        Self {
+            name: default_identity_name(),
+            description: None,
+            tags: vec![],
+            icon: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum IdentityRepr {
+    Name(String),
+    Identity {
+        #[serde(default = "default_identity_name")]
+        name: String,
+        #[serde(default)]
+        description: Option<String>,
+        #[serde(default)]
+        tags: Vec<String>,
+        #[serde(default)]
+        icon: Option<String>,
+    },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl From<IdentityRepr> for IdentityConfig {
+    fn from(repr: IdentityRepr) -> Self {
+        match repr {
+            IdentityRepr::Name(name) => Self {
+                name,
+                ..Self::default()
+            },
+            IdentityRepr::Identity {
+                name,
+                description,
+                tags,
+                icon,
+            } => Self {
+                name,
+                description,
+                tags,
+                icon,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ServerConfig {
     pub executable: String,
     pub arguments: Vec<String>,
     pub working_directory: Option<String>,
-    pub restart_delay_seconds: u64,
+    /// Extra environment variables for the server process. Everything in here
+    /// reaches the server and any child it starts, so `LD_PRELOAD` and the
+    /// like can run arbitrary code. Values are redacted in `GET /api/config`.
+    #[serde(default)]
+    pub env_vars: HashMap<String, String>,
+    /// Start from an empty environment, so only `env_vars` is set. Note that
+    /// `PATH` is gone too unless listed.
+    #[serde(default)]
+    pub clear_env: bool,
+    /// Delay before restarting after a crash, error or scheduled restart
+    #[serde(alias = "restart_delay_seconds")]
+    pub error_restart_delay_secs: u64,
+    /// Each consecutive crash multiplies the restart delay by this; 1.0 disables backoff
+    #[serde(default = "default_restart_backoff_multiplier")]
+    pub restart_backoff_multiplier: f64,
+    /// Upper bound for the backed-off restart delay
+    #[serde(
+        default = "default_restart_max_delay",
+        alias = "restart_max_delay_seconds"
+    )]
+    pub restart_max_delay_secs: u64,
+    /// Delay before a restart requested from the dashboard or API
+    #[serde(default)]
+    pub manual_restart_delay_secs: u64,
+    /// Extra spawn attempts before a failed start counts as a restart
+    #[serde(default = "default_spawn_retry_count")]
+    pub spawn_retry_count: u32,
+    #[serde(default = "default_spawn_retry_delay")]
+    pub spawn_retry_delay_ms: u64,
     pub max_restarts: Option<u32>,
+    /// Reset the restart counter once a run lasts this long, so `max_restarts`
+    /// only counts a recent crash loop
+    #[serde(default)]
+    pub restart_count_reset_after_uptime_secs: Option<u64>,
+    /// Deprecated: same as `auto_restart_interval_minutes: 60`
     #[serde(default)]
     pub auto_restart_hourly: bool,
+    /// Restart the server this often, warning players at each of
+    /// `restart_warning_minutes` before the restart
+    #[serde(default)]
+    pub auto_restart_interval_minutes: Option<u64>,
+    /// Broadcast, `{minutes}` is replaced with the time left
     #[serde(default = "default_restart_warning_message")]
     pub restart_warning_message: String,
+    /// Minutes before a scheduled restart at which the warning is sent
+    #[serde(default = "default_restart_warning_minutes")]
+    pub restart_warning_minutes: Vec<u64>,
+    /// Seconds to wait before the very first spawn (not between restarts)
+    #[serde(default)]
+    pub pre_start_delay_secs: u64,
+    /// Conditions that must hold before the first spawn
+    #[serde(default)]
+    pub pre_start_checks: Vec<PreLaunchCheck>,
+    /// Delete `*.hprof` files older than this; `null` keeps them forever
+    #[serde(default = "default_heap_dump_retention")]
+    pub heap_dump_retention_days: Option<u64>,
+    /// Give up on a stdin write after this long (server not reading)
+    #[serde(default = "default_stdin_write_timeout")]
+    pub stdin_write_timeout_secs: u64,
+    /// Appended to every command written to the server's stdin
+    #[serde(default)]
+    pub stdin_command_terminator: StdinTerminator,
+    /// Console encoding label (e.g. "utf-8", "windows-1251") for stdin and output
+    #[serde(default = "default_encoding")]
+    pub encoding: Option<String>,
+    /// Restart the server after it has run this long, without warning
+    #[serde(default)]
+    pub max_runtime_secs: Option<u64>,
+    /// Fraction (0.0-1.0) of info/warning lines to keep; errors are always kept
+    #[serde(default)]
+    pub log_sample_rate: Option<f32>,
+    /// Output line (substring) that marks the server as started. While set,
+    /// the status stays `starting` until the line appears.
+    #[serde(default, alias = "startup_ready_pattern")]
+    pub startup_pattern: Option<String>,
+    /// Restart if `startup_pattern` has not appeared after this long
+    #[serde(default)]
+    pub max_startup_latency_secs: Option<u64>,
+    /// Treat the start as failed if `startup_pattern` has not appeared
+    /// after this long: the process is killed and handled like an error
+    #[serde(default, alias = "startup_timeout_seconds")]
+    pub server_ready_timeout_secs: Option<u64>,
+    /// Written to stdin on stop and shutdown (e.g. "stop") so the server can save
+    #[serde(default)]
+    pub graceful_stop_command: Option<String>,
+    /// Wait this long for the server to exit after `graceful_stop_command`
+    #[serde(
+        default = "default_graceful_stop_timeout",
+        alias = "graceful_stop_timeout_seconds"
+    )]
+    pub graceful_stop_timeout_secs: u64,
+    /// Shell command run before every restart, in the working directory
+    #[serde(default)]
+    pub pre_restart_command: Option<String>,
+    /// Shell command run once the server is running
+    #[serde(default)]
+    pub post_start_command: Option<String>,
+    /// Port the game server listens on, for connection counting (Linux)
+    #[serde(default)]
+    pub server_port: Option<u16>,
+    /// Expand `${VAR}` and `${TOTAL_MEM_MB/2}` style tokens in `arguments`
+    #[serde(default)]
+    pub interpolate_args: bool,
+    /// Feed the server's stdin from this FIFO (created if missing, Unix only)
+    #[serde(default)]
+    pub stdin_fifo_path: Option<String>,
+    /// Read server output from this FIFO instead of a pipe (Unix only)
+    #[serde(default)]
+    pub stdout_fifo_path: Option<String>,
+    /// Signals that may be sent via `POST /api/signal`
+    #[serde(default = "default_allowed_signals")]
+    pub allowed_signals: Vec<i32>,
+    /// Also write server stdout/stderr to a rotated file
+    #[serde(default)]
+    pub log_file: Option<LogFileConfig>,
+    /// Remove ANSI colour codes from server output before it is matched
+    /// and logged
+    #[serde(default = "default_strip_ansi")]
+    pub strip_ansi: bool,
+    /// Join JVM stack traces (`\tat ...` lines) into the log entry of the
+    /// exception line that starts them
+    #[serde(default)]
+    pub assemble_stack_traces: bool,
+    /// Scheduled auto-restarts that fall inside one of these wait until it
+    /// ends
+    #[serde(default)]
+    pub maintenance_windows: Vec<MaintenanceWindow>,
+}
+
+impl ServerConfig {
+    /// Scheduled restart interval, taking the old `auto_restart_hourly`
+    /// flag into account
+    pub fn auto_restart_interval_minutes(&self) -> Option<u64> {
+        self.auto_restart_interval_minutes
+            .or(self.auto_restart_hourly.then_some(60))
+    }
+
+    /// The maintenance window `now` falls in, with the time it ends
+    pub fn active_maintenance_window(
+        &self,
+        now: NaiveDateTime,
+    ) -> Option<(&MaintenanceWindow, NaiveDateTime)> {
+        self.maintenance_windows
+            .iter()
+            .find_map(|window| window.end_if_active(now).map(|end| (window, end)))
+    }
+}
+
+/// Daily period, in local time, during which auto-restarts are held back
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MaintenanceWindow {
+    /// HH:MM; a window may run past midnight (e.g. 22:00-02:00)
+    pub start: String,
+    pub end: String,
+    /// Days the window starts on; empty means every day
+    #[serde(default)]
+    #[schema(value_type = Vec<String>, example = json!(["Sat", "Sun"]))]
+    pub days: Vec<Weekday>,
+}
+
+impl MaintenanceWindow {
+    fn times(&self) -> Option<(NaiveTime, NaiveTime)> {
+        let parse = |s: &str| NaiveTime::parse_from_str(s, "%H:%M").ok();
+        Some((parse(&self.start)?, parse(&self.end)?))
+    }
+
+    /// When the window ends, if `now` is inside it. Equal start and end
+    /// make a 24 hour window.
+    pub fn end_if_active(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let (start, end) = self.times()?;
+        let today = now.date();
+        // Yesterday's window may still run past midnight
+        for day in [today.pred_opt()?, today] {
+            if !self.days.is_empty() && !self.days.contains(&day.weekday()) {
+                continue;
+            }
+            let from = day.and_time(start);
+            let mut to = day.and_time(end);
+            if end <= start {
+                to += chrono::Duration::days(1);
+            }
+            if from <= now && now < to {
+                return Some(to);
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LogFileConfig {
+    /// Relative paths are resolved against the working directory
+    pub path: String,
+    #[serde(default)]
+    pub rotation: LogRotationPolicy,
+}
+
+/// When the server log file is rotated; rotated files are xz-compressed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LogRotationPolicy {
+    MaxSize {
+        max_size_mb: u64,
+    },
+    /// When the local date changes
+    Daily,
+    /// Whichever condition fires first
+    Both {
+        max_size_mb: u64,
+        rotate_daily: bool,
+    },
+}
+
+impl Default for LogRotationPolicy {
+    fn default() -> Self {
+        LogRotationPolicy::MaxSize { max_size_mb: 100 }
+    }
+}
+
+fn default_heap_dump_retention() -> Option<u64> {
+    Some(7)
+}
+
+fn default_stdin_write_timeout() -> u64 {
+    5
+}
+
+fn default_encoding() -> Option<String> {
+    Some("utf-8".to_string())
+}
+
+fn default_strip_ansi() -> bool {
+    true
+}
+
+fn default_spawn_retry_count() -> u32 {
+    3
+}
+
+fn default_spawn_retry_delay() -> u64 {
+    500
+}
+
+fn default_graceful_stop_timeout() -> u64 {
+    30
+}
+
+fn default_restart_backoff_multiplier() -> f64 {
+    1.0
+}
+
+fn default_restart_max_delay() -> u64 {
+    600
+}
+
+/// SIGUSR1, SIGUSR2, SIGTERM, with this platform's numbers
+#[cfg(unix)]
+fn default_allowed_signals() -> Vec<i32> {
+    use nix::sys::signal::Signal;
+    vec![
+        Signal::SIGUSR1 as i32,
+        Signal::SIGUSR2 as i32,
+        Signal::SIGTERM as i32,
+    ]
+}
+
+/// Signals cannot be sent without Unix
+#[cfg(not(unix))]
+fn default_allowed_signals() -> Vec<i32> {
+    Vec::new()
+}
+
+/// End-of-command marker the server's console expects
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StdinTerminator {
+    #[default]
+    Lf,
+    CrLf,
+    Null,
+    /// Raw byte, written as-is regardless of the console encoding
+    Custom(u8),
+}
+
+impl StdinTerminator {
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            StdinTerminator::Lf => b"\n",
+            StdinTerminator::CrLf => b"\r\n",
+            StdinTerminator::Null => b"\0",
+            StdinTerminator::Custom(byte) => std::slice::from_ref(byte),
+        }
+    }
+}
+
+/// Dependency check run before the server is first started
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PreLaunchCheck {
+    /// Path must exist, e.g. a network mount
+    PathExists { path: String },
+    /// Address must accept TCP connections, e.g. a database
+    TcpConnect { address: String },
 }
 
 fn default_restart_warning_message() -> String {
-    "Server will restart in 1 minute!".to_string()
+    "Server will restart in {minutes} min!".to_string()
+}
+
+fn default_restart_warning_minutes() -> Vec<u64> {
+    vec![5, 1]
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct TelegramConfig {
     pub enabled: bool,
     pub token: String,
     pub chat_id: String,
+    /// Include PID, paths, log lines and checksums in notifications
+    #[serde(default)]
+    pub verbose_notifications: bool,
+    /// Failed notifications kept for retry; the oldest are dropped beyond this
+    #[serde(default = "default_telegram_queue_size")]
+    pub queue_size: usize,
+    /// Resend attempts before a queued notification is given up on
+    #[serde(default = "default_telegram_max_retries")]
+    pub max_retries: u32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+fn default_telegram_queue_size() -> usize {
+    100
+}
+
+fn default_telegram_max_retries() -> u32 {
+    10
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ResourceConfig {
     pub cpu_threshold_percent: f32,
     pub memory_threshold_mb: u64,
     pub check_interval_seconds: u64,
+    /// Sample PSS / private dirty memory from smaps_rollup (Linux, every 30s)
+    #[serde(default)]
+    pub detailed_memory_stats: bool,
+    /// Poll heap and GC numbers from a Jolokia agent
+    #[serde(default)]
+    pub jvm_metrics: Option<JvmMetricsConfig>,
+    /// Combined read + write rate that raises a disk I/O alert
+    #[serde(default)]
+    pub disk_io_threshold_mb_per_sec: Option<u64>,
+    /// Established connections on `server.server_port` that trigger an alert
+    #[serde(default)]
+    pub max_connections_warning: Option<u32>,
+    /// Samples kept for `GET /api/stats/history`, one per check interval
+    #[serde(default = "default_stats_history_size")]
+    pub stats_history_size: usize,
+    /// Usage of the disk holding the working directory that raises an alert
+    #[serde(default = "default_disk_threshold_percent")]
+    pub disk_threshold_percent: f32,
+    /// Open file descriptors of the server that trigger a warning (Linux)
+    #[serde(default)]
+    pub fd_threshold: Option<u32>,
+    /// Server threads that trigger a warning
+    #[serde(default)]
+    pub thread_threshold: Option<u32>,
+    /// Seconds CPU must stay above `cpu_threshold_percent` before alerting
+    #[serde(default = "default_cpu_threshold_sustained_seconds")]
+    pub cpu_threshold_sustained_seconds: u64,
+    /// Minimum gap between two sustained CPU alerts
+    #[serde(default = "default_cpu_alert_cooldown_seconds")]
+    pub cpu_alert_cooldown_seconds: u64,
+    /// What a sustained CPU alert does besides notifying; `notify` when unset
+    #[serde(default)]
+    pub cpu_threshold_action: Option<CpuThresholdAction>,
+    /// Memory samples the leak detector fits a trend line to
+    #[serde(default = "default_memory_leak_window_minutes")]
+    pub memory_leak_window_minutes: u64,
+    /// Growth over a full window that counts as a leak
+    #[serde(default = "default_memory_leak_threshold_mb_per_hour")]
+    pub memory_leak_threshold_mb_per_hour: f64,
+}
+
+/// Reaction to CPU staying above the threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CpuThresholdAction {
+    Notify,
+    /// Notify and restart the server
+    Restart,
+}
+
+fn default_stats_history_size() -> usize {
+    3600
+}
+
+fn default_disk_threshold_percent() -> f32 {
+    90.0
+}
+
+fn default_cpu_threshold_sustained_seconds() -> u64 {
+    30
+}
+
+fn default_cpu_alert_cooldown_seconds() -> u64 {
+    300
+}
+
+fn default_memory_leak_window_minutes() -> u64 {
+    60
+}
+
+fn default_memory_leak_threshold_mb_per_hour() -> f64 {
+    500.0
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct JvmMetricsConfig {
+    /// Agent base URL, e.g. `http://127.0.0.1:8778/jolokia`
+    pub jolokia_url: String,
+    #[serde(default = "default_jvm_poll_interval")]
+    pub poll_interval_secs: u64,
+}
+
+fn default_jvm_poll_interval() -> u64 {
+    10
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ErrorPatterns {
-    pub critical: Vec<String>,
-    pub errors: Vec<String>,
-    pub warnings: Vec<String>,
+    pub critical: Vec<PatternEntry>,
+    pub errors: Vec<PatternEntry>,
+    pub warnings: Vec<PatternEntry>,
+    /// Lines matching one of these never trigger a restart, e.g. known
+    /// harmless exceptions
+    #[serde(default)]
+    pub suppress: Vec<PatternEntry>,
+    /// Stdout-only lists, checked before the global ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdout_critical: Option<Vec<PatternEntry>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdout_errors: Option<Vec<PatternEntry>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdout_warnings: Option<Vec<PatternEntry>>,
+    /// Stderr-only lists, e.g. JVM crash markers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stderr_critical: Option<Vec<PatternEntry>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stderr_errors: Option<Vec<PatternEntry>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stderr_warnings: Option<Vec<PatternEntry>>,
+}
+
+impl ErrorPatterns {
+    pub fn all(&self) -> impl Iterator<Item = &PatternEntry> {
+        let source_specific = [
+            &self.stdout_critical,
+            &self.stdout_errors,
+            &self.stdout_warnings,
+            &self.stderr_critical,
+            &self.stderr_errors,
+            &self.stderr_warnings,
+        ];
+        self.critical
+            .iter()
+            .chain(&self.errors)
+            .chain(&self.warnings)
+            .chain(&self.suppress)
+            .chain(source_specific.into_iter().flatten().flatten())
+    }
+
+    /// Lists applied to lines from `source` in the order they are checked:
+    /// the source-specific ones, then the global ones, each most severe
+    /// first. Watcher lines use the stdout lists.
+    pub fn levels_for(&self, source: LogSource) -> Vec<(LogLevel, &[PatternEntry])> {
+        let (critical, errors, warnings) = match source {
+            LogSource::Stderr => (
+                &self.stderr_critical,
+                &self.stderr_errors,
+                &self.stderr_warnings,
+            ),
+            LogSource::Server | LogSource::Watcher => (
+                &self.stdout_critical,
+                &self.stdout_errors,
+                &self.stdout_warnings,
+            ),
+        };
+        let specific = [
+            (LogLevel::Critical, critical),
+            (LogLevel::Error, errors),
+            (LogLevel::Warning, warnings),
+        ];
+        specific
+            .into_iter()
+            .filter_map(|(level, list)| Some((level, list.as_deref()?)))
+            .chain([
+                (LogLevel::Critical, self.critical.as_slice()),
+                (LogLevel::Error, self.errors.as_slice()),
+                (LogLevel::Warning, self.warnings.as_slice()),
+            ])
+            .collect()
+    }
+}
+
+/// One error pattern. A plain string in the config is a substring match
+/// and is written back the same way.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(from = "PatternEntryRepr", into = "PatternEntryRepr")]
+pub struct PatternEntry {
+    pub pattern: String,
+    pub is_regex: bool,
+    /// Store the regex's named groups on the log entry
+    pub extract_fields: bool,
+}
+
+impl PatternEntry {
+    pub fn plain(pattern: &str) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            is_regex: false,
+            extract_fields: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum PatternEntryRepr {
+    Plain(String),
+    Entry {
+        pattern: String,
+        #[serde(default)]
+        is_regex: bool,
+        #[serde(default)]
+        extract_fields: bool,
+    },
+}
+
+impl From<PatternEntryRepr> for PatternEntry {
+    fn from(repr: PatternEntryRepr) -> Self {
+        match repr {
+            PatternEntryRepr::Plain(pattern) => Self::plain(&pattern),
+            PatternEntryRepr::Entry {
+                pattern,
+                is_regex,
+                extract_fields,
+            } => Self {
+                pattern,
+                is_regex,
+                extract_fields,
+            },
+        }
+    }
+}
+
+impl From<PatternEntry> for PatternEntryRepr {
+    fn from(entry: PatternEntry) -> Self {
+        if entry.is_regex || entry.extract_fields {
+            PatternEntryRepr::Entry {
+                pattern: entry.pattern,
+                is_regex: entry.is_regex,
+                extract_fields: entry.extract_fields,
+            }
+        } else {
+            PatternEntryRepr::Plain(entry.pattern)
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LogCategoryConfig {
+    pub name: String,
+    /// Regex matched against the log message
+    pub pattern: String,
+    /// CSS colour for dashboard widgets
+    pub color: String,
+}
+
+fn log_category(name: &str, pattern: &str, color: &str) -> LogCategoryConfig {
+    LogCategoryConfig {
+        name: name.to_string(),
+        pattern: pattern.to_string(),
+        color: color.to_string(),
+    }
+}
+
+/// Defaults matching common Minecraft and Hytale server output
+fn default_log_categories() -> Vec<LogCategoryConfig> {
+    vec![
+        log_category(
+            "PlayerJoin",
+            r"(?i)joined the game|player \S+ (connected|joined)",
+            "#22c55e",
+        ),
+        log_category(
+            "PlayerLeave",
+            r"(?i)left the game|player \S+ (disconnected|left)|lost connection",
+            "#f59e0b",
+        ),
+        log_category(
+            "WorldSave",
+            r"(?i)saving (the )?(game|world|chunks)|saved the (game|world)|world saved",
+            "#3b82f6",
+        ),
+        log_category(
+            "ChunkError",
+            r"(?i)chunk.*(error|exception|failed|corrupt)",
+            "#ef4444",
+        ),
+    ]
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct RestartConfig {
     pub critical: bool,
     pub errors: bool,
@@ -63,7 +731,7 @@ pub struct RestartConfig {
     pub process_exit: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct BackupConfig {
     #[serde(default = "default_backup_enabled")]
     pub enabled: bool,
@@ -73,8 +741,61 @@ pub struct BackupConfig {
     pub backup_folder: String,
     #[serde(default = "default_backup_interval")]
     pub interval_hours: u64,
+    /// Globs relative to `source_folder` that are left out, e.g. `logs/**`
+    #[serde(default)]
+    pub exclude_patterns: Vec<String>,
+    /// Delete backups older than this; 0 turns the age limit off
     #[serde(default = "default_backup_retention")]
     pub retention_days: u64,
+    /// Keep at most this many backups (at least 1). Applies together with
+    /// `retention_days`; set that to 0 for count-only retention.
+    #[serde(default)]
+    pub retention_count: Option<u32>,
+    /// Create and verify a throwaway backup in a temp dir on startup
+    #[serde(default)]
+    pub test_on_startup: bool,
+    /// Pipe the tar stream to a separate compressor thread
+    #[serde(default)]
+    pub stream_to_pipe: bool,
+    /// Archive name prefix; falls back to the identity name, then `backup`
+    #[serde(default)]
+    pub archive_prefix: Option<String>,
+    /// xz level 1-9; `None` adapts the level to how long backups take
+    #[serde(default)]
+    pub compression_level: Option<u8>,
+    /// Defer scheduled backups while the server's CPU is above this
+    #[serde(default)]
+    pub skip_when_cpu_above_percent: Option<f32>,
+    /// How long to wait before retrying a deferred backup
+    #[serde(default = "default_backup_check_interval")]
+    pub check_interval_secs: u64,
+    /// Back up anyway once backups have been deferred this long
+    #[serde(default = "default_backup_max_skip_hours")]
+    pub max_skip_hours: u64,
+    /// Refuse to start a backup that would not fit on the backup partition
+    #[serde(default = "default_backup_disk_space_check")]
+    pub disk_space_check_enabled: bool,
+    /// Read every new archive back once it is written; slow storage may want this off
+    #[serde(default = "default_backup_verify")]
+    pub backup_verify: bool,
+    /// Encrypt archives with this age passphrase; they are kept as `.tar.xz.age`
+    #[serde(default)]
+    pub encryption_passphrase: Option<String>,
+    /// Most entries `GET /api/backup/:filename/contents` lists before truncating
+    #[serde(default = "default_backup_max_manifest_entries")]
+    pub max_manifest_entries: usize,
+    /// Upload every new backup to an S3-compatible bucket
+    #[serde(default)]
+    pub remote_s3: Option<S3Config>,
+    /// Shell command run before archiving; a non-zero exit skips the backup
+    #[serde(default)]
+    pub pre_backup_command: Option<String>,
+    /// Shell command run after a backup, with `BACKUP_FILE` (full path) and
+    /// `BACKUP_SIZE_BYTES` set
+    #[serde(default)]
+    pub post_backup_command: Option<String>,
+    #[serde(default = "default_backup_hook_timeout")]
+    pub hook_timeout_secs: u64,
 }
 
 fn default_backup_enabled() -> bool { true }
@@ -82,6 +803,12 @@ fn default_backup_source() -> String { "universe".to_string() }
 fn default_backup_dest() -> String { "backups".to_string() }
 fn default_backup_interval() -> u64 { 4 }
 fn default_backup_retention() -> u64 { 10 }
+fn default_backup_check_interval() -> u64 { 300 }
+fn default_backup_max_skip_hours() -> u64 { 6 }
+fn default_backup_disk_space_check() -> bool { true }
+fn default_backup_verify() -> bool { true }
+fn default_backup_max_manifest_entries() -> usize { 10_000 }
+fn default_backup_hook_timeout() -> u64 { 60 }
 
 impl Default for BackupConfig {
     fn default() -> Self {
@@ -91,11 +818,49 @@ impl Default for BackupConfig {
             backup_folder: default_backup_dest(),
             interval_hours: default_backup_interval(),
             retention_days: default_backup_retention(),
+            retention_count: None,
+            exclude_patterns: Vec::new(),
+            test_on_startup: false,
+            stream_to_pipe: false,
+            archive_prefix: None,
+            compression_level: None,
+            skip_when_cpu_above_percent: None,
+            check_interval_secs: default_backup_check_interval(),
+            max_skip_hours: default_backup_max_skip_hours(),
+            disk_space_check_enabled: default_backup_disk_space_check(),
+            backup_verify: default_backup_verify(),
+            encryption_passphrase: None,
+            max_manifest_entries: default_backup_max_manifest_entries(),
+            remote_s3: None,
+            pre_backup_command: None,
+            post_backup_command: None,
+            hook_timeout_secs: default_backup_hook_timeout(),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct S3Config {
+    /// e.g. `https://s3.eu-central-1.amazonaws.com` or a MinIO URL
+    pub endpoint: String,
+    pub bucket: String,
+    pub access_key: String,
+    pub secret_key: String,
+    /// Key prefix inside the bucket, e.g. `hytale/`
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default = "default_s3_region")]
+    pub region: String,
+    /// Delete the local archive once the upload succeeded
+    #[serde(default)]
+    pub remote_only: bool,
+}
+
+fn default_s3_region() -> String {
+    "us-east-1".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct WebConfig {
     #[serde(default = "default_web_enabled")]
     pub enabled: bool,
@@ -105,6 +870,9 @@ pub struct WebConfig {
     pub host: String,
     #[serde(default)]
     pub auth_token: Option<String>,
+    /// Required for `GET /api/config/full`; unset disables that endpoint
+    #[serde(default)]
+    pub superadmin_token: Option<String>,
 }
 
 fn default_web_enabled() -> bool { true }
@@ -118,11 +886,75 @@ impl Default for WebConfig {
             port: default_web_port(),
             host: default_web_host(),
             auth_token: None,
+            superadmin_token: None,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Settings for the config file itself
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct ConfigConfig {
+    /// Watch the config file and apply changes without a restart
+    #[serde(default)]
+    pub auto_reload: bool,
+}
+
+/// In-memory state kept by the watcher
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct StateConfig {
+    /// Log entries kept for the dashboard and `GET /api/logs`
+    #[serde(default = "default_max_logs")]
+    pub max_logs: usize,
+    /// Collapse identical consecutive lines, see `log_dedup_window_secs`
+    #[serde(default = "default_log_dedup_enabled")]
+    pub log_dedup_enabled: bool,
+    /// Identical consecutive lines within this many seconds are collapsed
+    /// into one entry with a repeat count; 0 disables
+    #[serde(default = "default_log_dedup_window_secs")]
+    pub log_dedup_window_secs: u64,
+}
+
+fn default_log_dedup_enabled() -> bool {
+    true
+}
+
+fn default_log_dedup_window_secs() -> u64 {
+    5
+}
+
+fn default_max_logs() -> usize {
+    1000
+}
+
+impl Default for StateConfig {
+    fn default() -> Self {
+        Self {
+            max_logs: default_max_logs(),
+            log_dedup_enabled: default_log_dedup_enabled(),
+            log_dedup_window_secs: default_log_dedup_window_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WatcherLogFileConfig {
+    pub path: String,
+    #[serde(default = "default_watcher_log_max_size_mb")]
+    pub max_size_mb: u64,
+    /// Rotated files kept as `<path>.1.gz`, `<path>.2.gz`, ...
+    #[serde(default = "default_watcher_log_max_files")]
+    pub max_files: u32,
+}
+
+fn default_watcher_log_max_size_mb() -> u64 {
+    10
+}
+
+fn default_watcher_log_max_files() -> u32 {
+    5
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct RemoteServer {
     pub id: String,
     pub name: String,
@@ -131,6 +963,98 @@ pub struct RemoteServer {
     pub token: Option<String>,
 }
 
+/// Placeholder for secrets in API responses
+pub const REDACTED_SECRET: &str = "***";
+
+/// One field that differs between two configs
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigDiff {
+    /// Dotted path such as `server.max_restarts` or `remote_servers[0].url`
+    pub field_path: String,
+    pub old_value: serde_json::Value,
+    pub new_value: serde_json::Value,
+}
+
+/// Recursively collect (field path, JSON pointer) of every leaf that differs.
+/// Arrays of different length are reported as a whole.
+fn diff_values(
+    path: &str,
+    pointer: &str,
+    old: &serde_json::Value,
+    new: &serde_json::Value,
+    out: &mut Vec<(String, String)>,
+) {
+    use serde_json::Value;
+
+    match (old, new) {
+        (Value::Object(a), Value::Object(b)) => {
+            let keys: std::collections::BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+            for key in keys {
+                let child_path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                let child_pointer =
+                    format!("{}/{}", pointer, key.replace('~', "~0").replace('/', "~1"));
+                diff_values(
+                    &child_path,
+                    &child_pointer,
+                    a.get(key).unwrap_or(&Value::Null),
+                    b.get(key).unwrap_or(&Value::Null),
+                    out,
+                );
+            }
+        }
+        (Value::Array(a), Value::Array(b)) if a.len() == b.len() => {
+            for (i, (x, y)) in a.iter().zip(b).enumerate() {
+                diff_values(
+                    &format!("{}[{}]", path, i),
+                    &format!("{}/{}", pointer, i),
+                    x,
+                    y,
+                    out,
+                );
+            }
+        }
+        _ if old != new => out.push((path.to_string(), pointer.to_string())),
+        _ => {}
+    }
+}
+
+fn redact(value: &mut String) {
+    if !value.is_empty() {
+        *value = REDACTED_SECRET.to_string();
+    }
+}
+
+fn redact_opt(value: &mut Option<String>) {
+    if let Some(v) = value {
+        redact(v);
+    }
+}
+
+fn restore(value: &mut String, current: &str) {
+    if value == REDACTED_SECRET {
+        *value = current.to_string();
+    }
+}
+
+fn restore_opt(value: &mut Option<String>, current: &Option<String>) {
+    if value.as_deref() == Some(REDACTED_SECRET) {
+        value.clone_from(current);
+    }
+}
+
+/// `server.max_restarts, web.port` for log messages
+pub fn changed_fields(changes: &[ConfigDiff]) -> String {
+    changes
+        .iter()
+        .map(|c| c.field_path.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 impl Config {
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
         let content = fs::read_to_string(path)?;
@@ -144,8 +1068,267 @@ impl Config {
         Ok(())
     }
 
+    /// Copy of the config with every secret replaced by `***`,
+    /// safe to hand out over the API
+    pub fn secrets_redacted(&self) -> Config {
+        let mut config = self.clone();
+        redact(&mut config.telegram.token);
+        redact(&mut config.telegram.chat_id);
+        redact_opt(&mut config.web.auth_token);
+        redact_opt(&mut config.web.superadmin_token);
+        redact_opt(&mut config.backup.encryption_passphrase);
+        if let Some(s3) = &mut config.backup.remote_s3 {
+            redact(&mut s3.secret_key);
+        }
+        config.server.env_vars.values_mut().for_each(redact);
+        for remote in &mut config.remote_servers {
+            redact_opt(&mut remote.token);
+        }
+        config
+    }
+
+    /// Fields changed from `self` (old) to `other` (new). Secret values
+    /// show up as `***`, but a changed secret is still listed.
+    pub fn diff(&self, other: &Config) -> Vec<ConfigDiff> {
+        let to_value = |c: &Config| serde_json::to_value(c).unwrap_or_default();
+
+        let mut changed = Vec::new();
+        diff_values("", "", &to_value(self), &to_value(other), &mut changed);
+
+        let old_redacted = to_value(&self.secrets_redacted());
+        let new_redacted = to_value(&other.secrets_redacted());
+        changed
+            .into_iter()
+            .map(|(field_path, pointer)| ConfigDiff {
+                field_path,
+                old_value: old_redacted.pointer(&pointer).cloned().unwrap_or_default(),
+                new_value: new_redacted.pointer(&pointer).cloned().unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    /// Undo `secrets_redacted` for a config sent back by a client:
+    /// any secret still set to `***` keeps its value from `current`.
+    pub fn restore_secrets(&mut self, current: &Config) {
+        restore(&mut self.telegram.token, &current.telegram.token);
+        restore(&mut self.telegram.chat_id, &current.telegram.chat_id);
+        restore_opt(&mut self.web.auth_token, &current.web.auth_token);
+        restore_opt(
+            &mut self.web.superadmin_token,
+            &current.web.superadmin_token,
+        );
+        restore_opt(
+            &mut self.backup.encryption_passphrase,
+            &current.backup.encryption_passphrase,
+        );
+        if let (Some(s3), Some(old)) = (&mut self.backup.remote_s3, &current.backup.remote_s3) {
+            restore(&mut s3.secret_key, &old.secret_key);
+        }
+        for (name, value) in &mut self.server.env_vars {
+            if let Some(old) = current.server.env_vars.get(name) {
+                restore(value, old);
+            }
+        }
+        for remote in &mut self.remote_servers {
+            if let Some(old) = current.remote_servers.iter().find(|r| r.id == remote.id) {
+                restore_opt(&mut remote.token, &old.token);
+            }
+        }
+    }
+
+    /// Basic sanity checks before a config is applied
+    pub fn validate(&self) -> Result<(), String> {
+        if self.server.executable.trim().is_empty() {
+            return Err("server.executable is empty".to_string());
+        }
+        if self.server.auto_restart_interval_minutes == Some(0) {
+            return Err("server.auto_restart_interval_minutes must be > 0".to_string());
+        }
+        for window in &self.server.maintenance_windows {
+            if window.times().is_none() {
+                return Err(format!(
+                    "server.maintenance_windows: invalid time in {}-{}, expected HH:MM",
+                    window.start, window.end
+                ));
+            }
+        }
+        for entry in self.error_patterns.all().filter(|e| e.is_regex) {
+            if let Err(e) = regex::Regex::new(&entry.pattern) {
+                return Err(format!(
+                    "error_patterns: invalid regex {:?}: {}",
+                    entry.pattern, e
+                ));
+            }
+        }
+        for category in &self.log_categories {
+            if let Err(e) = regex::Regex::new(&category.pattern) {
+                return Err(format!(
+                    "log_categories: invalid pattern for {}: {}",
+                    category.name, e
+                ));
+            }
+        }
+        for pattern in &self.backup.exclude_patterns {
+            if let Err(e) = glob::Pattern::new(pattern) {
+                return Err(format!(
+                    "backup.exclude_patterns: invalid pattern {}: {}",
+                    pattern, e
+                ));
+            }
+        }
+        if let Some(s3) = &self.backup.remote_s3 {
+            if s3.endpoint.trim().is_empty() || s3.bucket.trim().is_empty() {
+                return Err("backup.remote_s3 needs an endpoint and a bucket".to_string());
+            }
+        }
+        if self.state.max_logs == 0 {
+            return Err("state.max_logs must be > 0".to_string());
+        }
+        if self.resources.check_interval_seconds == 0 {
+            return Err("resources.check_interval_seconds must be > 0".to_string());
+        }
+        if let Some(rate) = self.server.log_sample_rate {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err("server.log_sample_rate must be between 0.0 and 1.0".to_string());
+            }
+        }
+        if self.backup.enabled && self.backup.interval_hours == 0 {
+            return Err("backup.interval_hours must be > 0".to_string());
+        }
+        if let Some(ref log_file) = self.server.log_file {
+            if let LogRotationPolicy::MaxSize { max_size_mb }
+            | LogRotationPolicy::Both { max_size_mb, .. } = log_file.rotation
+            {
+                if max_size_mb == 0 {
+                    return Err("server.log_file.rotation.max_size_mb must be > 0".to_string());
+                }
+            }
+        }
+        if self.log_file.as_ref().is_some_and(|f| f.max_size_mb == 0) {
+            return Err("log_file.max_size_mb must be > 0".to_string());
+        }
+        if self.backup.retention_count == Some(0) {
+            return Err("backup.retention_count must be > 0".to_string());
+        }
+        if let Some(level) = self.backup.compression_level {
+            if !(1..=9).contains(&level) {
+                return Err("backup.compression_level must be between 1 and 9".to_string());
+            }
+        }
+        if self.web.enabled && self.web.port == 0 {
+            return Err("web.port must be > 0".to_string());
+        }
+        Ok(())
+    }
+
+    /// Watch the config file and swap in valid changes.
+    /// Events are debounced by 500 ms; invalid files are logged and ignored.
+    pub fn watch_and_reload(
+        path: &str,
+        config: Arc<RwLock<Config>>,
+        state: Arc<AppState>,
+        mut shutdown_rx: watch::Receiver<bool>,
+    ) -> tokio::task::JoinHandle<()> {
+        let path = PathBuf::from(path);
+
+        tokio::spawn(async move {
+            let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+            let file_name = path.file_name().map(|n| n.to_os_string());
+
+            let handler = move |res: notify::Result<notify::Event>| {
+                if let Ok(event) = res {
+                    let _ = event_tx.send(event);
+                }
+            };
+            let mut watcher = match notify::recommended_watcher(handler) {
+                Ok(w) => w,
+                Err(e) => {
+                    tracing::error!("Failed to create config watcher: {}", e);
+                    return;
+                }
+            };
+
+            // Watch the parent dir so editors that replace the file
+            // (and Kubernetes ConfigMap symlink swaps) are picked up
+            let dir = match path.parent() {
+                Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+                _ => PathBuf::from("."),
+            };
+            if let Err(e) = watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                tracing::error!("Failed to watch {:?}: {}", dir, e);
+                return;
+            }
+
+            tracing::info!("Watching {:?} for changes", path);
+
+            loop {
+                let event = tokio::select! {
+                    ev = event_rx.recv() => match ev {
+                        Some(ev) => ev,
+                        None => break,
+                    },
+                    _ = shutdown_rx.changed() => {
+                        if *shutdown_rx.borrow() {
+                            break;
+                        }
+                        continue;
+                    }
+                };
+
+                let relevant = matches!(event.kind, EventKind::Modify(_) | EventKind::Create(_))
+                    && event.paths.iter().any(|p| {
+                        let name = p.file_name();
+                        name == file_name.as_deref()
+                            || name.is_some_and(|n| n.to_string_lossy().starts_with(".."))
+                    });
+                if !relevant {
+                    continue;
+                }
+
+                // Debounce: wait until no events for 500 ms
+                let debounce = Duration::from_millis(500);
+                while let Ok(Some(_)) = timeout(debounce, event_rx.recv()).await {}
+
+                let loaded = Config::load(&path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|c| c.validate().map(|_| c));
+
+                match loaded {
+                    Ok(new_config) => {
+                        let changes = config.read().diff(&new_config);
+                        if changes.is_empty() {
+                            continue;
+                        }
+                        state.set_stats_interval_secs(new_config.resources.check_interval_seconds);
+                        state.set_max_logs(new_config.state.max_logs);
+                        state.set_log_dedup_enabled(new_config.state.log_dedup_enabled);
+                        state.set_log_dedup_window_secs(new_config.state.log_dedup_window_secs);
+                        *config.write() = new_config;
+                        state.add_watcher_log(format!(
+                            "Config reloaded from {:?}, changed: {}",
+                            path,
+                            changed_fields(&changes)
+                        ));
+                        state.emit(StateChangeEvent::ConfigReloaded);
+                    }
+                    Err(e) => {
+                        tracing::error!("Config reload failed, keeping old config: {}", e);
+                        state.add_log(
+                            LogLevel::Error,
+                            LogSource::Watcher,
+                            format!("Config reload failed: {}", e),
+                        );
+                    }
+                }
+            }
+
+            tracing::info!("Config watcher stopped");
+        })
+    }
+
     pub fn default_config() -> Self {
         Self {
+            identity: IdentityConfig::default(),
             server: ServerConfig {
                 executable: "java".to_string(),
                 arguments: vec![
@@ -155,29 +1338,89 @@ impl Config {
                     "server.jar".to_string(),
                 ],
                 working_directory: None,
-                restart_delay_seconds: 30,
+                env_vars: HashMap::new(),
+                clear_env: false,
+                error_restart_delay_secs: 30,
+                restart_backoff_multiplier: default_restart_backoff_multiplier(),
+                restart_max_delay_secs: default_restart_max_delay(),
+                manual_restart_delay_secs: 0,
+                spawn_retry_count: default_spawn_retry_count(),
+                spawn_retry_delay_ms: default_spawn_retry_delay(),
                 max_restarts: None,
+                restart_count_reset_after_uptime_secs: None,
                 auto_restart_hourly: false,
+                auto_restart_interval_minutes: None,
                 restart_warning_message: default_restart_warning_message(),
+                restart_warning_minutes: default_restart_warning_minutes(),
+                pre_start_delay_secs: 0,
+                pre_start_checks: vec![],
+                heap_dump_retention_days: default_heap_dump_retention(),
+                stdin_write_timeout_secs: default_stdin_write_timeout(),
+                stdin_command_terminator: StdinTerminator::default(),
+                encoding: default_encoding(),
+                max_runtime_secs: None,
+                log_sample_rate: None,
+                startup_pattern: None,
+                max_startup_latency_secs: None,
+                server_ready_timeout_secs: None,
+                graceful_stop_command: None,
+                graceful_stop_timeout_secs: default_graceful_stop_timeout(),
+                pre_restart_command: None,
+                post_start_command: None,
+                server_port: None,
+                interpolate_args: false,
+                stdin_fifo_path: None,
+                stdout_fifo_path: None,
+                allowed_signals: default_allowed_signals(),
+                log_file: None,
+                strip_ansi: default_strip_ansi(),
+                assemble_stack_traces: false,
+                maintenance_windows: Vec::new(),
             },
             telegram: TelegramConfig {
                 enabled: false,
                 token: "YOUR_BOT_TOKEN".to_string(),
                 chat_id: "YOUR_CHAT_ID".to_string(),
+                verbose_notifications: false,
+                queue_size: default_telegram_queue_size(),
+                max_retries: default_telegram_max_retries(),
             },
             resources: ResourceConfig {
                 cpu_threshold_percent: 90.0,
                 memory_threshold_mb: 4096,
                 check_interval_seconds: 5,
+                detailed_memory_stats: false,
+                jvm_metrics: None,
+                disk_io_threshold_mb_per_sec: None,
+                max_connections_warning: None,
+                stats_history_size: default_stats_history_size(),
+                disk_threshold_percent: default_disk_threshold_percent(),
+                fd_threshold: None,
+                thread_threshold: None,
+                cpu_threshold_sustained_seconds: default_cpu_threshold_sustained_seconds(),
+                cpu_alert_cooldown_seconds: default_cpu_alert_cooldown_seconds(),
+                cpu_threshold_action: None,
+                memory_leak_window_minutes: default_memory_leak_window_minutes(),
+                memory_leak_threshold_mb_per_hour: default_memory_leak_threshold_mb_per_hour(),
             },
             error_patterns: ErrorPatterns {
                 critical: vec![
-                    "FATAL".to_string(),
-                    "Server crashed".to_string(),
-                    "OutOfMemoryError".to_string(),
+                    PatternEntry::plain("FATAL"),
+                    PatternEntry::plain("Server crashed"),
+                    PatternEntry::plain("OutOfMemoryError"),
+                ],
+                errors: vec![
+                    PatternEntry::plain("ERROR"),
+                    PatternEntry::plain("Exception"),
                 ],
-                errors: vec!["ERROR".to_string(), "Exception".to_string()],
-                warnings: vec!["WARN".to_string(), "Warning".to_string()],
+                warnings: vec![PatternEntry::plain("WARN"), PatternEntry::plain("Warning")],
+                suppress: Vec::new(),
+                stdout_critical: None,
+                stdout_errors: None,
+                stdout_warnings: None,
+                stderr_critical: None,
+                stderr_errors: None,
+                stderr_warnings: None,
             },
             restart_on: RestartConfig {
                 critical: true,
@@ -188,6 +1431,96 @@ impl Config {
             backup: BackupConfig::default(),
             web: WebConfig::default(),
             remote_servers: vec![],
+            config: ConfigConfig::default(),
+            state: StateConfig::default(),
+            log_file: None,
+            log_categories: default_log_categories(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_rejects_zero_retention_count() {
+        let mut config = Config::default_config();
+        config.backup.retention_count = Some(0);
+        assert!(config.validate().is_err());
+        config.backup.retention_count = Some(1);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_zero_log_file_size() {
+        let mut config = Config::default_config();
+        config.server.log_file = Some(LogFileConfig {
+            path: "server.log".to_string(),
+            rotation: LogRotationPolicy::Both {
+                max_size_mb: 0,
+                rotate_daily: true,
+            },
+        });
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn validate_rejects_zero_watcher_log_size() {
+        let mut config = Config::default_config();
+        config.log_file = Some(WatcherLogFileConfig {
+            path: "watcher.log".to_string(),
+            max_size_mb: 0,
+            max_files: 5,
+        });
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn diff_finds_nested_fields() {
+        let old = Config::default_config();
+        assert!(old.diff(&old.clone()).is_empty());
+
+        let mut new = old.clone();
+        new.server.max_restarts = Some(5);
+        new.server.arguments.push("--extra".to_string());
+        new.telegram.token = "new-token".to_string();
+
+        let diff = old.diff(&new);
+        assert_eq!(
+            diff.iter()
+                .map(|d| d.field_path.as_str())
+                .collect::<Vec<_>>(),
+            ["server.arguments", "server.max_restarts", "telegram.token"]
+        );
+        assert_eq!(diff[1].old_value, serde_json::Value::Null);
+        assert_eq!(diff[1].new_value, serde_json::json!(5));
+        // Secrets are listed but never shown
+        assert_eq!(diff[2].new_value, serde_json::json!(REDACTED_SECRET));
+
+        // Same-length lists are compared per element
+        let mut renamed = old.clone();
+        renamed.server.arguments[0] = "--renamed".to_string();
+        let diff = old.diff(&renamed);
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff[0].field_path, "server.arguments[0]");
+        assert_eq!(diff[0].new_value, serde_json::json!("--renamed"));
+    }
+
+    #[test]
+    fn server_name_is_read_into_identity() {
+        let mut value = serde_json::to_value(Config::default_config()).unwrap();
+        let object = value.as_object_mut().unwrap();
+        object.remove("identity");
+        object.insert("server_name".to_string(), serde_json::json!("Lobby"));
+
+        let config: Config = serde_json::from_value(value).unwrap();
+        assert_eq!(config.identity.name, "Lobby");
+        assert!(config.identity.tags.is_empty());
+
+        // Saved back under the new name
+        let saved = serde_json::to_value(&config).unwrap();
+        assert_eq!(saved["identity"]["name"], "Lobby");
+        assert!(saved.get("server_name").is_none());
+    }
+}
@@ -5,16 +5,22 @@ mod web;
 use config::Config;
 use parking_lot::RwLock;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::{mpsc, watch};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use watcher::{
-    backup::BackupManager,
-    process::{ProcessCommand, ProcessManager},
-    state::AppState,
+    backup::{archive_prefix, BackupManager},
+    heapdump::{heap_dump_dirs, HeapDumpCleanup},
+    logsink::{run_log_sink, LOG_SINK_CAPACITY},
+    process::{command_channel, track_java_version, ProcessManager},
+    state::{AppState, AppStateOptions},
     stats::StatsCollector,
     telegram::TelegramClient,
 };
 
+/// Read at startup and written back by config changes from the API
+const CONFIG_PATH: &str = "config-watcher.json";
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize logging
@@ -26,15 +32,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .init();
 
     // Load config
-    let config = match Config::load("config-watcher.json") {
+    let config = match Config::load(CONFIG_PATH) {
         Ok(cfg) => {
-            tracing::info!("Config loaded from config-watcher.json");
+            tracing::info!("Config loaded from {}", CONFIG_PATH);
             cfg
         }
         Err(e) => {
             tracing::warn!("Failed to load config: {}, using defaults", e);
             let default = Config::default_config();
-            if let Err(e) = default.save("config-watcher.json") {
+            if let Err(e) = default.save(CONFIG_PATH) {
                 tracing::error!("Failed to save default config: {}", e);
             }
             default
@@ -44,19 +50,36 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let config = Arc::new(RwLock::new(config));
 
     // Create shared state
-    let app_state = AppState::new();
+    let app_state = AppState::new_with_options(AppStateOptions {
+        max_logs: config.read().state.max_logs,
+        log_dedup_enabled: config.read().state.log_dedup_enabled,
+        log_dedup_window_secs: config.read().state.log_dedup_window_secs,
+        stats_history_size: config.read().resources.stats_history_size,
+        ..AppStateOptions::default()
+    });
 
     // Shutdown signal
     let (shutdown_tx, shutdown_rx) = watch::channel(false);
 
+    // Persist watcher log entries
+    let log_sink_handle = config.read().log_file.clone().map(|log_file| {
+        let (log_tx, log_rx) = mpsc::channel(LOG_SINK_CAPACITY);
+        app_state.set_log_sink(log_tx);
+        tokio::spawn(run_log_sink(log_file, log_rx, shutdown_rx.clone()))
+    });
+
     // Process command channel
-    let (process_tx, process_rx) = mpsc::channel::<ProcessCommand>(32);
+    let (process_tx, process_rx) = command_channel(32);
+
+    // On-demand backups from the API
+    let (backup_tx, backup_rx) = mpsc::channel(4);
 
     // Telegram client
     let telegram = {
         let cfg = config.read();
         if cfg.telegram.enabled {
-            let client = TelegramClient::new(cfg.telegram.clone());
+            let client = TelegramClient::new(cfg.telegram.clone(), cfg.identity.name.clone())
+                .with_maintenance_flag(app_state.maintenance_flag());
             Some(client)
         } else {
             None
@@ -69,7 +92,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // Spawn stats collector
-    let stats_collector = StatsCollector::new(Arc::clone(&app_state), shutdown_rx.clone());
+    let stats_collector = {
+        let cfg = config.read();
+        StatsCollector::new(
+            &cfg.resources,
+            Duration::from_secs(cfg.resources.check_interval_seconds),
+            cfg.server.server_port,
+            cfg.server.working_directory.clone(),
+            Arc::clone(&app_state),
+            telegram.clone(),
+            shutdown_rx.clone(),
+        )
+        .with_commands(process_tx.clone())
+    };
     let stats_handle = tokio::spawn(stats_collector.run());
 
     // Spawn backup manager
@@ -77,32 +112,59 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         let cfg = config.read();
         BackupManager::new(
             cfg.backup.clone(),
+            archive_prefix(&cfg.backup, &cfg.identity),
+            cfg.server
+                .heap_dump_retention_days
+                .map(|retention_days| HeapDumpCleanup {
+                    dirs: heap_dump_dirs(&cfg.server),
+                    retention_days,
+                }),
             cfg.server.working_directory.clone(),
             Arc::clone(&app_state),
             telegram.clone(),
             shutdown_rx.clone(),
         )
+        .with_commands(backup_rx)
     };
     let backup_handle = tokio::spawn(backup_manager.run());
 
     // Spawn process manager
-    let process_manager = {
-        let cfg = config.read().clone();
-        ProcessManager::new(
-            cfg,
+    let process_manager = ProcessManager::new(
+        Arc::clone(&config),
+        Arc::clone(&app_state),
+        telegram.clone(),
+        shutdown_rx.clone(),
+        process_rx,
+    );
+    let process_handle = tokio::spawn(process_manager.run());
+
+    // Re-probe the Java version when a reload changes the executable
+    tokio::spawn(track_java_version(
+        Arc::clone(&config),
+        Arc::clone(&app_state),
+        shutdown_rx.clone(),
+    ));
+
+    // Watch config file for changes
+    let config_watch_handle = if config.read().config.auto_reload {
+        Some(Config::watch_and_reload(
+            CONFIG_PATH,
+            Arc::clone(&config),
             Arc::clone(&app_state),
-            telegram.clone(),
             shutdown_rx.clone(),
-            process_rx,
-        )
+        ))
+    } else {
+        None
     };
-    let process_handle = tokio::spawn(process_manager.run());
 
     // Spawn web server
     let web_handle = tokio::spawn(web::start_server(
         Arc::clone(&config),
+        CONFIG_PATH.into(),
         Arc::clone(&app_state),
         process_tx.clone(),
+        backup_tx,
+        telegram.clone(),
         shutdown_rx.clone(),
     ));
 
@@ -115,6 +177,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Wait for all tasks
     let _ = tokio::join!(stats_handle, backup_handle, process_handle, web_handle);
+    if let Some(handle) = config_watch_handle {
+        let _ = handle.await;
+    }
+    if let Some(handle) = log_sink_handle {
+        let _ = handle.await;
+    }
 
     if let Some(ref tg) = telegram {
         tg.notify(watcher::telegram::NotifyType::Stop, "Server Watcher stopped")
@@ -0,0 +1,55 @@
+use crate::watcher::state::{RestartReason, RestartRecord};
+use chrono::Timelike;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Hours whose crash count is this many times the hourly average
+pub const SUSPICIOUS_CORRELATION_SCORE: f64 = 2.0;
+
+/// Crashes that fell into one hour of the day
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CrashCorrelation {
+    pub hour_of_day: u8,
+    pub crash_count: u32,
+    pub average_crash_count: f64,
+    /// `crash_count / average_crash_count`
+    pub correlation_score: f64,
+    pub suspicious: bool,
+}
+
+/// Restarts the watcher did not ask for
+fn is_crash(record: &RestartRecord) -> bool {
+    matches!(
+        record.restart_reason,
+        RestartReason::Error | RestartReason::ProcessExit
+    )
+}
+
+/// Bin crashes by local hour of day. Hours that see far more crashes than
+/// average usually line up with a cron job or some other scheduled task.
+pub fn crash_correlation(history: &[RestartRecord]) -> Vec<CrashCorrelation> {
+    let mut counts = [0u32; 24];
+    for record in history.iter().filter(|r| is_crash(r)) {
+        counts[record.timestamp.hour() as usize] += 1;
+    }
+
+    let average = counts.iter().sum::<u32>() as f64 / counts.len() as f64;
+    counts
+        .iter()
+        .enumerate()
+        .map(|(hour, &count)| {
+            let score = if average > 0.0 {
+                count as f64 / average
+            } else {
+                0.0
+            };
+            CrashCorrelation {
+                hour_of_day: hour as u8,
+                crash_count: count,
+                average_crash_count: average,
+                correlation_score: score,
+                suspicious: score > SUSPICIOUS_CORRELATION_SCORE,
+            }
+        })
+        .collect()
+}
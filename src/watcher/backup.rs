@@ -1,28 +1,146 @@
-use crate::config::BackupConfig;
-use crate::watcher::state::{AppState, BackupInfo, LogLevel, LogSource};
+use crate::config::{BackupConfig, IdentityConfig, S3Config};
+use crate::watcher::heapdump::{cleanup_heap_dumps, HeapDumpCleanup};
+use crate::watcher::process::run_hook;
+use crate::watcher::s3::upload_backup;
+use crate::watcher::state::{AppState, BackupInfo, LogLevel, LogSource, ServerStatus};
 use crate::watcher::telegram::{NotifyType, TelegramClient};
+use age::secrecy::SecretString;
 use chrono::{Local, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
 use std::fs::{self, File};
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
-use tar::Builder;
-use tokio::sync::watch;
+use tar::{Archive, Builder};
+use tokio::sync::{mpsc, oneshot, watch};
 use tokio::time::{interval, Instant};
+use utoipa::ToSchema;
 use walkdir::WalkDir;
+use xz2::read::XzDecoder;
 use xz2::write::XzEncoder;
 
+/// Archive prefix used when neither the config nor the identity provides one
+const DEFAULT_ARCHIVE_PREFIX: &str = "backup";
+const MAX_ARCHIVE_PREFIX_LEN: usize = 32;
+/// xz level used for test backups and as the starting point for auto mode
+pub const DEFAULT_COMPRESSION_LEVEL: u8 = 6;
+/// Appended to archives encrypted with `encryption_passphrase`
+pub const ENCRYPTED_SUFFIX: &str = ".age";
+/// First bytes of every xz stream
+const XZ_MAGIC: [u8; 6] = [0xFD, b'7', b'z', b'X', b'Z', 0x00];
+
+/// Contents of the `<prefix>_TIMESTAMP.json` sidecar written next to each archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupMetadata {
+    pub files_count: u64,
+    pub total_size_bytes_before_compression: u64,
+    pub source_folder: String,
+    pub created_at_utc: String,
+    pub watcher_version: String,
+    pub compression: String,
+    pub checksum_sha256: String,
+    /// Files skipped by `exclude_patterns`
+    #[serde(default)]
+    pub excluded_count: u64,
+    /// The archive was age-encrypted; `checksum_sha256` is of the encrypted file
+    #[serde(default)]
+    pub encrypted: bool,
+}
+
+/// Totals collected while archiving
+#[derive(Debug, Clone, Copy, Default)]
+struct ArchiveStats {
+    files_count: u64,
+    total_bytes: u64,
+    excluded_count: u64,
+}
+
+/// Compiled `exclude_patterns`, matched against paths relative to the
+/// source folder. `*` also matches `/`, so `*.tmp` applies at any depth.
+#[derive(Debug, Clone, Default)]
+pub struct ExcludePatterns(Vec<glob::Pattern>);
+
+impl ExcludePatterns {
+    pub fn new(patterns: &[String]) -> Result<Self, glob::PatternError> {
+        patterns
+            .iter()
+            .map(|p| glob::Pattern::new(p))
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+
+    fn is_excluded(&self, relative_path: &Path) -> bool {
+        self.0.iter().any(|p| p.matches_path(relative_path))
+    }
+}
+
+/// Result of a test backup run
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TestBackupReport {
+    pub size_bytes: u64,
+    pub sha256: String,
+    pub entries: usize,
+    pub duration_ms: u64,
+}
+
+/// File-level difference between two backup archives
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BackupDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+    pub unchanged_count: u32,
+}
+
+/// One entry of an archive listing, read from the tar header
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub size_bytes: u64,
+    pub mode: u32,
+}
+
+/// Result of a restore, or of a dry run that only looked at the archive
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RestoreReport {
+    pub filename: String,
+    pub dry_run: bool,
+    pub files_count: usize,
+    /// Archive files that already exist in the source folder
+    pub overwritten: Vec<String>,
+    /// Where the previous source folder was moved to
+    pub previous_source: Option<String>,
+}
+
+/// Requests to the backup manager from other tasks
+#[derive(Debug)]
+pub enum BackupCommand {
+    /// Back up now, outside the schedule. The sender has already claimed
+    /// the slot with `AppState::try_start_backup`.
+    Trigger {
+        reply: oneshot::Sender<Result<PathBuf, String>>,
+    },
+}
+
 pub struct BackupManager {
     config: BackupConfig,
+    archive_prefix: String,
+    heap_dumps: Option<HeapDumpCleanup>,
     base_path: PathBuf,
     state: Arc<AppState>,
     telegram: Option<TelegramClient>,
     shutdown_rx: watch::Receiver<bool>,
+    commands: Option<mpsc::Receiver<BackupCommand>>,
 }
 
 impl BackupManager {
     pub fn new(
         config: BackupConfig,
+        archive_prefix: String,
+        heap_dumps: Option<HeapDumpCleanup>,
         working_dir: Option<String>,
         state: Arc<AppState>,
         telegram: Option<TelegramClient>,
@@ -34,21 +152,38 @@ impl BackupManager {
 
         Self {
             config,
+            archive_prefix,
+            heap_dumps,
             base_path,
             state,
             telegram,
             shutdown_rx,
+            commands: None,
         }
     }
 
+    pub fn with_commands(mut self, commands: mpsc::Receiver<BackupCommand>) -> Self {
+        self.commands = Some(commands);
+        self
+    }
+
     pub async fn run(mut self) {
         if !self.config.enabled {
             tracing::info!("Backup system disabled");
             return;
         }
 
-        let interval_secs = self.config.interval_hours * 3600;
+        self.state.set_backup_compression_level(
+            self.config
+                .compression_level
+                .unwrap_or(DEFAULT_COMPRESSION_LEVEL),
+        );
+
+        let interval_secs = self.config.interval_hours.saturating_mul(3600);
         let mut last_backup = Instant::now();
+        // Set while a due backup is being held back by high CPU
+        let mut deferred_until: Option<Instant> = None;
+        let mut first_skipped_at: Option<Instant> = None;
 
         self.state.set_next_backup_secs(Some(interval_secs));
         self.state.add_watcher_log(format!(
@@ -59,6 +194,11 @@ impl BackupManager {
         // Initial backup list scan
         self.refresh_backup_list();
 
+        if self.config.test_on_startup {
+            let source_path = self.base_path.join(&self.config.source_folder);
+            let _ = test_backup(source_path, &self.state, self.telegram.as_ref()).await;
+        }
+
         let mut ticker = interval(Duration::from_secs(1));
 
         loop {
@@ -69,14 +209,55 @@ impl BackupManager {
                         break;
                     }
                 }
+                Some(cmd) = next_command(&mut self.commands) => {
+                    match cmd {
+                        BackupCommand::Trigger { reply } => {
+                            self.state.add_watcher_log("Manual backup requested".to_string());
+                            let result = self.create_backup_async().await;
+                            self.state.finish_backup();
+                            let _ = reply.send(result);
+                        }
+                    }
+                }
             }
 
             let elapsed = last_backup.elapsed().as_secs();
-            let remaining = interval_secs.saturating_sub(elapsed);
+            let remaining = match deferred_until {
+                Some(until) => until.saturating_duration_since(Instant::now()).as_secs(),
+                None => interval_secs.saturating_sub(elapsed),
+            };
             self.state.set_next_backup_secs(Some(remaining));
 
-            if elapsed >= interval_secs {
-                self.create_backup_async().await;
+            if elapsed >= interval_secs && remaining == 0 {
+                if let Some((cpu, threshold)) = self.cpu_over_threshold() {
+                    let max_skip =
+                        Duration::from_secs(self.config.max_skip_hours.saturating_mul(3600));
+                    let skipping_for = first_skipped_at.get_or_insert_with(Instant::now).elapsed();
+                    if skipping_for < max_skip {
+                        self.state.add_watcher_log(format!(
+                            "Skipping backup: CPU at {:.1}%, threshold {:.1}%",
+                            cpu, threshold
+                        ));
+                        self.state.increment_skipped_backups();
+                        deferred_until = Some(
+                            Instant::now() + Duration::from_secs(self.config.check_interval_secs),
+                        );
+                        continue;
+                    }
+                    self.state.add_watcher_log(format!(
+                        "CPU still at {:.1}% after {} hours of skipped backups, backing up anyway",
+                        cpu, self.config.max_skip_hours
+                    ));
+                }
+                // A manual backup is running; try again on the next tick
+                if !self.state.try_start_backup() {
+                    continue;
+                }
+                deferred_until = None;
+                first_skipped_at = None;
+                let _ = self.create_backup_async().await;
+                self.state.finish_backup();
+                self.cleanup_heap_dumps();
                 last_backup = Instant::now();
             }
         }
@@ -85,10 +266,30 @@ impl BackupManager {
         tracing::info!("Backup manager stopped");
     }
 
-    pub async fn create_backup_async(&self) {
+    /// Current CPU and the configured limit, if the server is running above it
+    fn cpu_over_threshold(&self) -> Option<(f32, f32)> {
+        let threshold = self.config.skip_when_cpu_above_percent?;
+        if self.state.status() != ServerStatus::Running {
+            return None;
+        }
+        let cpu = self.state.stats().cpu_percent;
+        (cpu > threshold).then_some((cpu, threshold))
+    }
+
+    /// Create an archive now and return its path
+    pub async fn create_backup_async(&self) -> Result<PathBuf, String> {
         let source_path = self.base_path.join(&self.config.source_folder);
         let backup_path = self.base_path.join(&self.config.backup_folder);
 
+        if let Some(ref command) = self.config.pre_backup_command {
+            if !self.run_backup_hook("pre_backup", command, &[]).await {
+                let message = "Backup skipped: pre_backup hook failed".to_string();
+                self.state
+                    .add_log(LogLevel::Error, LogSource::Watcher, message.clone());
+                return Err(message);
+            }
+        }
+
         self.state
             .add_watcher_log(format!("Starting backup of {:?}...", source_path));
 
@@ -96,10 +297,39 @@ impl BackupManager {
         let source = source_path.clone();
         let dest = backup_path.clone();
         let retention = self.config.retention_days;
+        let retention_count = self.config.retention_count;
+        let streaming = self.config.stream_to_pipe;
+        let exclude_patterns = self.config.exclude_patterns.clone();
+        let check_disk_space = self.config.disk_space_check_enabled;
+        let verify = self.config.backup_verify;
+        let passphrase = self
+            .config
+            .encryption_passphrase
+            .clone()
+            .filter(|p| !p.is_empty());
+        let prefix = self.archive_prefix.clone();
+        let level = self.state.backup_compression_level();
+        let started = Instant::now();
 
         let result = tokio::task::spawn_blocking(move || {
-            create_backup(&source, &dest).and_then(|file| {
-                cleanup_old_backups(&dest, retention)?;
+            if check_disk_space {
+                ensure_disk_space(&source, &dest)?;
+            }
+            let exclude = ExcludePatterns::new(&exclude_patterns)?;
+            let created = if streaming {
+                create_backup_streaming(&source, &dest, &prefix, level, &exclude)
+            } else {
+                create_backup(&source, &dest, &prefix, level, &exclude)
+            };
+            created.and_then(|file| {
+                if verify {
+                    verify_new_backup(&file)?;
+                }
+                let file = match passphrase {
+                    Some(ref passphrase) => encrypt_backup(&file, passphrase)?,
+                    None => file,
+                };
+                cleanup_old_backups(&dest, retention, retention_count)?;
                 Ok(file)
             })
         })
@@ -107,30 +337,53 @@ impl BackupManager {
 
         match result {
             Ok(Ok(backup_file)) => {
+                self.state.record_backup_duration(started.elapsed().as_secs());
+                if self.config.compression_level.is_none() {
+                    self.adjust_compression_level(level, started.elapsed());
+                }
                 let file_size = fs::metadata(&backup_file)
                     .map(|m| format_bytes(m.len()))
                     .unwrap_or_else(|_| "unknown".to_string());
 
+                let metadata = read_metadata(&backup_file);
+                let excluded = metadata.as_ref().map_or(0, |m| m.excluded_count);
+
                 self.state.set_last_backup_time(Some(Local::now()));
                 self.state.add_watcher_log(format!(
                     "Backup created: {} ({})",
                     backup_file.display(),
                     file_size
                 ));
+                if excluded > 0 {
+                    self.state
+                        .add_watcher_log(format!("Backup excluded {} files", excluded));
+                }
 
                 if let Some(ref tg) = self.telegram {
                     let filename = backup_file
                         .file_name()
                         .unwrap_or_default()
                         .to_string_lossy();
-                    tg.notify(
-                        NotifyType::Backup,
-                        &format!("Backup created: {} ({})", filename, file_size),
-                    )
-                    .await;
+                    let checksum = metadata.map(|m| m.checksum_sha256);
+                    tg.notify_backup(&filename, &file_size, checksum.as_deref())
+                        .await;
+                }
+
+                if let Some(ref command) = self.config.post_backup_command {
+                    let size_bytes = fs::metadata(&backup_file).map(|m| m.len()).unwrap_or(0);
+                    let env = [
+                        ("BACKUP_FILE", backup_file.display().to_string()),
+                        ("BACKUP_SIZE_BYTES", size_bytes.to_string()),
+                    ];
+                    self.run_backup_hook("post_backup", command, &env).await;
+                }
+
+                if let Some(ref s3) = self.config.remote_s3 {
+                    self.upload_to_s3(s3, &backup_file).await;
                 }
 
                 self.refresh_backup_list();
+                Ok(backup_file)
             }
             Ok(Err(e)) => {
                 self.state.add_log(
@@ -140,9 +393,15 @@ impl BackupManager {
                 );
 
                 if let Some(ref tg) = self.telegram {
-                    tg.notify(NotifyType::Error, &format!("Backup failed: {}", e))
+                    // A corrupt archive means the backup storage can't be trusted
+                    let notify_type = match e.downcast_ref::<BackupError>() {
+                        Some(BackupError::CorruptArchive { .. }) => NotifyType::Critical,
+                        _ => NotifyType::Error,
+                    };
+                    tg.notify(notify_type, &format!("Backup failed: {}", e))
                         .await;
                 }
+                Err(e.to_string())
             }
             Err(e) => {
                 self.state.add_log(
@@ -150,10 +409,107 @@ impl BackupManager {
                     LogSource::Watcher,
                     format!("Backup task panicked: {}", e),
                 );
+                Err(e.to_string())
+            }
+        }
+    }
+
+    /// Run a backup hook in the server's working directory
+    async fn run_backup_hook(&self, name: &str, command: &str, env: &[(&str, String)]) -> bool {
+        let working_dir = self.base_path.to_string_lossy();
+        let timeout = Duration::from_secs(self.config.hook_timeout_secs);
+        run_hook(&self.state, name, command, Some(&working_dir), timeout, env).await
+    }
+
+    /// Upload a finished backup. A failed upload keeps the local copy; a
+    /// successful one removes it when `remote_only` is set.
+    async fn upload_to_s3(&self, s3: &S3Config, backup_file: &Path) {
+        self.state
+            .add_watcher_log(format!("Uploading backup to S3 bucket {}...", s3.bucket));
+        let result = upload_backup(s3, backup_file, &self.state).await;
+        self.state.set_backup_upload(None);
+
+        let url = match result {
+            Ok(url) => url,
+            Err(e) => {
+                self.state.add_log(
+                    LogLevel::Error,
+                    LogSource::Watcher,
+                    format!("Backup upload failed, local copy kept: {}", e),
+                );
+                return;
+            }
+        };
+        self.state
+            .add_watcher_log(format!("Backup uploaded: {}", url));
+        if let Some(ref tg) = self.telegram {
+            tg.notify(
+                NotifyType::Backup,
+                &format!(
+                    "Backup uploaded\nLocal: {}\nS3: {}",
+                    backup_file.display(),
+                    url
+                ),
+            )
+            .await;
+        }
+
+        if s3.remote_only {
+            let filename = backup_file
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy();
+            let backup_path = self.base_path.join(&self.config.backup_folder);
+            match delete_backup(&backup_path, &filename) {
+                Ok(()) => self
+                    .state
+                    .add_watcher_log(format!("Deleted local copy of {}", filename)),
+                Err(e) => self.state.add_log(
+                    LogLevel::Warning,
+                    LogSource::Watcher,
+                    format!("Failed to delete local copy of {}: {}", filename, e),
+                ),
             }
         }
     }
 
+    /// Remove heap dumps past their retention, alongside backup cleanup
+    fn cleanup_heap_dumps(&self) {
+        let Some(ref heap_dumps) = self.heap_dumps else {
+            return;
+        };
+        for dump in cleanup_heap_dumps(&heap_dumps.dirs, heap_dumps.retention_days) {
+            tracing::info!("Deleted old heap dump: {:?}", dump.path);
+            self.state.add_watcher_log(format!(
+                "Deleted old heap dump: {} ({})",
+                dump.filename,
+                format_bytes(dump.size_bytes)
+            ));
+        }
+    }
+
+    /// Auto mode: step the xz level up when the last backup used under 80%
+    /// of the interval, down when it overran it by more than 20%.
+    fn adjust_compression_level(&self, level: u8, took: Duration) {
+        let interval = self.config.interval_hours as f64 * 3600.0;
+        let took = took.as_secs_f64();
+        let next = if took < interval * 0.8 {
+            (level + 1).min(9)
+        } else if took > interval * 1.2 {
+            level.saturating_sub(1).max(1)
+        } else {
+            level
+        };
+
+        if next != level {
+            self.state.add_watcher_log(format!(
+                "Backup took {}s, compression level {} -> {}",
+                took as u64, level, next
+            ));
+            self.state.set_backup_compression_level(next);
+        }
+    }
+
     fn refresh_backup_list(&self) {
         let backup_path = self.base_path.join(&self.config.backup_folder);
         let backups = list_backups(&backup_path).unwrap_or_default();
@@ -165,9 +521,172 @@ impl BackupManager {
     }
 }
 
+/// Next command, or never when no command channel was attached
+async fn next_command(
+    commands: &mut Option<mpsc::Receiver<BackupCommand>>,
+) -> Option<BackupCommand> {
+    match commands {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
 pub fn create_backup(
     source_path: &Path,
     backup_path: &Path,
+    prefix: &str,
+    level: u8,
+    exclude: &ExcludePatterns,
+) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    let backup_file_path = prepare_backup(source_path, backup_path, prefix)?;
+
+    let file = File::create(&backup_file_path)?;
+    let encoder = XzEncoder::new(file, level.into());
+    let mut tar = Builder::new(encoder);
+
+    let stats = append_source(&mut tar, source_path, exclude)?;
+
+    let encoder = tar.into_inner()?;
+    encoder.finish()?;
+
+    write_metadata(&backup_file_path, source_path, stats, level)?;
+
+    Ok(backup_file_path)
+}
+
+/// Like `create_backup`, but the tar stream is piped to a separate
+/// compressor thread so reading the source and compressing overlap.
+/// Useful when the destination is a slow network mount.
+pub fn create_backup_streaming(
+    source_path: &Path,
+    backup_path: &Path,
+    prefix: &str,
+    level: u8,
+    exclude: &ExcludePatterns,
+) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    let backup_file_path = prepare_backup(source_path, backup_path, prefix)?;
+    let (mut reader, writer) = io::pipe()?;
+
+    let dest = backup_file_path.clone();
+    let compressor = std::thread::spawn(move || -> io::Result<()> {
+        let file = File::create(&dest)?;
+        let mut encoder = XzEncoder::new(file, level.into());
+        io::copy(&mut reader, &mut encoder)?;
+        encoder.finish()?;
+        Ok(())
+    });
+
+    let mut tar = Builder::new(writer);
+    let archived = append_source(&mut tar, source_path, exclude).and_then(|stats| {
+        // Dropping the writer closes the pipe and lets the compressor finish
+        drop(tar.into_inner()?);
+        Ok(stats)
+    });
+    let compressed = compressor
+        .join()
+        .unwrap_or_else(|_| Err(io::Error::other("Compressor thread panicked")));
+
+    let result = compressed
+        .map_err(Into::into)
+        .and(archived)
+        .and_then(|stats| write_metadata(&backup_file_path, source_path, stats, level));
+
+    if let Err(e) = result {
+        let _ = fs::remove_file(&backup_file_path);
+        return Err(e);
+    }
+
+    Ok(backup_file_path)
+}
+
+/// Read a freshly written archive back; a corrupt one is deleted along
+/// with its sidecar so it never shows up as a usable backup
+fn verify_new_backup(archive_path: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if let Err(e) = check_archive_entries(File::open(archive_path)?) {
+        let _ = fs::remove_file(archive_path);
+        remove_sidecars(archive_path);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Encrypt `<archive>` to `<archive>.age` with an age passphrase and delete
+/// the plaintext, also when encryption fails. The checksum sidecars are
+/// rewritten for the encrypted file.
+fn encrypt_backup(
+    archive_path: &Path,
+    passphrase: &str,
+) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    let mut target = archive_path.as_os_str().to_owned();
+    target.push(ENCRYPTED_SUFFIX);
+    let target = PathBuf::from(target);
+    let mut partial = target.clone().into_os_string();
+    partial.push(".part");
+    let partial = PathBuf::from(partial);
+
+    let encrypted = (|| -> io::Result<()> {
+        let encryptor =
+            age::Encryptor::with_user_passphrase(SecretString::from(passphrase.to_string()));
+        let mut writer = encryptor.wrap_output(io::BufWriter::new(File::create(&partial)?))?;
+        io::copy(&mut File::open(archive_path)?, &mut writer)?;
+        writer
+            .finish()?
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+        fs::rename(&partial, &target)
+    })();
+
+    let _ = fs::remove_file(archive_path);
+    let _ = fs::remove_file(checksum_path(archive_path));
+    if let Err(e) = encrypted {
+        let _ = fs::remove_file(&partial);
+        let _ = fs::remove_file(sidecar_path(archive_path));
+        return Err(format!("Backup encryption failed: {}", e).into());
+    }
+
+    let digest = sha256_file(&target)?;
+    if let Some(mut metadata) = read_metadata(&target) {
+        metadata.checksum_sha256 = digest.clone();
+        metadata.encrypted = true;
+        save_metadata(&target, &metadata)?;
+    }
+    write_checksum(&target, &digest)?;
+    Ok(target)
+}
+
+/// Unlock an encrypted archive and decrypt its first chunk. Enough to tell a
+/// wrong passphrase or a damaged header apart from a good archive without
+/// decrypting the whole file.
+pub fn check_passphrase(
+    archive_path: &Path,
+    passphrase: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let decryptor = age::Decryptor::new(io::BufReader::new(File::open(archive_path)?))?;
+    if !decryptor.is_scrypt() {
+        return Err("Archive is not passphrase-encrypted".into());
+    }
+
+    let identity = age::scrypt::Identity::new(SecretString::from(passphrase.to_string()));
+    let mut reader = decryptor.decrypt(std::iter::once(&identity as &dyn age::Identity))?;
+    let mut magic = [0u8; XZ_MAGIC.len()];
+    reader.read_exact(&mut magic)?;
+    if magic != XZ_MAGIC {
+        return Err("Decrypted data is not an xz archive".into());
+    }
+    Ok(())
+}
+
+/// Archive encrypted with `encryption_passphrase`
+pub fn is_encrypted_backup(filename: &str) -> bool {
+    filename.ends_with(ENCRYPTED_SUFFIX)
+}
+
+/// Check the source exists and pick a timestamped archive path
+fn prepare_backup(
+    source_path: &Path,
+    backup_path: &Path,
+    prefix: &str,
 ) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
     if !source_path.exists() {
         return Err(format!("Source folder does not exist: {:?}", source_path).into());
@@ -176,66 +695,690 @@ pub fn create_backup(
     fs::create_dir_all(backup_path)?;
 
     let timestamp = Utc::now().format("%Y%m%d_%H%M%S");
-    let backup_filename = format!("backup_{}.tar.xz", timestamp);
-    let backup_file_path = backup_path.join(&backup_filename);
+    let backup_filename = format!("{}_{}.tar.xz", prefix, timestamp);
+    Ok(backup_path.join(&backup_filename))
+}
 
-    let file = File::create(&backup_file_path)?;
-    let encoder = XzEncoder::new(file, 6);
-    let mut tar = Builder::new(encoder);
+/// Archive size relative to the source when no earlier backup has metadata
+const DEFAULT_COMPRESSION_RATIO: f64 = 0.5;
+/// Free space required on top of the estimated archive size
+const DISK_SPACE_MARGIN: f64 = 1.2;
+const BYTES_PER_GB: f64 = 1_073_741_824.0;
+
+/// Fail before writing anything if the backup partition cannot hold the
+/// archive. The size is estimated from the source and the compression
+/// ratio of the newest backup.
+fn ensure_disk_space(source_path: &Path, backup_path: &Path) -> Result<(), BackupError> {
+    let Some(available) = available_space(backup_path) else {
+        return Ok(());
+    };
+
+    let source_size: u64 = WalkDir::new(source_path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum();
+    let ratio = list_backups(backup_path)
+        .unwrap_or_default()
+        .into_iter()
+        .find_map(|b| match b.original_size_bytes {
+            Some(original) if original > 0 => Some(b.size_bytes as f64 / original as f64),
+            _ => None,
+        })
+        .unwrap_or(DEFAULT_COMPRESSION_RATIO);
+
+    let required = source_size as f64 * ratio * DISK_SPACE_MARGIN;
+    if (available as f64) < required {
+        return Err(BackupError::InsufficientDiskSpace {
+            available_gb: available as f64 / BYTES_PER_GB,
+            required_gb: required / BYTES_PER_GB,
+        });
+    }
+    Ok(())
+}
+
+/// Free bytes for unprivileged users on the partition holding `path`,
+/// which may not exist yet
+#[cfg(unix)]
+fn available_space(path: &Path) -> Option<u64> {
+    let existing = path.ancestors().find(|p| p.exists())?;
+    let stat = nix::sys::statvfs::statvfs(existing).ok()?;
+    Some(stat.blocks_available() as u64 * stat.fragment_size() as u64)
+}
+
+#[cfg(not(unix))]
+fn available_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Pick the archive prefix: explicit `archive_prefix`, else a customised
+/// identity name, else `backup`. Always sanitised.
+pub fn archive_prefix(backup: &BackupConfig, identity: &IdentityConfig) -> String {
+    let raw = match &backup.archive_prefix {
+        Some(prefix) => prefix.as_str(),
+        None if identity.name != IdentityConfig::default().name => identity.name.as_str(),
+        None => DEFAULT_ARCHIVE_PREFIX,
+    };
+    sanitize_prefix(raw)
+}
+
+/// Replace anything but ASCII letters, digits and `-` with `_`, cap at 32 chars
+fn sanitize_prefix(raw: &str) -> String {
+    let sanitized: String = raw
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(MAX_ARCHIVE_PREFIX_LEN)
+        .collect();
+
+    if sanitized.is_empty() {
+        DEFAULT_ARCHIVE_PREFIX.to_string()
+    } else {
+        sanitized
+    }
+}
+
+/// Matches `<prefix>_YYYYMMDD_HHMMSS.tar.<ext>`, optionally followed by
+/// `.age`, for any sanitised prefix.
+/// Also serves as the path-traversal check for API-supplied filenames.
+pub fn is_backup_filename(filename: &str) -> bool {
+    let filename = filename.strip_suffix(ENCRYPTED_SUFFIX).unwrap_or(filename);
+    let Some((base, ext)) = filename.rsplit_once(".tar.") else {
+        return false;
+    };
+    if ext.is_empty() || !ext.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return false;
+    }
+
+    // "_YYYYMMDD_HHMMSS" is 16 bytes
+    let Some(split) = base.len().checked_sub(16) else {
+        return false;
+    };
+    let (Some(prefix), Some(stamp)) = (base.get(..split), base.get(split..)) else {
+        return false;
+    };
+
+    let stamp_ok = stamp.bytes().enumerate().all(|(i, b)| match i {
+        0 | 9 => b == b'_',
+        _ => b.is_ascii_digit(),
+    });
+    let prefix_ok = !prefix.is_empty()
+        && prefix.len() <= MAX_ARCHIVE_PREFIX_LEN
+        && prefix
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    stamp_ok && prefix_ok
+}
+
+fn append_source<W: Write>(
+    tar: &mut Builder<W>,
+    source_path: &Path,
+    exclude: &ExcludePatterns,
+) -> Result<ArchiveStats, Box<dyn std::error::Error + Send + Sync>> {
+    let mut stats = ArchiveStats::default();
+    // Everything below an excluded directory is skipped too
+    let mut excluded_dirs: Vec<PathBuf> = Vec::new();
 
     for entry in WalkDir::new(source_path) {
         let entry = entry?;
         let path = entry.path();
         let relative_path = path.strip_prefix(source_path)?;
 
+        if path != source_path
+            && (excluded_dirs.iter().any(|d| relative_path.starts_with(d))
+                || exclude.is_excluded(relative_path))
+        {
+            if path.is_dir() {
+                excluded_dirs.push(relative_path.to_path_buf());
+            } else {
+                stats.excluded_count += 1;
+            }
+            continue;
+        }
+
         if path.is_file() {
             tar.append_path_with_name(path, relative_path)?;
+            stats.files_count += 1;
+            stats.total_bytes += entry.metadata().map(|m| m.len()).unwrap_or(0);
         } else if path.is_dir() && path != source_path {
             tar.append_dir(relative_path, path)?;
         }
     }
 
-    let encoder = tar.into_inner()?;
-    encoder.finish()?;
+    Ok(stats)
+}
 
-    Ok(backup_file_path)
+/// `<prefix>_X.tar.xz` or `<prefix>_X.tar.xz.age` -> `<prefix>_X.json`
+pub fn sidecar_path(archive_path: &Path) -> PathBuf {
+    let name = archive_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let stem = name.strip_suffix(ENCRYPTED_SUFFIX).unwrap_or(&name);
+    let stem = stem.strip_suffix(".tar.xz").unwrap_or(stem);
+    archive_path.with_file_name(format!("{}.json", stem))
+}
+
+fn write_metadata(
+    archive_path: &Path,
+    source_path: &Path,
+    stats: ArchiveStats,
+    level: u8,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let metadata = BackupMetadata {
+        files_count: stats.files_count,
+        total_size_bytes_before_compression: stats.total_bytes,
+        source_folder: source_path.display().to_string(),
+        created_at_utc: Utc::now().to_rfc3339(),
+        watcher_version: env!("CARGO_PKG_VERSION").to_string(),
+        compression: format!("xz-{}", level),
+        checksum_sha256: sha256_file(archive_path)?,
+        excluded_count: stats.excluded_count,
+        encrypted: false,
+    };
+    save_metadata(archive_path, &metadata)?;
+    write_checksum(archive_path, &metadata.checksum_sha256)?;
+    Ok(())
+}
+
+fn save_metadata(
+    archive_path: &Path,
+    metadata: &BackupMetadata,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    fs::write(
+        sidecar_path(archive_path),
+        serde_json::to_string_pretty(metadata)?,
+    )?;
+    Ok(())
+}
+
+pub fn read_metadata(archive_path: &Path) -> Option<BackupMetadata> {
+    let content = fs::read_to_string(sidecar_path(archive_path)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// `<prefix>_X.tar.xz` -> `<prefix>_X.tar.xz.sha256`
+pub fn checksum_path(archive_path: &Path) -> PathBuf {
+    let mut name = archive_path.as_os_str().to_owned();
+    name.push(".sha256");
+    PathBuf::from(name)
+}
+
+/// In `sha256sum` format, so `sha256sum -c` can check a downloaded copy
+fn write_checksum(archive_path: &Path, digest: &str) -> io::Result<()> {
+    let filename = archive_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    fs::write(
+        checksum_path(archive_path),
+        format!("{}  {}\n", digest, filename),
+    )
+}
+
+/// Hex digest from the `.sha256` file, falling back to the metadata sidecar
+pub fn read_checksum(archive_path: &Path) -> Option<String> {
+    fs::read_to_string(checksum_path(archive_path))
+        .ok()
+        .and_then(|content| content.split_whitespace().next().map(str::to_string))
+        .or_else(|| read_metadata(archive_path).map(|m| m.checksum_sha256))
+}
+
+/// Delete the metadata and checksum files that belong to an archive
+fn remove_sidecars(archive_path: &Path) {
+    let _ = fs::remove_file(sidecar_path(archive_path));
+    let _ = fs::remove_file(checksum_path(archive_path));
+}
+
+/// Compute the hex-encoded SHA-256 of a file
+pub fn sha256_file(path: &Path) -> Result<String, io::Error> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Check the archive checksum, then decompress and read every entry.
+/// Returns the number of entries in the archive.
+pub fn verify_backup(
+    archive_path: &Path,
+    expected_sha256: &str,
+) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    let actual = sha256_file(archive_path)?;
+    if !actual.eq_ignore_ascii_case(expected_sha256) {
+        return Err(format!(
+            "Checksum mismatch: expected {}, got {}",
+            expected_sha256, actual
+        )
+        .into());
+    }
+
+    Ok(check_archive_entries(File::open(archive_path)?)?)
+}
+
+/// Archive problems callers may want to tell apart from plain I/O errors
+#[derive(Debug)]
+pub enum BackupError {
+    /// `entry` is empty when the stream broke before an entry header
+    CorruptArchive {
+        entry: String,
+        error: String,
+    },
+    InsufficientDiskSpace {
+        available_gb: f64,
+        required_gb: f64,
+    },
+}
+
+impl std::fmt::Display for BackupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackupError::CorruptArchive { entry, error } if entry.is_empty() => {
+                write!(f, "Corrupt archive: {}", error)
+            }
+            BackupError::CorruptArchive { entry, error } => {
+                write!(f, "Corrupt archive at {}: {}", entry, error)
+            }
+            BackupError::InsufficientDiskSpace {
+                available_gb,
+                required_gb,
+            } => write!(
+                f,
+                "Not enough disk space for backup: {:.2} GB available, {:.2} GB required",
+                available_gb, required_gb
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BackupError {}
+
+/// Decompress and read every entry without writing anything to disk.
+/// Meant as the first pass before extracting, so a corrupt archive is
+/// rejected instead of producing a partial restore.
+pub fn check_archive_entries(file: File) -> Result<usize, BackupError> {
+    let corrupt = |entry: String, error: io::Error| BackupError::CorruptArchive {
+        entry,
+        error: error.to_string(),
+    };
+
+    let mut archive = Archive::new(XzDecoder::new(file));
+    let mut entries = 0;
+
+    for entry in archive.entries().map_err(|e| corrupt(String::new(), e))? {
+        let mut entry = entry.map_err(|e| corrupt(String::new(), e))?;
+        let path = entry
+            .path()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default();
+        io::copy(&mut entry, &mut io::sink()).map_err(|e| corrupt(path, e))?;
+        entries += 1;
+    }
+
+    // The tar end marker comes before the xz index and footer; only reading
+    // to the end checks those, so a truncated archive fails here
+    io::copy(&mut archive.into_inner(), &mut io::sink()).map_err(|e| corrupt(String::new(), e))?;
+
+    Ok(entries)
+}
+
+/// Path -> (size, mtime) for every file in an archive, from the tar headers
+fn archive_listing(
+    archive_path: &Path,
+) -> Result<HashMap<String, (u64, u64)>, Box<dyn std::error::Error + Send + Sync>> {
+    let file = File::open(archive_path)?;
+    let mut archive = Archive::new(XzDecoder::new(file));
+    let mut listing = HashMap::new();
+
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let header = entry.header();
+        if !header.entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.to_string_lossy().to_string();
+        listing.insert(path, (header.size()?, header.mtime()?));
+    }
+
+    Ok(listing)
+}
+
+/// Up to `max_entries` entries of an archive without extracting it, and
+/// whether the listing was cut short. Blocking; run inside `spawn_blocking`.
+pub fn archive_manifest(
+    archive_path: &Path,
+    max_entries: usize,
+) -> Result<(Vec<ManifestEntry>, bool), Box<dyn std::error::Error + Send + Sync>> {
+    let file = File::open(archive_path)?;
+    let mut archive = Archive::new(XzDecoder::new(file));
+    let mut manifest = Vec::new();
+
+    for entry in archive.entries()? {
+        let entry = entry?;
+        if manifest.len() == max_entries {
+            return Ok((manifest, true));
+        }
+        let header = entry.header();
+        manifest.push(ManifestEntry {
+            path: entry.path()?.to_string_lossy().to_string(),
+            size_bytes: header.size()?,
+            mode: header.mode()?,
+        });
+    }
+
+    Ok((manifest, false))
+}
+
+/// Compare the file listings of archive `a` (older) and `b` (newer).
+/// Blocking; run inside `spawn_blocking`.
+pub fn diff_backups(
+    a: &Path,
+    b: &Path,
+) -> Result<BackupDiff, Box<dyn std::error::Error + Send + Sync>> {
+    let old = archive_listing(a)?;
+    let new = archive_listing(b)?;
+
+    let mut diff = BackupDiff {
+        added: Vec::new(),
+        removed: Vec::new(),
+        changed: Vec::new(),
+        unchanged_count: 0,
+    };
+
+    for (path, meta) in &new {
+        match old.get(path) {
+            None => diff.added.push(path.clone()),
+            Some(old_meta) if old_meta != meta => diff.changed.push(path.clone()),
+            Some(_) => diff.unchanged_count += 1,
+        }
+    }
+    diff.removed = old
+        .keys()
+        .filter(|path| !new.contains_key(*path))
+        .cloned()
+        .collect();
+
+    diff.added.sort();
+    diff.removed.sort();
+    diff.changed.sort();
+
+    Ok(diff)
+}
+
+/// Back up `source_path` into a temporary directory, verify the archive and
+/// remove it again. Blocking; run inside `spawn_blocking`.
+pub fn run_test_backup(
+    source_path: &Path,
+) -> Result<TestBackupReport, Box<dyn std::error::Error + Send + Sync>> {
+    let started = std::time::Instant::now();
+    let temp_dir =
+        std::env::temp_dir().join(format!("server-watcher-test-{}", uuid::Uuid::new_v4()));
+
+    let result = (|| {
+        let archive = create_backup(
+            source_path,
+            &temp_dir,
+            DEFAULT_ARCHIVE_PREFIX,
+            DEFAULT_COMPRESSION_LEVEL,
+            &ExcludePatterns::default(),
+        )?;
+        let size_bytes = fs::metadata(&archive)?.len();
+        let sha256 = sha256_file(&archive)?;
+        let entries = verify_backup(&archive, &sha256)?;
+        Ok(TestBackupReport {
+            size_bytes,
+            sha256,
+            entries,
+            duration_ms: started.elapsed().as_millis() as u64,
+        })
+    })();
+
+    if let Err(e) = fs::remove_dir_all(&temp_dir) {
+        tracing::warn!("Failed to remove test backup dir {:?}: {}", temp_dir, e);
+    }
+
+    result
+}
+
+/// Run a test backup, log the outcome and notify Telegram.
+/// Does not touch the regular backup schedule or `last_backup_time`.
+pub async fn test_backup(
+    source_path: PathBuf,
+    state: &AppState,
+    telegram: Option<&TelegramClient>,
+) -> Result<TestBackupReport, String> {
+    state.add_watcher_log(format!("Starting test backup of {:?}...", source_path));
+
+    let result = tokio::task::spawn_blocking(move || {
+        run_test_backup(&source_path).map_err(|e| e.to_string())
+    })
+    .await
+    .unwrap_or_else(|e| Err(format!("Test backup task panicked: {}", e)));
+
+    match result {
+        Ok(ref report) => {
+            let message = format!(
+                "Test backup OK: {} entries, {} (sha256 {})",
+                report.entries,
+                format_bytes(report.size_bytes),
+                &report.sha256[..12]
+            );
+            state.add_watcher_log(message.clone());
+            if let Some(tg) = telegram {
+                tg.notify(NotifyType::Info, &message).await;
+            }
+        }
+        Err(ref e) => {
+            state.add_log(
+                LogLevel::Error,
+                LogSource::Watcher,
+                format!("Test backup failed: {}", e),
+            );
+            if let Some(tg) = telegram {
+                tg.notify(NotifyType::Error, &format!("Test backup failed: {}", e))
+                    .await;
+            }
+        }
+    }
+
+    result
+}
+
+/// `<source>.bak`, where a restore moves the previous source folder
+fn restore_backup_dir(source_path: &Path) -> PathBuf {
+    let mut name = source_path.as_os_str().to_owned();
+    name.push(".bak");
+    PathBuf::from(name)
+}
+
+/// Unpack into a temp dir next to the source, then swap it in. The old
+/// source ends up in `<source>.bak`; an earlier `.bak` is only deleted once
+/// the swap has succeeded. Blocking; run inside `spawn_blocking`.
+fn run_restore(
+    archive_path: &Path,
+    source_path: &Path,
+    dry_run: bool,
+) -> Result<RestoreReport, Box<dyn std::error::Error + Send + Sync>> {
+    let listing = archive_listing(archive_path)?;
+    let mut overwritten: Vec<String> = listing
+        .keys()
+        .filter(|path| source_path.join(path).exists())
+        .cloned()
+        .collect();
+    overwritten.sort();
+
+    let mut report = RestoreReport {
+        filename: archive_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+        dry_run,
+        files_count: listing.len(),
+        overwritten,
+        previous_source: None,
+    };
+    if dry_run {
+        return Ok(report);
+    }
+
+    // Refuse a damaged archive before anything is unpacked or swapped
+    let verified = match read_checksum(archive_path) {
+        Some(expected) => verify_backup(archive_path, &expected),
+        None => check_archive_entries(File::open(archive_path)?).map_err(Into::into),
+    };
+    if let Err(e) = verified {
+        return Err(format!("Backup failed verification: {}", e).into());
+    }
+
+    let parent = source_path
+        .parent()
+        .ok_or("Source folder has no parent directory")?;
+    let temp_dir = parent.join(format!(".restore-{}", uuid::Uuid::new_v4()));
+    let unpacked = (|| -> io::Result<()> {
+        fs::create_dir_all(&temp_dir)?;
+        Archive::new(XzDecoder::new(File::open(archive_path)?)).unpack(&temp_dir)
+    })();
+    if let Err(e) = unpacked {
+        let _ = fs::remove_dir_all(&temp_dir);
+        return Err(format!("Failed to unpack archive: {}", e).into());
+    }
+
+    let backup_dir = restore_backup_dir(source_path);
+    // The earlier `.bak` is parked until the new source is in place
+    let parked_dir = parent.join(format!(".restore-bak-{}", uuid::Uuid::new_v4()));
+    let swapped = (|| -> io::Result<()> {
+        if source_path.exists() {
+            if backup_dir.exists() {
+                fs::rename(&backup_dir, &parked_dir)?;
+            }
+            fs::rename(source_path, &backup_dir)?;
+            report.previous_source = Some(backup_dir.display().to_string());
+        }
+        fs::rename(&temp_dir, source_path)
+    })();
+    if let Err(e) = swapped {
+        // Put the old source and `.bak` back rather than lose either
+        if report.previous_source.is_some() && !source_path.exists() {
+            let _ = fs::rename(&backup_dir, source_path);
+        }
+        if parked_dir.exists() && !backup_dir.exists() {
+            let _ = fs::rename(&parked_dir, &backup_dir);
+        }
+        let _ = fs::remove_dir_all(&temp_dir);
+        return Err(e.into());
+    }
+    if parked_dir.exists() {
+        let _ = fs::remove_dir_all(&parked_dir);
+    }
+
+    Ok(report)
+}
+
+/// Replace `source_path` with the contents of backup `filename`, or with
+/// `dry_run` only report which files would be overwritten. The caller makes
+/// sure the server is stopped.
+pub async fn restore_backup(
+    backup_path: &Path,
+    source_path: &Path,
+    filename: &str,
+    dry_run: bool,
+) -> Result<RestoreReport, String> {
+    // Security check
+    if !is_backup_filename(filename) {
+        return Err("Invalid backup filename".to_string());
+    }
+    if is_encrypted_backup(filename) {
+        return Err("Encrypted backups cannot be restored".to_string());
+    }
+
+    let archive = backup_path.join(filename);
+    let source = source_path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        run_restore(&archive, &source, dry_run).map_err(|e| e.to_string())
+    })
+    .await
+    .unwrap_or_else(|e| Err(format!("Restore task panicked: {}", e)))
 }
 
+/// Delete untagged backups older than `retention_days` (0 disables the age
+/// limit), then the oldest ones beyond `retention_count`. Tagged backups are
+/// neither deleted nor counted.
 pub fn cleanup_old_backups(
     backup_path: &Path,
     retention_days: u64,
+    retention_count: Option<u32>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     if !backup_path.exists() {
         return Ok(());
     }
 
     let now = std::time::SystemTime::now();
-    let retention_duration = Duration::from_secs(retention_days * 24 * 3600);
+    let retention_duration = Duration::from_secs(retention_days.saturating_mul(24 * 3600));
+    // Unreadable tags could mean deleting a protected backup
+    let tags = match load_backup_tags(backup_path) {
+        Ok(tags) => tags,
+        Err(e) => {
+            tracing::warn!("Skipping backup retention, {}: {}", BACKUP_TAGS_FILE, e);
+            return Ok(());
+        }
+    };
+    let mut kept = Vec::new();
 
     for entry in fs::read_dir(backup_path)? {
         let entry = entry?;
         let path = entry.path();
 
-        if !path.extension().map_or(false, |e| e == "xz") {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !is_backup_filename(&name) {
+            continue;
+        }
+        // Tagged backups are protected
+        if tags.contains_key(&name) {
             continue;
         }
 
-        if let Some(name) = path.file_name() {
-            let name_str = name.to_string_lossy();
-            if !name_str.starts_with("backup_") {
-                continue;
-            }
+        let Ok(modified) = entry.metadata().and_then(|m| m.modified()) else {
+            continue;
+        };
+        let expired = retention_days > 0
+            && now
+                .duration_since(modified)
+                .is_ok_and(|age| age > retention_duration);
+        if expired {
+            fs::remove_file(&path)?;
+            remove_sidecars(&path);
+            tracing::info!("Deleted old backup: {:?}", path);
+        } else {
+            kept.push((modified, path));
         }
+    }
 
-        if let Ok(metadata) = entry.metadata() {
-            if let Ok(modified) = metadata.modified() {
-                if let Ok(age) = now.duration_since(modified) {
-                    if age > retention_duration {
-                        fs::remove_file(&path)?;
-                        tracing::info!("Deleted old backup: {:?}", path);
-                    }
-                }
-            }
+    if let Some(count) = retention_count {
+        // Newest first; everything past `count` goes, never the newest
+        kept.sort_by(|a, b| b.cmp(a));
+        for (_, path) in kept.into_iter().skip(count.max(1) as usize) {
+            fs::remove_file(&path)?;
+            remove_sidecars(&path);
+            tracing::info!("Deleted backup over retention_count: {:?}", path);
         }
     }
 
@@ -248,18 +1391,15 @@ pub fn list_backups(backup_path: &Path) -> Result<Vec<BackupInfo>, std::io::Erro
     }
 
     let mut backups = Vec::new();
+    let mut tags = read_backup_tags(backup_path);
 
     for entry in fs::read_dir(backup_path)? {
         let entry = entry?;
         let path = entry.path();
 
-        if !path.extension().map_or(false, |e| e == "xz") {
-            continue;
-        }
-
         if let Some(name) = path.file_name() {
             let name_str = name.to_string_lossy();
-            if !name_str.starts_with("backup_") {
+            if !is_backup_filename(&name_str) {
                 continue;
             }
 
@@ -270,10 +1410,20 @@ pub fn list_backups(backup_path: &Path) -> Result<Vec<BackupInfo>, std::io::Erro
                     .map(|t| DateTime::from(t))
                     .unwrap_or_else(Local::now);
 
+                let sidecar = read_metadata(&path);
+                let checksum = read_checksum(&path);
+
                 backups.push(BackupInfo {
                     filename: name_str.to_string(),
                     size_bytes: metadata.len(),
                     created_at,
+                    files_count: sidecar.as_ref().map(|m| m.files_count),
+                    original_size_bytes: sidecar
+                        .as_ref()
+                        .map(|m| m.total_size_bytes_before_compression),
+                    watcher_version: sidecar.map(|m| m.watcher_version),
+                    tags: tags.remove(name_str.as_ref()).unwrap_or_default(),
+                    checksum,
                 });
             }
         }
@@ -285,18 +1435,314 @@ pub fn list_backups(backup_path: &Path) -> Result<Vec<BackupInfo>, std::io::Erro
     Ok(backups)
 }
 
+/// One page of backups (1-based), newest first, plus the total count.
+/// Sidecar metadata is only read for the entries on the requested page.
+pub fn list_backups_paginated(
+    backup_path: &Path,
+    page: usize,
+    page_size: usize,
+    tag: Option<&str>,
+) -> Result<(Vec<BackupInfo>, usize), std::io::Error> {
+    if !backup_path.exists() {
+        return Ok((vec![], 0));
+    }
+
+    let mut tags = read_backup_tags(backup_path);
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(backup_path)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !is_backup_filename(&name) {
+            continue;
+        }
+        if let Some(tag) = tag {
+            if !tags.get(&name).is_some_and(|t| t.iter().any(|t| t == tag)) {
+                continue;
+            }
+        }
+        if let Ok(metadata) = entry.metadata() {
+            let created_at: DateTime<Local> = metadata
+                .modified()
+                .ok()
+                .map(DateTime::from)
+                .unwrap_or_else(Local::now);
+            entries.push((created_at, name, metadata.len()));
+        }
+    }
+
+    // Date descending, file name as tie-breaker so pages never overlap
+    entries.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| b.1.cmp(&a.1)));
+
+    let total = entries.len();
+    let page = entries
+        .into_iter()
+        .skip(page.saturating_sub(1).saturating_mul(page_size))
+        .take(page_size)
+        .map(|(created_at, filename, size_bytes)| {
+            let path = backup_path.join(&filename);
+            let sidecar = read_metadata(&path);
+            let checksum = read_checksum(&path);
+            let tags = tags.remove(&filename).unwrap_or_default();
+            BackupInfo {
+                filename,
+                size_bytes,
+                created_at,
+                files_count: sidecar.as_ref().map(|m| m.files_count),
+                original_size_bytes: sidecar
+                    .as_ref()
+                    .map(|m| m.total_size_bytes_before_compression),
+                watcher_version: sidecar.map(|m| m.watcher_version),
+                tags,
+                checksum,
+            }
+        })
+        .collect();
+
+    Ok((page, total))
+}
+
+/// Parsed `GET /api/backups/search` query
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackupQuery {
+    /// `>100MB`
+    LargerThan(u64),
+    /// `<1GB`
+    SmallerThan(u64),
+    /// `2024-01`, `2024-01-16 15`, matched against the creation time
+    DatePrefix(String),
+    /// File name pattern; `*` matches any run of characters
+    Glob(String),
+}
+
+impl BackupQuery {
+    pub fn parse(query: &str) -> Self {
+        let query = query.trim();
+
+        if let Some(rest) = query.strip_prefix('>') {
+            if let Some(bytes) = parse_size(rest) {
+                return BackupQuery::LargerThan(bytes);
+            }
+        }
+        if let Some(rest) = query.strip_prefix('<') {
+            if let Some(bytes) = parse_size(rest) {
+                return BackupQuery::SmallerThan(bytes);
+            }
+        }
+        if is_date_prefix(query) {
+            return BackupQuery::DatePrefix(query.replace('T', " "));
+        }
+        BackupQuery::Glob(query.to_string())
+    }
+
+    pub fn matches(&self, backup: &BackupInfo) -> bool {
+        match self {
+            BackupQuery::LargerThan(bytes) => backup.size_bytes > *bytes,
+            BackupQuery::SmallerThan(bytes) => backup.size_bytes < *bytes,
+            BackupQuery::DatePrefix(prefix) => backup
+                .created_at
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+                .starts_with(prefix.as_str()),
+            BackupQuery::Glob(pattern) => {
+                let filename = backup.filename.to_lowercase();
+                let pattern = pattern.to_lowercase();
+                if pattern.contains('*') {
+                    glob_match(&pattern, &filename)
+                } else {
+                    filename.contains(&pattern)
+                }
+            }
+        }
+    }
+}
+
+/// `100MB`, `1.5gb`, `512kb` or plain bytes
+fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim().to_lowercase();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let multiplier = match unit.trim() {
+        "" | "b" => 1.0,
+        "kb" | "k" => 1024.0,
+        "mb" | "m" => 1024.0 * 1024.0,
+        "gb" | "g" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some((number * multiplier) as u64)
+}
+
+/// `YYYY`, `YYYY-MM`, `YYYY-MM-DD`, optionally followed by ` HH` or `THH:MM`
+fn is_date_prefix(value: &str) -> bool {
+    static DATE_PREFIX: std::sync::OnceLock<regex::Regex> = std::sync::OnceLock::new();
+    DATE_PREFIX
+        .get_or_init(|| {
+            regex::Regex::new(r"^\d{4}(-\d{2}(-\d{2}([ T]\d{2}(:\d{2}(:\d{2})?)?)?)?)?$")
+                .expect("valid date regex")
+        })
+        .is_match(value)
+}
+
+/// Wildcard match where `*` is the only special character
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No `*` at all
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Backups matching `query`, newest first, at most `limit` of them
+pub fn search_backups(
+    backup_path: &Path,
+    query: &BackupQuery,
+    limit: usize,
+) -> Result<Vec<BackupInfo>, std::io::Error> {
+    // list_backups is already sorted newest first
+    Ok(list_backups(backup_path)?
+        .into_iter()
+        .filter(|backup| query.matches(backup))
+        .take(limit)
+        .collect())
+}
+
 pub fn delete_backup(backup_path: &Path, filename: &str) -> Result<(), std::io::Error> {
     let file_path = backup_path.join(filename);
 
     // Security check
-    if !filename.starts_with("backup_") || !filename.ends_with(".tar.xz") {
+    if !is_backup_filename(filename) {
         return Err(std::io::Error::new(
             std::io::ErrorKind::InvalidInput,
             "Invalid backup filename",
         ));
     }
 
-    fs::remove_file(file_path)
+    fs::remove_file(&file_path)?;
+    remove_sidecars(&file_path);
+
+    let _guard = BACKUP_TAGS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    match load_backup_tags(backup_path) {
+        Ok(mut tags) => {
+            if tags.remove(filename).is_some() {
+                write_backup_tags(backup_path, &tags)?;
+            }
+        }
+        Err(e) => tracing::warn!("Tags of {} left in place: {}", filename, e),
+    }
+    Ok(())
+}
+
+/// Tags file kept next to the archives
+const BACKUP_TAGS_FILE: &str = "backup_tags.json";
+
+/// Backup file name to its tags
+pub type BackupTags = BTreeMap<String, Vec<String>>;
+
+/// Serializes read-modify-write updates of `backup_tags.json`
+static BACKUP_TAGS_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Tags from `backup_tags.json`, empty if the file is missing or unreadable.
+/// For display only; updates go through `load_backup_tags`.
+pub fn read_backup_tags(backup_path: &Path) -> BackupTags {
+    load_backup_tags(backup_path).unwrap_or_else(|e| {
+        tracing::warn!("Ignoring {}: {}", BACKUP_TAGS_FILE, e);
+        BackupTags::new()
+    })
+}
+
+/// Tags from `backup_tags.json`; empty if the file is missing, an error if
+/// it cannot be read or parsed
+fn load_backup_tags(backup_path: &Path) -> Result<BackupTags, std::io::Error> {
+    let content = match fs::read_to_string(backup_path.join(BACKUP_TAGS_FILE)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BackupTags::new()),
+        Err(e) => return Err(e),
+    };
+    serde_json::from_str(&content)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Write through a temp file and rename, so a crash never leaves half a file
+fn write_backup_tags(backup_path: &Path, tags: &BackupTags) -> Result<(), std::io::Error> {
+    let content = serde_json::to_string_pretty(tags)?;
+    let path = backup_path.join(BACKUP_TAGS_FILE);
+    let temp_path = backup_path.join(format!("{}.tmp", BACKUP_TAGS_FILE));
+    fs::write(&temp_path, content)?;
+    fs::rename(&temp_path, &path)
+}
+
+/// Add a tag to an existing backup, returning its tags
+pub fn add_backup_tag(
+    backup_path: &Path,
+    filename: &str,
+    tag: &str,
+) -> Result<Vec<String>, std::io::Error> {
+    // Security check
+    if !is_backup_filename(filename) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Invalid backup filename",
+        ));
+    }
+    let tag = tag.trim();
+    if tag.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Tag must not be empty",
+        ));
+    }
+    if !backup_path.join(filename).is_file() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "Backup not found",
+        ));
+    }
+
+    let _guard = BACKUP_TAGS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut tags = load_backup_tags(backup_path)?;
+    let entry = tags.entry(filename.to_string()).or_default();
+    if !entry.iter().any(|t| t == tag) {
+        entry.push(tag.to_string());
+    }
+    let result = entry.clone();
+    write_backup_tags(backup_path, &tags)?;
+    Ok(result)
+}
+
+/// Remove one tag from a backup, returning the tags left
+pub fn remove_backup_tag(
+    backup_path: &Path,
+    filename: &str,
+    tag: &str,
+) -> Result<Vec<String>, std::io::Error> {
+    let _guard = BACKUP_TAGS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut tags = load_backup_tags(backup_path)?;
+    let entry = tags
+        .get_mut(filename)
+        .filter(|entry| entry.iter().any(|t| t == tag))
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Tag not found"))?;
+    entry.retain(|t| t != tag);
+    let result = entry.clone();
+    if result.is_empty() {
+        tags.remove(filename);
+    }
+    write_backup_tags(backup_path, &tags)?;
+    Ok(result)
 }
 
 pub fn format_bytes(bytes: u64) -> String {
@@ -312,3 +1758,255 @@ pub fn format_bytes(bytes: u64) -> String {
 }
 
 use chrono::DateTime;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_retention_count_keeps_newest_backup() {
+        let dir = std::env::temp_dir().join(format!("watcher-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let older = dir.join("backup_20240101_000000.tar.xz");
+        let newest = dir.join("backup_20240102_000000.tar.xz");
+        fs::write(&older, b"old").unwrap();
+        fs::write(&newest, b"new").unwrap();
+        let past = std::time::SystemTime::now() - Duration::from_secs(3600);
+        fs::File::options()
+            .write(true)
+            .open(&older)
+            .unwrap()
+            .set_modified(past)
+            .unwrap();
+
+        cleanup_old_backups(&dir, 0, Some(0)).unwrap();
+
+        assert!(!older.exists());
+        assert!(newest.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn restore_keeps_previous_bak_until_swap_succeeds() {
+        let dir = std::env::temp_dir().join(format!("watcher-test-{}", uuid::Uuid::new_v4()));
+        let source = dir.join("universe");
+        let backup_dir = restore_backup_dir(&source);
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(&backup_dir).unwrap();
+        fs::write(source.join("world.dat"), b"current").unwrap();
+        fs::write(backup_dir.join("world.dat"), b"previous").unwrap();
+
+        // Not an archive: unpacking fails before anything is swapped
+        let archive = dir.join("backup_20240101_000000.tar.xz");
+        fs::write(&archive, b"not xz").unwrap();
+        assert!(run_restore(&archive, &source, false).is_err());
+        assert_eq!(fs::read(source.join("world.dat")).unwrap(), b"current");
+        assert_eq!(fs::read(backup_dir.join("world.dat")).unwrap(), b"previous");
+
+        let staging = dir.join("staging");
+        fs::create_dir_all(&staging).unwrap();
+        fs::write(staging.join("world.dat"), b"restored").unwrap();
+        let mut builder = Builder::new(XzEncoder::new(File::create(&archive).unwrap(), 1));
+        builder.append_dir_all(".", &staging).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        run_restore(&archive, &source, false).unwrap();
+        assert_eq!(fs::read(source.join("world.dat")).unwrap(), b"restored");
+        assert_eq!(fs::read(backup_dir.join("world.dat")).unwrap(), b"current");
+        let leftovers = fs::read_dir(&dir)
+            .unwrap()
+            .filter(|e| e.as_ref().unwrap().file_name().to_string_lossy().starts_with(".restore"))
+            .count();
+        assert_eq!(leftovers, 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unparsable_tags_block_retention_and_tag_updates() {
+        let dir = std::env::temp_dir().join(format!("watcher-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let backup = dir.join("backup_20240101_000000.tar.xz");
+        fs::write(&backup, b"old").unwrap();
+        fs::write(dir.join(BACKUP_TAGS_FILE), b"{ not json").unwrap();
+
+        cleanup_old_backups(&dir, 0, Some(1)).unwrap();
+        let newer = dir.join("backup_20240102_000000.tar.xz");
+        fs::write(&newer, b"new").unwrap();
+        cleanup_old_backups(&dir, 0, Some(1)).unwrap();
+        assert!(backup.exists());
+
+        let err = add_backup_tag(&dir, "backup_20240101_000000.tar.xz", "keep").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(fs::read(dir.join(BACKUP_TAGS_FILE)).unwrap(), b"{ not json");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn tag_updates_do_not_lose_writes() {
+        let dir = std::env::temp_dir().join(format!("watcher-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let names: Vec<String> = (0..8)
+            .map(|i| format!("backup_2024010{}_000000.tar.xz", i + 1))
+            .collect();
+        for name in &names {
+            fs::write(dir.join(name), b"backup").unwrap();
+        }
+
+        std::thread::scope(|scope| {
+            for name in &names {
+                let dir = &dir;
+                scope.spawn(move || add_backup_tag(dir, name, "keep").unwrap());
+            }
+        });
+
+        assert_eq!(load_backup_tags(&dir).unwrap().len(), names.len());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn write_test_archive(dir: &Path) -> PathBuf {
+        let staging = dir.join("staging");
+        fs::create_dir_all(&staging).unwrap();
+        fs::write(staging.join("world.dat"), vec![7u8; 64 * 1024]).unwrap();
+        let archive = dir.join("backup_20240101_000000.tar.xz");
+        let mut builder = Builder::new(XzEncoder::new(File::create(&archive).unwrap(), 1));
+        builder.append_dir_all(".", &staging).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+        archive
+    }
+
+    #[test]
+    fn integrity_check_reads_archive_to_the_end() {
+        let dir = std::env::temp_dir().join(format!("watcher-test-{}", uuid::Uuid::new_v4()));
+        let archive = write_test_archive(&dir);
+        assert!(check_archive_entries(File::open(&archive).unwrap()).is_ok());
+
+        // Damage only the xz footer, past the last tar entry
+        let mut bytes = fs::read(&archive).unwrap();
+        let len = bytes.len();
+        bytes[len - 4..].copy_from_slice(b"\0\0\0\0");
+        fs::write(&archive, &bytes).unwrap();
+        let result = check_archive_entries(File::open(&archive).unwrap());
+        assert!(matches!(result, Err(BackupError::CorruptArchive { .. })));
+
+        bytes.truncate(len - 12);
+        fs::write(&archive, &bytes).unwrap();
+        let result = check_archive_entries(File::open(&archive).unwrap());
+        assert!(matches!(result, Err(BackupError::CorruptArchive { .. })));
+
+        // verify_new_backup relies on the same check and drops the archive
+        assert!(verify_new_backup(&archive).is_err());
+        assert!(!archive.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn backup_info(filename: &str, size_bytes: u64, created_at: &str) -> BackupInfo {
+        BackupInfo {
+            filename: filename.to_string(),
+            size_bytes,
+            created_at: chrono::NaiveDateTime::parse_from_str(created_at, "%Y-%m-%d %H:%M:%S")
+                .unwrap()
+                .and_local_timezone(Local)
+                .unwrap(),
+            files_count: None,
+            original_size_bytes: None,
+            watcher_version: None,
+            tags: Vec::new(),
+            checksum: None,
+        }
+    }
+
+    #[test]
+    fn search_by_size() {
+        let small = backup_info(
+            "backup_20240101_000000.tar.xz",
+            50 * 1024 * 1024,
+            "2024-01-01 00:00:00",
+        );
+        let large = backup_info(
+            "backup_20240102_000000.tar.xz",
+            2 * 1024 * 1024 * 1024,
+            "2024-01-02 00:00:00",
+        );
+
+        let query = BackupQuery::parse(">100MB");
+        assert_eq!(query, BackupQuery::LargerThan(100 * 1024 * 1024));
+        assert!(!query.matches(&small));
+        assert!(query.matches(&large));
+
+        let query = BackupQuery::parse("<1.5gb");
+        assert_eq!(query, BackupQuery::SmallerThan(1610612736));
+        assert!(query.matches(&small));
+        assert!(!query.matches(&large));
+
+        // Not a size after all: searched as a file name
+        assert_eq!(
+            BackupQuery::parse(">lots"),
+            BackupQuery::Glob(">lots".to_string())
+        );
+    }
+
+    #[test]
+    fn search_by_date_prefix() {
+        let backup = backup_info("backup_20240116_153000.tar.xz", 1, "2024-01-16 15:30:00");
+
+        for prefix in [
+            "2024",
+            "2024-01",
+            "2024-01-16",
+            "2024-01-16 15",
+            "2024-01-16T15:30",
+        ] {
+            let query = BackupQuery::parse(prefix);
+            assert!(matches!(query, BackupQuery::DatePrefix(_)), "{}", prefix);
+            assert!(query.matches(&backup), "{}", prefix);
+        }
+        assert!(!BackupQuery::parse("2024-02").matches(&backup));
+        assert!(!BackupQuery::parse("2024-01-16 16").matches(&backup));
+    }
+
+    #[test]
+    fn search_by_file_name() {
+        let backup = backup_info(
+            "backup_20240116_153000.tar.xz.age",
+            1,
+            "2024-01-16 15:30:00",
+        );
+
+        // Without `*` a case-insensitive substring, with `*` a whole-name glob
+        assert!(BackupQuery::parse("TAR.XZ").matches(&backup));
+        assert!(BackupQuery::parse("*.age").matches(&backup));
+        assert!(BackupQuery::parse("backup_2024*_15*").matches(&backup));
+        assert!(!BackupQuery::parse("*.tar.xz").matches(&backup));
+        assert!(!BackupQuery::parse("restore").matches(&backup));
+    }
+
+    #[test]
+    fn restore_refuses_damaged_archive() {
+        let dir = std::env::temp_dir().join(format!("watcher-test-{}", uuid::Uuid::new_v4()));
+        let source = dir.join("universe");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("world.dat"), b"current").unwrap();
+        let archive = write_test_archive(&dir);
+        let assert_untouched = || {
+            assert_eq!(fs::read(source.join("world.dat")).unwrap(), b"current");
+            assert!(!restore_backup_dir(&source).exists());
+        };
+
+        // A checksum that does not match the archive
+        write_checksum(&archive, &"0".repeat(64)).unwrap();
+        let error = run_restore(&archive, &source, false).unwrap_err();
+        assert!(error.to_string().contains("Checksum mismatch"), "{}", error);
+        assert_untouched();
+        fs::remove_file(checksum_path(&archive)).unwrap();
+
+        // No checksum file and a damaged xz footer
+        let mut bytes = fs::read(&archive).unwrap();
+        let len = bytes.len();
+        bytes[len - 4..].copy_from_slice(b"\0\0\0\0");
+        fs::write(&archive, &bytes).unwrap();
+        let error = run_restore(&archive, &source, false).unwrap_err();
+        assert!(error.to_string().contains("Corrupt archive"), "{}", error);
+        assert_untouched();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
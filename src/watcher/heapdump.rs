@@ -0,0 +1,132 @@
+use crate::config::ServerConfig;
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A `*.hprof` file left behind by an OutOfMemoryError
+#[derive(Debug, Clone, Serialize)]
+pub struct HeapDumpInfo {
+    pub filename: String,
+    pub path: PathBuf,
+    pub size_bytes: u64,
+    pub modified: DateTime<Local>,
+}
+
+/// Where heap dumps end up, for the retention sweep in `BackupManager`
+#[derive(Debug, Clone)]
+pub struct HeapDumpCleanup {
+    pub dirs: Vec<PathBuf>,
+    pub retention_days: u64,
+}
+
+/// The working directory plus the `-XX:HeapDumpPath=` target, if any
+pub fn heap_dump_dirs(server: &ServerConfig) -> Vec<PathBuf> {
+    let working_dir = server
+        .working_directory
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::current_dir().unwrap_or_default());
+
+    let mut dirs = vec![working_dir.clone()];
+    for arg in &server.arguments {
+        if let Some(path) = arg.strip_prefix("-XX:HeapDumpPath=") {
+            let path = working_dir.join(path);
+            // The flag accepts either a directory or a file name
+            let dir = if path.extension().is_some_and(|e| e == "hprof") {
+                path.parent().map(Path::to_path_buf).unwrap_or(path)
+            } else {
+                path
+            };
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+    }
+    dirs
+}
+
+/// Plain `*.hprof` file name, no directory components
+pub fn is_heap_dump_filename(filename: &str) -> bool {
+    filename.len() > ".hprof".len()
+        && filename.ends_with(".hprof")
+        && !filename.contains(['/', '\\'])
+}
+
+/// All heap dumps in `dirs`, newest first
+pub fn list_heap_dumps(dirs: &[PathBuf]) -> Vec<HeapDumpInfo> {
+    let mut dumps = Vec::new();
+
+    for dir in dirs {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let filename = entry.file_name().to_string_lossy().to_string();
+            if !is_heap_dump_filename(&filename) {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+            dumps.push(HeapDumpInfo {
+                filename,
+                path: entry.path(),
+                size_bytes: metadata.len(),
+                modified: metadata
+                    .modified()
+                    .map(DateTime::from)
+                    .unwrap_or_else(|_| Local::now()),
+            });
+        }
+    }
+
+    dumps.sort_by_key(|d| std::cmp::Reverse(d.modified));
+    dumps
+}
+
+/// Delete heap dumps older than `retention_days`, returning what was removed
+pub fn cleanup_heap_dumps(dirs: &[PathBuf], retention_days: u64) -> Vec<HeapDumpInfo> {
+    let retention = Duration::from_secs(retention_days.saturating_mul(86_400));
+    // A retention too long to subtract from now keeps every dump
+    let Some(cutoff) = chrono::Duration::from_std(retention)
+        .ok()
+        .and_then(|retention| Local::now().checked_sub_signed(retention))
+    else {
+        return Vec::new();
+    };
+
+    list_heap_dumps(dirs)
+        .into_iter()
+        .filter(|dump| dump.modified < cutoff)
+        .filter(|dump| match fs::remove_file(&dump.path) {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!("Failed to delete heap dump {:?}: {}", dump.path, e);
+                false
+            }
+        })
+        .collect()
+}
+
+/// Delete one heap dump by file name
+pub fn delete_heap_dump(dirs: &[PathBuf], filename: &str) -> Result<(), std::io::Error> {
+    // Security check
+    if !is_heap_dump_filename(filename) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Invalid heap dump filename",
+        ));
+    }
+
+    let path = dirs
+        .iter()
+        .map(|dir| dir.join(filename))
+        .find(|path| path.is_file())
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Heap dump not found"))?;
+    fs::remove_file(path)
+}
@@ -0,0 +1,212 @@
+use crate::config::LogRotationPolicy;
+use chrono::{DateTime, Local, NaiveDate};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+use xz2::write::XzEncoder;
+
+/// xz level for rotated log files, the xz default preset. Compression runs
+/// on its own thread, so the level does not slow down writes.
+const ROTATED_LOG_COMPRESSION_LEVEL: u32 = 6;
+
+/// Lines waiting for the writer thread before new ones are dropped
+const LOG_FILE_CAPACITY: usize = 8192;
+
+/// Queues lines for the writer thread. Shared between the stdout and
+/// stderr readers and across restarts; the thread exits with the last clone.
+#[derive(Clone)]
+pub struct SharedLogFile {
+    tx: mpsc::Sender<String>,
+}
+
+impl SharedLogFile {
+    /// Never blocks; a line is dropped while the writer is behind
+    pub fn write_line(&self, line: &str) -> Result<(), mpsc::error::TrySendError<String>> {
+        self.tx.try_send(line.to_string())
+    }
+}
+
+/// Decides when the current log file has to be rotated
+struct LogRotator {
+    policy: LogRotationPolicy,
+    /// Day the lines in the current file belong to
+    current_date: NaiveDate,
+}
+
+impl LogRotator {
+    fn is_due(&self, size_bytes: u64, today: NaiveDate) -> bool {
+        let (max_size_mb, daily) = match self.policy {
+            LogRotationPolicy::MaxSize { max_size_mb } => (Some(max_size_mb), false),
+            LogRotationPolicy::Daily => (None, true),
+            LogRotationPolicy::Both {
+                max_size_mb,
+                rotate_daily,
+            } => (Some(max_size_mb), rotate_daily),
+        };
+        let too_big = max_size_mb.is_some_and(|mb| size_bytes >= mb.saturating_mul(1024 * 1024));
+        let new_day = daily && today != self.current_date;
+        size_bytes > 0 && (too_big || new_day)
+    }
+}
+
+/// Appends server output to a file and rotates it per `LogRotationPolicy`.
+/// A rotated file is renamed to `<stem>-<date>.log` and compressed to
+/// `<stem>-<date>.log.xz` on a background thread.
+pub struct LogFileWriter {
+    path: PathBuf,
+    file: File,
+    size_bytes: u64,
+    rotator: LogRotator,
+}
+
+impl LogFileWriter {
+    pub fn open(path: PathBuf, policy: LogRotationPolicy) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let metadata = file.metadata()?;
+        // An existing file keeps the date it was last written on
+        let current_date = metadata
+            .modified()
+            .map(|t| DateTime::<Local>::from(t).date_naive())
+            .unwrap_or_else(|_| Local::now().date_naive());
+
+        Ok(Self {
+            path,
+            file,
+            size_bytes: metadata.len(),
+            rotator: LogRotator {
+                policy,
+                current_date,
+            },
+        })
+    }
+
+    /// Move the writer onto its own thread, so file I/O and rotation never
+    /// hold up the output readers
+    pub fn spawn(mut self) -> io::Result<SharedLogFile> {
+        let (tx, mut rx) = mpsc::channel::<String>(LOG_FILE_CAPACITY);
+        std::thread::Builder::new()
+            .name("server-log-file".to_string())
+            .spawn(move || {
+                while let Some(line) = rx.blocking_recv() {
+                    if let Err(e) = self.write_line(&line) {
+                        tracing::warn!("Failed to write server log file {:?}: {}", self.path, e);
+                    }
+                }
+                let _ = self.file.flush();
+            })?;
+        Ok(SharedLogFile { tx })
+    }
+
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let today = Local::now().date_naive();
+        if self.rotator.is_due(self.size_bytes, today) {
+            self.rotate()?;
+        }
+        self.rotator.current_date = today;
+
+        self.file.write_all(line.as_bytes())?;
+        self.file.write_all(b"\n")?;
+        self.size_bytes += line.len() as u64 + 1;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let rotated = self.rotated_path();
+        fs::rename(&self.path, &rotated)?;
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size_bytes = 0;
+
+        std::thread::spawn(move || {
+            if let Err(e) = compress_rotated(&rotated) {
+                tracing::warn!("Failed to compress rotated log {:?}: {}", rotated, e);
+            }
+        });
+        Ok(())
+    }
+
+    /// `server-2024-01-15.log`, or `server-2024-01-15.1.log` and so on when
+    /// the file was already rotated that day
+    fn rotated_path(&self) -> PathBuf {
+        let stem = self
+            .path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "server".to_string());
+        let base = format!("{}-{}", stem, self.rotator.current_date.format("%Y-%m-%d"));
+
+        (0..)
+            .map(|n| match n {
+                0 => self.path.with_file_name(format!("{}.log", base)),
+                n => self.path.with_file_name(format!("{}.{}.log", base, n)),
+            })
+            .find(|candidate| !candidate.exists() && !compressed_path(candidate).exists())
+            .expect("unbounded range")
+    }
+}
+
+fn compressed_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".xz");
+    PathBuf::from(name)
+}
+
+/// Compress next to the target and rename, so a `.log.xz` is never partial
+fn compress_rotated(path: &Path) -> io::Result<()> {
+    let target = compressed_path(path);
+    let mut partial = target.clone().into_os_string();
+    partial.push(".part");
+    let partial = PathBuf::from(partial);
+
+    let mut encoder = XzEncoder::new(File::create(&partial)?, ROTATED_LOG_COMPRESSION_LEVEL);
+    io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?.sync_all()?;
+
+    fs::rename(&partial, &target)?;
+    fs::remove_file(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn huge_max_size_does_not_overflow() {
+        let today = Local::now().date_naive();
+        let rotator = LogRotator {
+            policy: LogRotationPolicy::MaxSize {
+                max_size_mb: u64::MAX,
+            },
+            current_date: today,
+        };
+        assert!(!rotator.is_due(u64::MAX - 1, today));
+    }
+
+    #[test]
+    fn lines_are_written_by_the_writer_thread() {
+        let dir = std::env::temp_dir().join(format!("watcher-test-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("server.log");
+        let writer = LogFileWriter::open(path.clone(), LogRotationPolicy::Daily)
+            .unwrap()
+            .spawn()
+            .unwrap();
+        writer.write_line("first").unwrap();
+        writer.write_line("second").unwrap();
+        drop(writer);
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while fs::read_to_string(&path).unwrap() != "first\nsecond\n" {
+            assert!(std::time::Instant::now() < deadline);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,173 @@
+use crate::config::WatcherLogFileConfig;
+use crate::watcher::state::LogEntry;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tokio::sync::{mpsc, watch};
+
+/// Entries waiting for the writer before `AppState::add_log` starts dropping
+pub const LOG_SINK_CAPACITY: usize = 8192;
+
+/// Writes watcher log entries as JSON lines and rotates by size.
+/// `<path>.1.gz` is the newest rotated file.
+struct LogSinkWriter {
+    path: PathBuf,
+    file: File,
+    size_bytes: u64,
+    max_size_bytes: u64,
+    max_files: u32,
+}
+
+impl LogSinkWriter {
+    fn open(config: &WatcherLogFileConfig) -> io::Result<Self> {
+        let path = PathBuf::from(&config.path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            size_bytes: file.metadata()?.len(),
+            path,
+            file,
+            max_size_bytes: config.max_size_mb.saturating_mul(1024 * 1024),
+            max_files: config.max_files,
+        })
+    }
+
+    fn write(&mut self, entry: &LogEntry) -> io::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.size_bytes += line.len() as u64;
+        Ok(())
+    }
+
+    /// Shift `<path>.N.gz` up by one, dropping the oldest, then compress
+    /// the current file into `<path>.1.gz`
+    async fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        if self.max_files > 0 {
+            let _ = fs::remove_file(rotated_path(&self.path, self.max_files));
+            for n in (1..self.max_files).rev() {
+                let from = rotated_path(&self.path, n);
+                if from.exists() {
+                    fs::rename(&from, rotated_path(&self.path, n + 1))?;
+                }
+            }
+
+            let mut plain = self.path.as_os_str().to_owned();
+            plain.push(".1");
+            let plain = PathBuf::from(plain);
+            fs::rename(&self.path, &plain)?;
+            let target = rotated_path(&self.path, 1);
+            tokio::task::spawn_blocking(move || compress(&plain, &target))
+                .await
+                .map_err(io::Error::other)??;
+        } else {
+            fs::remove_file(&self.path)?;
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size_bytes = 0;
+        Ok(())
+    }
+}
+
+/// `<path>.<n>.gz`
+fn rotated_path(path: &Path, n: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}.gz", n));
+    PathBuf::from(name)
+}
+
+fn compress(path: &Path, target: &Path) -> io::Result<()> {
+    let mut encoder = GzEncoder::new(File::create(target)?, Compression::default());
+    io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?.sync_all()?;
+    fs::remove_file(path)
+}
+
+/// Drain `rx` into the log file until shutdown, then write what is left
+pub async fn run_log_sink(
+    config: WatcherLogFileConfig,
+    mut rx: mpsc::Receiver<LogEntry>,
+    mut shutdown_rx: watch::Receiver<bool>,
+) {
+    let mut writer = match LogSinkWriter::open(&config) {
+        Ok(writer) => writer,
+        Err(e) => {
+            tracing::error!("Failed to open watcher log file {}: {}", config.path, e);
+            return;
+        }
+    };
+
+    loop {
+        let entry = tokio::select! {
+            entry = rx.recv() => match entry {
+                Some(entry) => entry,
+                None => break,
+            },
+            _ = shutdown_rx.changed() => {
+                if *shutdown_rx.borrow() {
+                    break;
+                }
+                continue;
+            }
+        };
+        write_entry(&mut writer, &entry).await;
+    }
+
+    while let Ok(entry) = rx.try_recv() {
+        write_entry(&mut writer, &entry).await;
+    }
+    let _ = writer.file.flush();
+    tracing::info!("Watcher log writer stopped");
+}
+
+async fn write_entry(writer: &mut LogSinkWriter, entry: &LogEntry) {
+    if let Err(e) = writer.write(entry) {
+        tracing::warn!("Failed to write watcher log file: {}", e);
+        return;
+    }
+    if writer.size_bytes >= writer.max_size_bytes {
+        if let Err(e) = writer.rotate().await {
+            tracing::warn!("Failed to rotate watcher log file: {}", e);
+        }
+    }
+}
+
+/// Every entry in the log file and its rotations, oldest first. Lines
+/// that don't parse are skipped.
+pub fn read_log_files(config: &WatcherLogFileConfig) -> io::Result<Vec<LogEntry>> {
+    use flate2::read::GzDecoder;
+    use std::io::{BufRead, BufReader, Read};
+
+    let path = PathBuf::from(&config.path);
+    let mut readers: Vec<Box<dyn Read>> = Vec::new();
+    for n in (1..=config.max_files).rev() {
+        if let Ok(file) = File::open(rotated_path(&path, n)) {
+            readers.push(Box::new(GzDecoder::new(file)));
+        }
+    }
+    match File::open(&path) {
+        Ok(file) => readers.push(Box::new(file)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    let mut entries = Vec::new();
+    for reader in readers {
+        for line in BufReader::new(reader).lines() {
+            if let Ok(entry) = serde_json::from_str(&line?) {
+                entries.push(entry);
+            }
+        }
+    }
+    Ok(entries)
+}
@@ -3,6 +3,11 @@ pub mod process;
 pub mod backup;
 pub mod stats;
 pub mod telegram;
+pub mod heapdump;
+pub mod analytics;
+pub mod logfile;
+pub mod logsink;
+pub mod s3;
 
 pub use state::*;
 pub use process::*;
@@ -1,114 +1,392 @@
-use crate::config::{Config, ErrorPatterns, RestartConfig};
-use crate::watcher::state::{AppState, LogLevel, LogSource, ServerStatus};
+use crate::config::{
+    Config, ErrorPatterns, PatternEntry, PreLaunchCheck, RestartConfig, StdinTerminator,
+};
+use crate::watcher::logfile::{LogFileWriter, SharedLogFile};
+use crate::watcher::state::{
+    AppState, ExitReport, LogLevel, LogSource, RestartReason, ServerStatus, StateChangeEvent,
+};
 use crate::watcher::telegram::{NotifyType, TelegramClient};
-use encoding_rs::WINDOWS_1251;
+use chrono::Local;
+use encoding_rs::{Encoding, UTF_8};
+use parking_lot::{Mutex, RwLock};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use tokio::io::AsyncWriteExt;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
-use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tokio::process::{Child, Command};
-use tokio::sync::{mpsc, watch};
-use tokio::time::{sleep, Duration};
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
+use tokio::time::{interval, sleep, Duration};
+use utoipa::ToSchema;
 
 /// Commands that can be sent to the process manager
 #[derive(Debug)]
 pub enum ProcessCommand {
     Restart,
     Stop,
-    SendInput(String),
+    /// Start a stopped server; resets the restart counter
+    Start,
+    /// Write a console command to the server's stdin
+    SendInput {
+        command: String,
+        reply: oneshot::Sender<Result<(), StdinWriteError>>,
+    },
+    /// Send a Unix signal to the server process
+    Signal(i32),
+    /// Abort the delay before a pending restart and keep the server stopped;
+    /// while running, skip this run's scheduled auto-restart
+    CancelRestart,
+    /// Write `flag_file` in the working directory and wait for the server
+    /// to create `ack_file`. Replies with the time it took to acknowledge.
+    Checkpoint {
+        flag_file: String,
+        ack_file: String,
+        timeout_secs: u64,
+        reply: oneshot::Sender<Result<Duration, String>>,
+    },
 }
 
+impl ProcessCommand {
+    /// Control commands jump ahead of queued stdin input
+    pub fn is_control(&self) -> bool {
+        !matches!(self, ProcessCommand::SendInput { .. })
+    }
+}
+
+/// Sending half of the process command queue.
+/// Control commands and stdin input travel on separate channels.
+#[derive(Clone)]
+pub struct CommandSender {
+    control_tx: mpsc::Sender<ProcessCommand>,
+    input_tx: mpsc::Sender<ProcessCommand>,
+}
+
+impl CommandSender {
+    pub async fn send(
+        &self,
+        cmd: ProcessCommand,
+    ) -> Result<(), mpsc::error::SendError<ProcessCommand>> {
+        if cmd.is_control() {
+            self.control_tx.send(cmd).await
+        } else {
+            self.input_tx.send(cmd).await
+        }
+    }
+}
+
+/// Receiving half of the process command queue
+pub struct CommandReceiver {
+    control_rx: mpsc::Receiver<ProcessCommand>,
+    input_rx: mpsc::Receiver<ProcessCommand>,
+}
+
+impl CommandReceiver {
+    /// Next command, always preferring pending control commands
+    pub async fn recv(&mut self) -> Option<ProcessCommand> {
+        tokio::select! {
+            biased;
+            Some(cmd) = self.control_rx.recv() => Some(cmd),
+            Some(cmd) = self.input_rx.recv() => Some(cmd),
+            else => None,
+        }
+    }
+}
+
+/// Create the prioritised command queue for `ProcessManager`
+pub fn command_channel(capacity: usize) -> (CommandSender, CommandReceiver) {
+    let (control_tx, control_rx) = mpsc::channel(capacity);
+    let (input_tx, input_rx) = mpsc::channel(capacity);
+    (
+        CommandSender {
+            control_tx,
+            input_tx,
+        },
+        CommandReceiver {
+            control_rx,
+            input_rx,
+        },
+    )
+}
+
+/// Lines kept for the exit report
+const EXIT_LINES: usize = 50;
+
+/// How often configured FIFOs are checked for removal
+const FIFO_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A process exit this soon after a console command counts as requested
+const COMMAND_EXIT_WINDOW: Duration = Duration::from_secs(10);
+
+/// `pre_restart_command` / `post_start_command` are killed after this long
+const HOOK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// After a graceful stop, how long the output readers get to reach EOF
+const OUTPUT_DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A held exception line is logged after this long without more output
+const STACK_TRACE_FLUSH_DELAY: Duration = Duration::from_millis(500);
+
+/// A fully qualified exception class, e.g. `java.lang.IllegalStateException`
+const EXCEPTION_LINE_PATTERN: &str =
+    r"(?:^|[\s:\[])(?:[a-zA-Z_$][\w$]*\.)+[A-Z][\w$]*(?:Exception|Error|Throwable)\b";
+
+/// SGR sequences such as `\x1b[32m`, removed with `server.strip_ansi`
+const ANSI_COLOUR_PATTERN: &str = r"\x1b\[[0-9;]*m";
+
+/// Server stdin: the child's pipe or the writer end of `stdin_fifo_path`
+type ServerStdin = Box<dyn AsyncWrite + Send + Unpin>;
+/// Server output: the child's pipe or the reader end of `stdout_fifo_path`
+type ServerStdout = Box<dyn AsyncRead + Send + Unpin>;
+
 pub struct ProcessManager {
+    /// Settings for the current run, refreshed before each start and restart
     config: Config,
+    /// Live config, changed by reloads and the API
+    shared_config: Arc<RwLock<Config>>,
     state: Arc<AppState>,
     telegram: Option<TelegramClient>,
     shutdown_rx: watch::Receiver<bool>,
-    command_rx: mpsc::Receiver<ProcessCommand>,
+    command_rx: CommandReceiver,
+    /// `error_patterns`, compiled once
+    error_patterns: Arc<CompiledPatterns>,
+    /// Matches ANSI colour codes; None unless `server.strip_ansi`
+    ansi_regex: Option<Arc<regex::Regex>>,
+    /// Last output lines of the current process (stdout and stderr)
+    recent_lines: Arc<Mutex<VecDeque<String>>>,
+    /// `server.log_file`, kept open across restarts
+    log_file: Option<SharedLogFile>,
+    /// `server.encoding`, for stdin commands and server output
+    encoding: &'static Encoding,
+    /// Crash restarts since the server last stayed up long enough
+    consecutive_crashes: u32,
+    /// When the last console command was sent, to tell a `stop` typed by an
+    /// operator apart from a crash
+    last_console_command: Option<Instant>,
 }
 
 impl ProcessManager {
     pub fn new(
-        config: Config,
+        shared_config: Arc<RwLock<Config>>,
         state: Arc<AppState>,
         telegram: Option<TelegramClient>,
         shutdown_rx: watch::Receiver<bool>,
-        command_rx: mpsc::Receiver<ProcessCommand>,
+        command_rx: CommandReceiver,
     ) -> Self {
+        let config = shared_config.read().clone();
+        let log_file = config.server.log_file.as_ref().and_then(|log_file| {
+            let path = match config.server.working_directory {
+                Some(ref dir) => Path::new(dir).join(&log_file.path),
+                None => PathBuf::from(&log_file.path),
+            };
+            match LogFileWriter::open(path.clone(), log_file.rotation.clone())
+                .and_then(LogFileWriter::spawn)
+            {
+                Ok(writer) => Some(writer),
+                Err(e) => {
+                    state.add_log(
+                        LogLevel::Error,
+                        LogSource::Watcher,
+                        format!("Failed to open log file {}: {}", path.display(), e),
+                    );
+                    None
+                }
+            }
+        });
+
+        let label = config.server.encoding.as_deref().unwrap_or("utf-8");
+        let encoding = resolve_encoding(label);
+        if Encoding::for_label(label.as_bytes()).is_none() {
+            state.add_log(
+                LogLevel::Warning,
+                LogSource::Watcher,
+                format!("Unknown encoding '{}', using UTF-8", label),
+            );
+        }
+
+        let error_patterns = Arc::new(CompiledPatterns::new(&config.error_patterns));
+        let ansi_regex = config
+            .server
+            .strip_ansi
+            .then(|| Arc::new(regex::Regex::new(ANSI_COLOUR_PATTERN).unwrap()));
+
         Self {
             config,
+            shared_config,
             state,
             telegram,
             shutdown_rx,
             command_rx,
+            error_patterns,
+            ansi_regex,
+            recent_lines: Arc::new(Mutex::new(VecDeque::with_capacity(EXIT_LINES))),
+            log_file,
+            encoding,
+            consecutive_crashes: 0,
+            last_console_command: None,
         }
     }
 
     pub async fn run(mut self) {
+        refresh_java_version(&self.config.server.executable, &self.state).await;
+        self.state
+            .set_restart_delay_secs(self.config.server.error_restart_delay_secs);
+
+        if !self.wait_before_first_start().await {
+            self.state.set_status(ServerStatus::Stopped);
+            tracing::info!("Process manager stopped");
+            return;
+        }
+
         loop {
+            self.reload_config();
+
             // Check restart limit
             if let Some(max) = self.config.server.max_restarts {
                 if self.state.restart_count() >= max {
                     self.state.add_watcher_log("Max restart limit reached".to_string());
-                    break;
+                    if !self.wait_for_start().await {
+                        break;
+                    }
                 }
             }
 
             // Check shutdown
-            if *self.shutdown_rx.borrow() {
+            if *self.shutdown_rx.borrow() || !self.wait_for_restore().await {
                 break;
             }
 
             // Start server
             self.state.set_status(ServerStatus::Starting);
+            self.state.reset_log_dedup();
             self.state.add_watcher_log(format!(
                 "Starting server: {} {}",
                 self.config.server.executable,
                 self.config.server.arguments.join(" ")
             ));
 
-            match self.spawn_server().await {
-                Ok(mut child) => {
+            match self.spawn_with_retries().await {
+                Ok((mut child, stdin, stdout)) => {
+                    let spawned_at = Instant::now();
+                    self.recent_lines.lock().clear();
                     let pid = child.id().unwrap_or(0);
                     self.state.set_pid(Some(pid));
-                    self.state.set_status(ServerStatus::Running);
+                    // With a startup pattern the output decides when it is running
+                    if self.config.server.startup_pattern.is_none() {
+                        self.state.set_status(ServerStatus::Running);
+                    }
                     self.state.set_start_time(Some(Instant::now()));
                     self.state.add_watcher_log(format!("Server started with PID: {}", pid));
 
                     if let Some(ref tg) = self.telegram {
-                        tg.notify(NotifyType::Start, &format!("Server started (PID: {})", pid))
-                            .await;
+                        let working_dir = self
+                            .config
+                            .server
+                            .working_directory
+                            .clone()
+                            .or_else(|| {
+                                std::env::current_dir()
+                                    .ok()
+                                    .map(|d| d.display().to_string())
+                            })
+                            .unwrap_or_default();
+                        tg.notify_start(pid, &working_dir).await;
                     }
 
                     // Run until exit or command
-                    let exit_reason = self.monitor_process(&mut child).await;
+                    let exit_reason = self
+                        .monitor_process(&mut child, stdin, stdout, spawned_at)
+                        .await;
 
-                    // Cleanup
-                    let _ = child.kill().await;
-                    let _ = child.wait().await;
+                    // Cleanup. A process that closed its output is given a
+                    // moment to exit by itself so its exit code is kept.
+                    let exited = if matches!(exit_reason, ExitReason::ProcessExit) {
+                        tokio::time::timeout(Duration::from_secs(2), child.wait())
+                            .await
+                            .ok()
+                            .and_then(|r| r.ok())
+                    } else {
+                        None
+                    };
+                    let exit_status = match exited {
+                        Some(status) => Some(status),
+                        None => {
+                            let _ = child.kill().await;
+                            child.wait().await.ok()
+                        }
+                    };
+
+                    if !matches!(exit_reason, ExitReason::Shutdown) {
+                        self.state.set_last_exit(ExitReport {
+                            exit_reason: exit_reason.as_str().to_string(),
+                            exit_code: exit_status.and_then(|s| s.code()),
+                            last_lines: self.recent_lines.lock().iter().cloned().collect(),
+                            timestamp: Local::now(),
+                        });
+                    }
 
                     self.state.set_pid(None);
                     self.state.set_start_time(None);
                     self.state.set_auto_restart_remaining(None);
 
+                    // A run this long means the crash loop (if any) is over
+                    let stable_secs = self
+                        .config
+                        .server
+                        .error_restart_delay_secs
+                        .saturating_mul(2);
+                    if spawned_at.elapsed() >= Duration::from_secs(stable_secs) {
+                        self.reset_restart_backoff();
+                    }
+                    if let Some(secs) = self.config.server.restart_count_reset_after_uptime_secs {
+                        let ran_for = spawned_at.elapsed();
+                        if ran_for >= Duration::from_secs(secs) && self.state.restart_count() > 0 {
+                            self.state.reset_restart_count();
+                            self.state.add_watcher_log(format!(
+                                "Restart counter reset after {}s of uptime",
+                                ran_for.as_secs()
+                            ));
+                        }
+                    }
+
                     match exit_reason {
                         ExitReason::Shutdown => {
                             self.state.set_status(ServerStatus::Stopped);
                             break;
                         }
-                        ExitReason::Restart | ExitReason::ProcessExit | ExitReason::Error => {
+                        ExitReason::ProcessExit | ExitReason::Error
+                            if self.state.in_maintenance() && !*self.shutdown_rx.borrow() =>
+                        {
+                            if !self.wait_out_maintenance().await {
+                                break;
+                            }
+                        }
+                        ExitReason::Restart(_) | ExitReason::ProcessExit | ExitReason::Error => {
                             if !*self.shutdown_rx.borrow() {
-                                self.handle_restart().await;
+                                let restart_reason = match exit_reason {
+                                    ExitReason::ProcessExit if self.exited_after_command() => {
+                                        RestartReason::Command
+                                    }
+                                    _ => exit_reason.restart_reason(),
+                                };
+                                if !self.handle_restart(exit_reason, restart_reason).await
+                                    && !self.wait_for_start().await
+                                {
+                                    break;
+                                }
                             } else {
                                 self.state.set_status(ServerStatus::Stopped);
                                 break;
                             }
                         }
                         ExitReason::Stopped => {
-                            self.state.set_status(ServerStatus::Stopped);
                             self.state.add_watcher_log("Server stopped normally".to_string());
-                            break;
+                            if !self.wait_for_start().await {
+                                break;
+                            }
                         }
                     }
                 }
@@ -119,15 +397,27 @@ impl ProcessManager {
                         LogSource::Watcher,
                         format!("Failed to start: {}", e),
                     );
-                    self.state.increment_restart_count();
+                    self.state
+                        .record_restart("spawn_failed", RestartReason::Error);
 
                     if let Some(ref tg) = self.telegram {
                         tg.notify(NotifyType::Critical, &format!("Failed to start: {}", e))
                             .await;
                     }
 
-                    // Wait before retry
-                    sleep(Duration::from_secs(self.config.server.restart_delay_seconds)).await;
+                    // Back off like a crash; shutdown cuts the wait short
+                    let delay_secs = self.crash_restart_delay(self.consecutive_crashes);
+                    self.consecutive_crashes = self.consecutive_crashes.saturating_add(1);
+                    self.state
+                        .set_restart_delay_secs(self.crash_restart_delay(self.consecutive_crashes));
+                    tokio::select! {
+                        _ = sleep(Duration::from_secs(delay_secs)) => {}
+                        changed = self.shutdown_rx.changed() => {
+                            if changed.is_err() {
+                                break;
+                            }
+                        }
+                    }
                 }
             }
         }
@@ -136,26 +426,282 @@ impl ProcessManager {
         tracing::info!("Process manager stopped");
     }
 
-    async fn spawn_server(&self) -> Result<Child, std::io::Error> {
+    /// Maintenance mode: leave a crashed server stopped until maintenance
+    /// ends or a restart is requested. Returns false if the manager should exit.
+    async fn wait_out_maintenance(&mut self) -> bool {
+        self.state.set_status(ServerStatus::Stopped);
+        self.state
+            .add_watcher_log("Maintenance mode: not restarting the server".to_string());
+
+        let mut ticker = interval(Duration::from_secs(1));
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    if !self.state.in_maintenance() {
+                        self.state.add_watcher_log("Maintenance mode ended".to_string());
+                        return true;
+                    }
+                }
+                _ = self.shutdown_rx.changed() => {
+                    if *self.shutdown_rx.borrow() {
+                        return false;
+                    }
+                }
+                Some(cmd) = self.command_rx.recv() => {
+                    match cmd {
+                        ProcessCommand::Restart | ProcessCommand::Start => {
+                            self.state
+                                .add_watcher_log("Manual start during maintenance".to_string());
+                            return self.wait_for_restore().await;
+                        }
+                        // Stays stopped past the end of maintenance
+                        ProcessCommand::Stop => return self.wait_for_start().await,
+                        ProcessCommand::Checkpoint { reply, .. } => {
+                            let _ = reply.send(Err("Server is not running".to_string()));
+                        }
+                        ProcessCommand::SendInput { reply, .. } => {
+                            let _ = reply.send(Err(StdinWriteError::NotRunning));
+                        }
+                        ProcessCommand::Signal(_) | ProcessCommand::CancelRestart => {}
+                    }
+                }
+            }
+        }
+    }
+
+    /// Keep the server stopped until `Start` is requested. Returns false if
+    /// the manager should exit.
+    async fn wait_for_start(&mut self) -> bool {
+        self.state.set_status(ServerStatus::Stopped);
+
+        loop {
+            tokio::select! {
+                _ = self.shutdown_rx.changed() => {
+                    if *self.shutdown_rx.borrow() {
+                        return false;
+                    }
+                }
+                cmd = self.command_rx.recv() => {
+                    match cmd {
+                        None => return false,
+                        Some(ProcessCommand::Start) => {
+                            self.state.reset_restart_count();
+                            self.reset_restart_backoff();
+                            self.state.add_watcher_log("Manual start requested".to_string());
+                            return self.wait_for_restore().await;
+                        }
+                        Some(ProcessCommand::Checkpoint { reply, .. }) => {
+                            let _ = reply.send(Err("Server is not running".to_string()));
+                        }
+                        Some(ProcessCommand::SendInput { reply, .. }) => {
+                            let _ = reply.send(Err(StdinWriteError::NotRunning));
+                        }
+                        Some(_) => {}
+                    }
+                }
+            }
+        }
+    }
+
+    /// Hold a start until a running restore has swapped the source folder.
+    /// Returns false if shutdown was requested while waiting.
+    async fn wait_for_restore(&mut self) -> bool {
+        if !self.state.restore_in_progress() {
+            return true;
+        }
+        self.state
+            .add_watcher_log("Waiting for the backup restore to finish".to_string());
+
+        let mut ticker = interval(Duration::from_millis(500));
+        while self.state.restore_in_progress() {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = self.shutdown_rx.changed() => {
+                    if *self.shutdown_rx.borrow() {
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    /// Apply `pre_start_delay_secs` and `pre_start_checks`.
+    /// Returns false if shutdown was requested while waiting.
+    async fn wait_before_first_start(&self) -> bool {
+        let delay = self.config.server.pre_start_delay_secs;
+        if delay > 0 {
+            self.state
+                .add_watcher_log(format!("Waiting {} seconds before first start", delay));
+            for remaining in (1..=delay).rev() {
+                if *self.shutdown_rx.borrow() {
+                    return false;
+                }
+                if remaining != delay && remaining % 10 == 0 {
+                    self.state
+                        .add_watcher_log(format!("Starting in {} seconds...", remaining));
+                }
+                sleep(Duration::from_secs(1)).await;
+            }
+        }
+
+        let checks = &self.config.server.pre_start_checks;
+        if checks.is_empty() {
+            return true;
+        }
+
+        loop {
+            let mut failed = Vec::new();
+            for check in checks {
+                if let Err(e) = run_pre_launch_check(check).await {
+                    failed.push(e);
+                }
+            }
+
+            if failed.is_empty() {
+                self.state
+                    .add_watcher_log("Pre-start checks passed".to_string());
+                return true;
+            }
+
+            self.state.add_log(
+                LogLevel::Warning,
+                LogSource::Watcher,
+                format!(
+                    "Pre-start checks failed, retrying in 5s: {}",
+                    failed.join("; ")
+                ),
+            );
+
+            for _ in 0..5 {
+                if *self.shutdown_rx.borrow() {
+                    return false;
+                }
+                sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+
+    /// `spawn_server`, retried a few times after a short delay since
+    /// transient failures such as EAGAIN usually clear up quickly
+    async fn spawn_with_retries(
+        &self,
+    ) -> Result<(Child, Option<ServerStdin>, Option<ServerStdout>), std::io::Error> {
+        let retries = self.config.server.spawn_retry_count;
+        let delay = Duration::from_millis(self.config.server.spawn_retry_delay_ms);
+        let mut attempt = 0;
+        loop {
+            match self.spawn_server().await {
+                Err(e) if attempt < retries => {
+                    attempt += 1;
+                    self.state.add_watcher_log(format!(
+                        "Failed to start: {}, retrying in {} ms ({}/{})",
+                        e,
+                        delay.as_millis(),
+                        attempt,
+                        retries
+                    ));
+                    sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn spawn_server(
+        &self,
+    ) -> Result<(Child, Option<ServerStdin>, Option<ServerStdout>), std::io::Error> {
+        let arguments = if self.config.server.interpolate_args {
+            interpolate_args(&self.config.server.arguments, &self.state)
+        } else {
+            self.config.server.arguments.clone()
+        };
+
         let mut command = Command::new(&self.config.server.executable);
         command
-            .args(&self.config.server.arguments)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
+            .args(&arguments)
             .stderr(Stdio::piped())
             .kill_on_drop(true);
 
         if let Some(ref dir) = self.config.server.working_directory {
             command.current_dir(dir);
         }
+        if self.config.server.clear_env {
+            command.env_clear();
+        }
+        command.envs(&self.config.server.env_vars);
+
+        let stdin_fifo = match self.fifo_path(&self.config.server.stdin_fifo_path) {
+            Some(path) => {
+                let (child_end, writer) = open_stdin_fifo(&path)?;
+                command.stdin(child_end);
+                Some(writer)
+            }
+            None => {
+                command.stdin(Stdio::piped());
+                None
+            }
+        };
+        let stdout_fifo = match self.fifo_path(&self.config.server.stdout_fifo_path) {
+            Some(path) => {
+                let (child_end, reader) = open_stdout_fifo(&path)?;
+                command.stdout(child_end);
+                Some(reader)
+            }
+            None => {
+                command.stdout(Stdio::piped());
+                None
+            }
+        };
+
+        let mut child = command.spawn()?;
+        let stdin = stdin_fifo.or_else(|| {
+            child
+                .stdin
+                .take()
+                .map(|stdin| Box::new(stdin) as ServerStdin)
+        });
+        let stdout = stdout_fifo.or_else(|| {
+            child
+                .stdout
+                .take()
+                .map(|stdout| Box::new(stdout) as ServerStdout)
+        });
+
+        Ok((child, stdin, stdout))
+    }
 
-        command.spawn()
+    /// FIFO paths are relative to the server's working directory
+    fn fifo_path(&self, path: &Option<String>) -> Option<PathBuf> {
+        let path = path.as_ref()?;
+        Some(match self.config.server.working_directory {
+            Some(ref dir) => Path::new(dir).join(path),
+            None => PathBuf::from(path),
+        })
     }
 
-    async fn monitor_process(&mut self, child: &mut Child) -> ExitReason {
-        let stdout = child.stdout.take();
+    async fn monitor_process(
+        &mut self,
+        child: &mut Child,
+        stdin: Option<ServerStdin>,
+        stdout: Option<ServerStdout>,
+        spawned_at: Instant,
+    ) -> ExitReason {
+        let pid = child.id();
         let stderr = child.stderr.take();
-        let stdin = child.stdin.take();
+
+        self.state.clear_startup_latency();
+        let startup = Arc::new(StartupDetector::new(
+            self.config.server.startup_pattern.clone(),
+            spawned_at,
+        ));
+
+        // Without a startup pattern the server counts as running right away
+        let mut post_start_pending = self.config.server.post_start_command.is_some();
+        if post_start_pending && self.config.server.startup_pattern.is_none() {
+            post_start_pending = false;
+            self.spawn_post_start_hook();
+        }
 
         let found_error = Arc::new(AtomicBool::new(false));
         let force_restart = Arc::new(AtomicBool::new(false));
@@ -163,45 +709,107 @@ impl ProcessManager {
 
         // Stderr reader task
         let state_err = Arc::clone(&self.state);
-        let patterns_err = self.config.error_patterns.clone();
+        let patterns_err = Arc::clone(&self.error_patterns);
         let restart_on_err = self.config.restart_on.clone();
         let found_error_err = Arc::clone(&found_error);
         let telegram_err = self.telegram.clone();
+        let recent_err = Arc::clone(&self.recent_lines);
+        let mut sampler_err = LogSampler::new(self.config.server.log_sample_rate);
+        let startup_err = Arc::clone(&startup);
+        let log_file_err = self.log_file.clone();
+        let ansi_err = self.ansi_regex.clone();
+        let assemble_traces = self.config.server.assemble_stack_traces;
+        let encoding = self.encoding;
 
-        let stderr_task = tokio::spawn(async move {
+        let mut stderr_task = tokio::spawn(async move {
             if let Some(stderr) = stderr {
-                let reader = BufReader::new(stderr);
-                let mut lines = reader.lines();
-                while let Ok(Some(line)) = lines.next_line().await {
-                    let level = detect_error_level(&line, &patterns_err);
-                    state_err.add_log(level, LogSource::Stderr, line.clone());
-
-                    if should_restart(level, &restart_on_err) {
-                        found_error_err.store(true, Ordering::SeqCst);
-                        if let Some(ref tg) = telegram_err {
-                            tg.notify(NotifyType::Error, &line).await;
+                let mut reader = BufReader::new(stderr);
+                let mut buf = Vec::new();
+                let mut traces = StackTraceAssembler::new(assemble_traces);
+                loop {
+                    let (ready, eof) =
+                        match read_line_flushing(&mut reader, &mut buf, encoding, &traces).await {
+                            Ok(Some(line)) => {
+                                let line = strip_ansi(ansi_err.as_deref(), line);
+                                push_recent(&recent_err, &line);
+                                append_log_file(log_file_err.as_ref(), &line);
+                                startup_err.check(&line, &state_err);
+                                (traces.push(line), false)
+                            }
+                            Ok(None) => (traces.finish(), true),
+                            // Output went quiet; log the held trace
+                            Err(_) => (traces.finish(), false),
+                        };
+                    for line in ready {
+                        // A stack trace is matched by its exception line
+                        let head = line.lines().next().unwrap_or_default();
+                        // Sample before the full pattern scan, the costly part
+                        if !sampler_err.keep(|| patterns_err.is_severe(head, LogSource::Stderr)) {
+                            state_err.increment_dropped_log_lines();
+                            continue;
+                        }
+                        let level = patterns_err.detect_level(head, LogSource::Stderr);
+                        let fields = patterns_err.extract_fields(head, LogSource::Stderr);
+                        state_err.add_log_with_fields(
+                            level,
+                            LogSource::Stderr,
+                            line.clone(),
+                            fields,
+                        );
+
+                        if should_restart(level, &restart_on_err) && !state_err.in_maintenance() {
+                            if patterns_err.is_suppressed(head) {
+                                log_suppressed_restart(&state_err, head);
+                                continue;
+                            }
+                            found_error_err.store(true, Ordering::SeqCst);
+                            if let Some(ref tg) = telegram_err {
+                                tg.notify_log_line(NotifyType::Error, head).await;
+                            }
                         }
                     }
+                    if eof {
+                        break;
+                    }
                 }
             }
         });
 
         // Auto-restart timer task
-        let auto_restart_task = if self.config.server.auto_restart_hourly {
+        // Shared by the auto-restart warning and `SendInput`
+        let stdin = Arc::new(tokio::sync::Mutex::new(stdin));
+
+        let auto_restart_task = if let Some(minutes) =
+            self.config.server.auto_restart_interval_minutes()
+        {
             let state_auto = Arc::clone(&self.state);
             let auto_restart_triggered_clone = Arc::clone(&auto_restart_triggered);
             let telegram_auto = self.telegram.clone();
             let warning_message = self.config.server.restart_warning_message.clone();
-            let stdin_arc = Arc::new(tokio::sync::Mutex::new(stdin));
-            let stdin_for_task = Arc::clone(&stdin_arc);
+            let stdin_for_task = Arc::clone(&stdin);
+            let config_auto = Arc::clone(&self.shared_config);
+            let maintenance_windows = self.config.server.maintenance_windows.clone();
 
-            self.state.set_auto_restart_remaining(Some(60 * 60));
+            let restart_time = Duration::from_secs(minutes.saturating_mul(60));
+            // Largest first; marks at or past the interval length never fire
+            let mut warning_minutes: Vec<u64> = self
+                .config
+                .server
+                .restart_warning_minutes
+                .iter()
+                .copied()
+                .filter(|&m| m > 0 && m < minutes)
+                .collect();
+            warning_minutes.sort_unstable_by(|a, b| b.cmp(a));
+            warning_minutes.dedup();
+
+            self.state
+                .set_auto_restart_remaining(Some(restart_time.as_secs()));
 
             Some(tokio::spawn(async move {
-                let start = Instant::now();
-                let warning_time = Duration::from_secs(59 * 60);
-                let restart_time = Duration::from_secs(60 * 60);
-                let mut warning_sent = false;
+                let mut start = Instant::now();
+                let mut warnings_sent = 0;
+                let mut held_by_window = false;
 
                 loop {
                     sleep(Duration::from_secs(1)).await;
@@ -210,31 +818,71 @@ impl ProcessManager {
                     let remaining = restart_time.saturating_sub(elapsed).as_secs();
                     state_auto.set_auto_restart_remaining(Some(remaining));
 
-                    // Warning at 59 minutes
-                    if elapsed >= warning_time && !warning_sent {
-                        warning_sent = true;
-                        state_auto.add_watcher_log("Auto-restart: sending warning".to_string());
+                    // Each mark is sent once; a late tick skips straight to
+                    // the smallest mark already reached
+                    let due = warning_minutes[warnings_sent..]
+                        .iter()
+                        .rposition(|&m| remaining <= m.saturating_mul(60));
+                    if let Some(offset) = due {
+                        warnings_sent += offset + 1;
+                        let left = warning_minutes[warnings_sent - 1];
+                        state_auto.add_watcher_log(format!(
+                            "Auto-restart: sending warning ({} min left)",
+                            left
+                        ));
 
                         if let Ok(mut stdin_guard) = stdin_for_task.try_lock() {
                             if let Some(ref mut stdin) = *stdin_guard {
-                                let cmd = format!("broadcast {}\n", warning_message);
-                                let (encoded, _, _) = WINDOWS_1251.encode(&cmd);
-                                let _ = stdin.write_all(&encoded).await;
-                                let _ = stdin.flush().await;
+                                let message =
+                                    warning_message.replace("{minutes}", &left.to_string());
+                                let cmd = format!("broadcast {}", message);
+                                let (stdin_timeout, terminator) =
+                                    stdin_settings(&config_auto.read());
+                                let encoded = encode_command(&cmd, encoding, terminator);
+                                let _ =
+                                    write_stdin(stdin, &encoded, stdin_timeout, &state_auto).await;
                             }
                         }
 
                         if let Some(ref tg) = telegram_auto {
-                            tg.notify(NotifyType::Info, "Auto-restart warning (1 min remaining)")
-                                .await;
+                            tg.notify(
+                                NotifyType::Info,
+                                &format!("Auto-restart warning ({} min remaining)", left),
+                            )
+                            .await;
                         }
                     }
 
-                    // Restart at 60 minutes
+                    // Restart at the end of the interval
+                    if elapsed >= restart_time && state_auto.in_maintenance() {
+                        state_auto.add_watcher_log(
+                            "Auto-restart: skipped during maintenance".to_string(),
+                        );
+                        start = Instant::now();
+                        warnings_sent = 0;
+                        continue;
+                    }
                     if elapsed >= restart_time {
-                        state_auto.add_watcher_log("Auto-restart: hourly restart".to_string());
+                        let now = Local::now().naive_local();
+                        let window_end = maintenance_windows
+                            .iter()
+                            .find_map(|window| window.end_if_active(now));
+                        if let Some(end) = window_end {
+                            if !held_by_window {
+                                held_by_window = true;
+                                state_auto.add_watcher_log(format!(
+                                    "Auto-restart: held by maintenance window until {}",
+                                    end.format("%H:%M")
+                                ));
+                            }
+                            continue;
+                        }
+                        state_auto.add_watcher_log(format!(
+                            "Auto-restart: scheduled restart after {} min",
+                            minutes
+                        ));
                         if let Some(ref tg) = telegram_auto {
-                            tg.notify(NotifyType::Restart, "Hourly auto-restart triggered")
+                            tg.notify(NotifyType::Restart, "Scheduled auto-restart triggered")
                                 .await;
                         }
                         auto_restart_triggered_clone.store(true, Ordering::SeqCst);
@@ -248,52 +896,137 @@ impl ProcessManager {
 
         // Stdout reader (main loop)
         let state_out = Arc::clone(&self.state);
-        let patterns_out = self.config.error_patterns.clone();
+        let patterns_out = Arc::clone(&self.error_patterns);
         let restart_on_out = self.config.restart_on.clone();
         let found_error_out = Arc::clone(&found_error);
         let auto_restart_out = Arc::clone(&auto_restart_triggered);
         let force_restart_out = Arc::clone(&force_restart);
         let telegram_out = self.telegram.clone();
+        let recent_out = Arc::clone(&self.recent_lines);
+        let mut sampler_out = LogSampler::new(self.config.server.log_sample_rate);
+        let startup_out = Arc::clone(&startup);
+        let log_file_out = self.log_file.clone();
+        let ansi_out = self.ansi_regex.clone();
 
         let stdout_task = tokio::spawn(async move {
             if let Some(stdout) = stdout {
-                let reader = BufReader::new(stdout);
-                let mut lines = reader.lines();
+                let mut reader = BufReader::new(stdout);
+                let mut buf = Vec::new();
+                let mut traces = StackTraceAssembler::new(assemble_traces);
 
-                while let Ok(Some(line)) = lines.next_line().await {
-                    if force_restart_out.load(Ordering::SeqCst)
-                        || auto_restart_out.load(Ordering::SeqCst)
-                    {
-                        break;
-                    }
+                'lines: loop {
+                    let (ready, eof) =
+                        match read_line_flushing(&mut reader, &mut buf, encoding, &traces).await {
+                            Ok(Some(line)) => {
+                                if force_restart_out.load(Ordering::SeqCst)
+                                    || auto_restart_out.load(Ordering::SeqCst)
+                                {
+                                    break;
+                                }
 
-                    let level = detect_error_level(&line, &patterns_out);
-                    state_out.add_log(level, LogSource::Server, line.clone());
+                                let line = strip_ansi(ansi_out.as_deref(), line);
+                                push_recent(&recent_out, &line);
+                                append_log_file(log_file_out.as_ref(), &line);
+                                startup_out.check(&line, &state_out);
+                                (traces.push(line), false)
+                            }
+                            Ok(None) => (traces.finish(), true),
+                            // Output went quiet; log the held trace
+                            Err(_) => (traces.finish(), false),
+                        };
+                    for line in ready {
+                        // A stack trace is matched by its exception line
+                        let head = line.lines().next().unwrap_or_default();
+                        // Sample before the full pattern scan, the costly part
+                        if !sampler_out.keep(|| patterns_out.is_severe(head, LogSource::Server)) {
+                            state_out.increment_dropped_log_lines();
+                            continue;
+                        }
+                        let level = patterns_out.detect_level(head, LogSource::Server);
+                        let fields = patterns_out.extract_fields(head, LogSource::Server);
+                        state_out.add_log_with_fields(
+                            level,
+                            LogSource::Server,
+                            line.clone(),
+                            fields,
+                        );
 
-                    if should_restart(level, &restart_on_out) {
-                        found_error_out.store(true, Ordering::SeqCst);
-                        if let Some(ref tg) = telegram_out {
-                            let notify_type = match level {
-                                LogLevel::Critical => NotifyType::Critical,
-                                _ => NotifyType::Error,
-                            };
-                            tg.notify(notify_type, &line).await;
+                        if should_restart(level, &restart_on_out) && !state_out.in_maintenance() {
+                            if patterns_out.is_suppressed(head) {
+                                log_suppressed_restart(&state_out, head);
+                                continue;
+                            }
+                            found_error_out.store(true, Ordering::SeqCst);
+                            if let Some(ref tg) = telegram_out {
+                                let notify_type = match level {
+                                    LogLevel::Critical => NotifyType::Critical,
+                                    _ => NotifyType::Error,
+                                };
+                                tg.notify_log_line(notify_type, head).await;
+                            }
+                            break 'lines;
                         }
+                    }
+                    if eof {
                         break;
                     }
                 }
             }
         });
 
+        // Fail-safe recycle after max_runtime_secs
+        let max_runtime = self.config.server.max_runtime_secs.map(Duration::from_secs);
+        let runtime_limit = sleep(max_runtime.unwrap_or_default());
+
+        // Slow start counts as a hung server
+        let max_startup = self
+            .config
+            .server
+            .max_startup_latency_secs
+            .map(Duration::from_secs);
+        let mut startup_deadline_armed =
+            max_startup.is_some() && self.config.server.startup_pattern.is_some();
+        let startup_deadline = tokio::time::sleep_until(
+            tokio::time::Instant::from_std(spawned_at) + max_startup.unwrap_or_default(),
+        );
+
+        // A server that never becomes ready is a failed start
+        let ready_timeout = self
+            .config
+            .server
+            .server_ready_timeout_secs
+            .map(Duration::from_secs);
+        let mut ready_deadline_armed =
+            ready_timeout.is_some() && self.config.server.startup_pattern.is_some();
+        let ready_deadline = tokio::time::sleep_until(
+            tokio::time::Instant::from_std(spawned_at) + ready_timeout.unwrap_or_default(),
+        );
+
         // Wait for exit conditions
         tokio::pin!(stdout_task);
+        tokio::pin!(runtime_limit);
+        tokio::pin!(startup_deadline);
+        tokio::pin!(ready_deadline);
+
+        // Set together with `force_restart`
+        let mut restart_reason = RestartReason::Manual;
+
+        // An unlinked FIFO keeps working for this process, but nothing new can
+        // open it. Warn once; it is created again on the next start.
+        let fifo_paths: Vec<PathBuf> = [
+            self.fifo_path(&self.config.server.stdin_fifo_path),
+            self.fifo_path(&self.config.server.stdout_fifo_path),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        let mut removed_fifos: Vec<PathBuf> = Vec::new();
+        let mut fifo_check = interval(FIFO_CHECK_INTERVAL);
 
         let exit_reason = loop {
             tokio::select! {
                 _ = self.shutdown_rx.changed() => {
                     if *self.shutdown_rx.borrow() {
-                        stderr_task.abort();
-                        stdout_task.abort();
                         if let Some(ref t) = auto_restart_task { t.abort(); }
                         break ExitReason::Shutdown;
                     }
@@ -301,19 +1034,170 @@ impl ProcessManager {
                 Some(cmd) = self.command_rx.recv() => {
                     match cmd {
                         ProcessCommand::Restart => {
+                            restart_reason = RestartReason::Manual;
                             force_restart.store(true, Ordering::SeqCst);
                             self.state.add_watcher_log("Manual restart requested".to_string());
                             // Signal stdout to break
                             stdout_task.abort();
                         }
                         ProcessCommand::Stop => {
-                            stderr_task.abort();
-                            stdout_task.abort();
                             if let Some(ref t) = auto_restart_task { t.abort(); }
                             break ExitReason::Stopped;
                         }
-                        ProcessCommand::SendInput(_input) => {
-                            // TODO: send to stdin
+                        // Already running
+                        ProcessCommand::Start => {}
+                        ProcessCommand::SendInput { command, reply } => {
+                            self.last_console_command = Some(Instant::now());
+                            self.state.add_watcher_log(format!("Console command: {}", command));
+                            let (stdin_timeout, terminator) =
+                                stdin_settings(&self.shared_config.read());
+                            let data = encode_command(&command, self.encoding, terminator);
+                            let result = match stdin.lock().await.as_mut() {
+                                Some(stdin) => {
+                                    write_stdin(stdin, &data, stdin_timeout, &self.state).await
+                                }
+                                None => Err(StdinWriteError::NotRunning),
+                            };
+                            let _ = reply.send(result);
+                        }
+                        ProcessCommand::Checkpoint { flag_file, ack_file, timeout_secs, reply } => {
+                            let dir = self
+                                .config
+                                .server
+                                .working_directory
+                                .as_ref()
+                                .map(PathBuf::from)
+                                .unwrap_or_else(|| std::env::current_dir().unwrap_or_default());
+                            let state = Arc::clone(&self.state);
+                            // Runs on its own so the monitor loop keeps going
+                            tokio::spawn(async move {
+                                state.add_watcher_log(format!("Checkpoint: wrote {}", flag_file));
+                                let result = run_checkpoint(
+                                    &dir,
+                                    &flag_file,
+                                    &ack_file,
+                                    Duration::from_secs(timeout_secs),
+                                )
+                                .await;
+                                match &result {
+                                    Ok(took) => state.add_watcher_log(format!(
+                                        "Checkpoint acknowledged in {} ms",
+                                        took.as_millis()
+                                    )),
+                                    Err(e) => state.add_log(
+                                        LogLevel::Warning,
+                                        LogSource::Watcher,
+                                        format!("Checkpoint failed: {}", e),
+                                    ),
+                                }
+                                let _ = reply.send(result);
+                            });
+                        }
+                        ProcessCommand::Signal(signum) => {
+                            let result = pid
+                                .ok_or_else(|| "process has no PID".to_string())
+                                .and_then(|pid| send_signal(pid, signum));
+                            match result {
+                                Ok(()) => {
+                                    self.state.add_watcher_log(format!("Sent signal {}", signum));
+                                }
+                                Err(e) => {
+                                    self.state.add_log(
+                                        LogLevel::Error,
+                                        LogSource::Watcher,
+                                        format!("Failed to send signal {}: {}", signum, e),
+                                    );
+                                }
+                            }
+                        }
+                        ProcessCommand::CancelRestart => {
+                            if let Some(ref t) = auto_restart_task {
+                                t.abort();
+                                self.state.set_auto_restart_remaining(None);
+                                self.state.add_watcher_log(
+                                    "Scheduled auto-restart cancelled for this run".to_string(),
+                                );
+                            }
+                        }
+                    }
+                }
+                _ = &mut runtime_limit, if max_runtime.is_some() => {
+                    if self.state.in_maintenance() {
+                        self.state.add_watcher_log(
+                            "Maximum runtime reached, skipped during maintenance".to_string(),
+                        );
+                        let next = tokio::time::Instant::now() + max_runtime.unwrap_or_default();
+                        runtime_limit.as_mut().reset(next);
+                        continue;
+                    }
+                    restart_reason = RestartReason::MaxRuntime;
+                    force_restart.store(true, Ordering::SeqCst);
+                    self.state.add_watcher_log("Maximum runtime reached, restarting".to_string());
+                    if let Some(ref tg) = self.telegram {
+                        tg.notify(NotifyType::Restart, "Maximum runtime reached, restarting")
+                            .await;
+                    }
+                    stdout_task.abort();
+                    break ExitReason::Restart(restart_reason);
+                }
+                _ = &mut startup_deadline, if startup_deadline_armed => {
+                    startup_deadline_armed = false;
+                    if startup.is_detected() {
+                        continue;
+                    }
+                    if self.state.in_maintenance() {
+                        self.state.add_watcher_log(
+                            "Startup took too long, restart skipped during maintenance".to_string(),
+                        );
+                        continue;
+                    }
+                    let message = format!(
+                        "Server did not start within {} seconds, restarting",
+                        max_startup.unwrap_or_default().as_secs()
+                    );
+                    self.state.add_log(LogLevel::Error, LogSource::Watcher, message.clone());
+                    if let Some(ref tg) = self.telegram {
+                        tg.notify(NotifyType::Error, &message).await;
+                    }
+                    restart_reason = RestartReason::Error;
+                    force_restart.store(true, Ordering::SeqCst);
+                    stdout_task.abort();
+                    break ExitReason::Restart(restart_reason);
+                }
+                _ = &mut ready_deadline, if ready_deadline_armed => {
+                    ready_deadline_armed = false;
+                    if startup.is_detected() {
+                        continue;
+                    }
+                    let message = format!(
+                        "Server failed to become ready within {}s",
+                        ready_timeout.unwrap_or_default().as_secs()
+                    );
+                    self.state.add_log(LogLevel::Critical, LogSource::Watcher, message.clone());
+                    if let Some(ref tg) = self.telegram {
+                        tg.notify(NotifyType::Critical, &message).await;
+                    }
+                    stdout_task.abort();
+                    break ExitReason::Error;
+                }
+                _ = startup.ready.notified(), if post_start_pending => {
+                    post_start_pending = false;
+                    self.spawn_post_start_hook();
+                }
+                _ = fifo_check.tick(), if !fifo_paths.is_empty() => {
+                    for path in &fifo_paths {
+                        if path.exists() {
+                            removed_fifos.retain(|p| p != path);
+                        } else if !removed_fifos.contains(path) {
+                            self.state.add_log(
+                                LogLevel::Warning,
+                                LogSource::Watcher,
+                                format!(
+                                    "FIFO {} was removed while the server is running",
+                                    path.display()
+                                ),
+                            );
+                            removed_fifos.push(path.clone());
                         }
                     }
                 }
@@ -324,19 +1208,44 @@ impl ProcessManager {
         };
 
         // Cleanup tasks
-        stderr_task.abort();
         if let Some(t) = auto_restart_task {
             t.abort();
         }
 
-        // Determine final exit reason
+        // Determine final exit reason. The output readers stay up through a
+        // graceful stop so the server's last lines are still logged.
         if matches!(exit_reason, ExitReason::Shutdown | ExitReason::Stopped) {
+            if let Some(ref command) = self.config.server.graceful_stop_command {
+                let (stdin_timeout, terminator) = stdin_settings(&self.shared_config.read());
+                let data = encode_command(command, self.encoding, terminator);
+                if let Some(stdin) = stdin.lock().await.as_mut() {
+                    self.state
+                        .add_watcher_log(format!("Graceful stop: sending '{}'", command));
+                    if write_stdin(stdin, &data, stdin_timeout, &self.state)
+                        .await
+                        .is_ok()
+                        && self.wait_for_graceful_exit(child).await
+                    {
+                        // Let the readers reach EOF on what the server printed last
+                        let _ = tokio::time::timeout(OUTPUT_DRAIN_TIMEOUT, async {
+                            let _ = (&mut stdout_task).await;
+                            let _ = (&mut stderr_task).await;
+                        })
+                        .await;
+                    }
+                }
+            }
+            stderr_task.abort();
+            stdout_task.abort();
             return exit_reason;
         }
+        stderr_task.abort();
 
         // Determine exit reason
-        if force_restart.load(Ordering::SeqCst) || auto_restart_triggered.load(Ordering::SeqCst) {
-            ExitReason::Restart
+        if auto_restart_triggered.load(Ordering::SeqCst) {
+            ExitReason::Restart(RestartReason::AutoHourly)
+        } else if force_restart.load(Ordering::SeqCst) {
+            ExitReason::Restart(restart_reason)
         } else if found_error.load(Ordering::SeqCst) {
             ExitReason::Error
         } else if self.config.restart_on.process_exit {
@@ -346,63 +1255,1197 @@ impl ProcessManager {
         }
     }
 
-    async fn handle_restart(&self) {
+    /// Give the server `graceful_stop_timeout_secs` to exit by itself; the
+    /// caller kills it if it is still running afterwards. True if it exited.
+    async fn wait_for_graceful_exit(&self, child: &mut Child) -> bool {
+        let timeout = Duration::from_secs(self.config.server.graceful_stop_timeout_secs);
+        match tokio::time::timeout(timeout, child.wait()).await {
+            Ok(Ok(status)) => {
+                self.state
+                    .add_watcher_log(format!("Server exited gracefully ({})", status));
+                true
+            }
+            Ok(Err(e)) => {
+                self.state.add_log(
+                    LogLevel::Warning,
+                    LogSource::Watcher,
+                    format!("Failed to wait for server exit: {}", e),
+                );
+                false
+            }
+            Err(_) => {
+                self.state.add_log(
+                    LogLevel::Warning,
+                    LogSource::Watcher,
+                    format!(
+                        "Server did not exit within {}s, killing it",
+                        timeout.as_secs()
+                    ),
+                );
+                false
+            }
+        }
+    }
+
+    /// `post_start_command` in the background, so monitoring is not held up
+    fn spawn_post_start_hook(&self) {
+        let Some(command) = self.config.server.post_start_command.clone() else {
+            return;
+        };
+        let state = Arc::clone(&self.state);
+        let working_dir = self.config.server.working_directory.clone();
+        tokio::spawn(async move {
+            let working_dir = working_dir.as_deref();
+            run_hook(&state, "post_start", &command, working_dir, HOOK_TIMEOUT, &[]).await;
+        });
+    }
+
+    fn exited_after_command(&self) -> bool {
+        self.last_console_command
+            .is_some_and(|sent| sent.elapsed() <= COMMAND_EXIT_WINDOW)
+    }
+
+    /// Pick up config changes made since the last start
+    fn reload_config(&mut self) {
+        self.config = self.shared_config.read().clone();
+    }
+
+    fn reset_restart_backoff(&mut self) {
+        self.consecutive_crashes = 0;
+        self.state
+            .set_restart_delay_secs(self.config.server.error_restart_delay_secs);
+    }
+
+    /// Delay before restarting after `attempt` crashes in a row
+    fn crash_restart_delay(&self, attempt: u32) -> u64 {
+        let server = &self.config.server;
+        restart_backoff_delay(
+            server.error_restart_delay_secs,
+            server.restart_backoff_multiplier,
+            attempt,
+            server.restart_max_delay_secs,
+        )
+    }
+
+    /// Returns false if the restart was cancelled during its delay. Only a
+    /// restart that goes ahead is recorded and counts towards the backoff.
+    async fn handle_restart(&mut self, reason: ExitReason, restart_reason: RestartReason) -> bool {
+        self.reload_config();
+        let server = &self.config.server;
+        // Only crashes back off; scheduled restarts use the plain delay
+        let crashed = matches!(
+            restart_reason,
+            RestartReason::Error | RestartReason::ProcessExit
+        );
+        let delay_secs = match restart_reason {
+            RestartReason::Manual | RestartReason::Command => server.manual_restart_delay_secs,
+            _ if crashed => self.crash_restart_delay(self.consecutive_crashes),
+            _ => server.error_restart_delay_secs,
+        };
+
         self.state.set_status(ServerStatus::Restarting);
-        self.state.increment_restart_count();
+        self.state.set_restart_pending(true);
         self.state.add_watcher_log(format!(
-            "Restarting in {} seconds...",
-            self.config.server.restart_delay_seconds
+            "Restarting in {} seconds ({})...",
+            delay_secs,
+            restart_reason.label()
         ));
 
         if let Some(ref tg) = self.telegram {
             tg.notify(
                 NotifyType::Restart,
                 &format!(
-                    "Restarting in {} seconds",
-                    self.config.server.restart_delay_seconds
+                    "Restarting in {} seconds ({})",
+                    delay_secs,
+                    restart_reason.label()
                 ),
             )
             .await;
         }
 
-        // Wait with shutdown check
-        let delay = Duration::from_secs(self.config.server.restart_delay_seconds);
+        if let Some(ref command) = self.config.server.pre_restart_command {
+            let working_dir = self.config.server.working_directory.as_deref();
+            run_hook(
+                &self.state,
+                "pre_restart",
+                command,
+                working_dir,
+                HOOK_TIMEOUT,
+                &[],
+            )
+            .await;
+        }
+
+        // Wait with shutdown and command checks
+        let delay = Duration::from_secs(delay_secs);
         let start = Instant::now();
+        let mut proceed = true;
         while start.elapsed() < delay {
             if *self.shutdown_rx.borrow() {
                 break;
             }
-            sleep(Duration::from_millis(100)).await;
-        }
-    }
-}
+            tokio::select! {
+                _ = sleep(Duration::from_millis(100)) => {}
+                Some(cmd) = self.command_rx.recv() => {
+                    match cmd {
+                        ProcessCommand::CancelRestart | ProcessCommand::Stop => {
+                            self.state.add_watcher_log("Pending restart cancelled".to_string());
+                            proceed = false;
+                            break;
+                        }
+                        // Skip the rest of the delay
+                        ProcessCommand::Restart | ProcessCommand::Start => break,
+                        ProcessCommand::Checkpoint { reply, .. } => {
+                            let _ = reply.send(Err("Server is not running".to_string()));
+                        }
+                        ProcessCommand::SendInput { reply, .. } => {
+                            let _ = reply.send(Err(StdinWriteError::NotRunning));
+                        }
+                        ProcessCommand::Signal(_) => {}
+                    }
+                }
+            }
+        }
+        self.state.set_restart_pending(false);
+
+        if proceed && !*self.shutdown_rx.borrow() {
+            self.state.record_restart(reason.as_str(), restart_reason);
+            if crashed {
+                self.consecutive_crashes = self.consecutive_crashes.saturating_add(1);
+                self.state
+                    .set_restart_delay_secs(self.crash_restart_delay(self.consecutive_crashes));
+            }
+        }
+        proceed
+    }
+}
 
 #[derive(Debug, Clone, Copy)]
 enum ExitReason {
     Shutdown,
-    Restart,
+    Restart(RestartReason),
     Stopped,
     ProcessExit,
     Error,
 }
 
-fn detect_error_level(line: &str, patterns: &ErrorPatterns) -> LogLevel {
-    for pattern in &patterns.critical {
-        if line.contains(pattern) {
-            return LogLevel::Critical;
+impl ExitReason {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ExitReason::Shutdown => "shutdown",
+            ExitReason::Restart(_) => "requested",
+            ExitReason::Stopped => "stopped",
+            ExitReason::ProcessExit => "process_exit",
+            ExitReason::Error => "error",
+        }
+    }
+
+    fn restart_reason(&self) -> RestartReason {
+        match self {
+            ExitReason::Restart(reason) => *reason,
+            ExitReason::ProcessExit => RestartReason::ProcessExit,
+            _ => RestartReason::Error,
+        }
+    }
+}
+
+/// Whether `executable` looks like a Java launcher
+fn is_java_executable(executable: &str) -> bool {
+    std::path::Path::new(executable)
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().to_lowercase().contains("java"))
+}
+
+/// How long `java -version` may take before the probe gives up
+const JAVA_VERSION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Run `<executable> -version` and return the quoted version string,
+/// e.g. `21.0.2` from `openjdk version "21.0.2" 2024-01-16`
+async fn detect_java_version(executable: &str) -> Result<String, String> {
+    let output = Command::new(executable)
+        .arg("-version")
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(JAVA_VERSION_TIMEOUT, output)
+        .await
+        .map_err(|_| format!("timed out after {}s", JAVA_VERSION_TIMEOUT.as_secs()))?
+        .map_err(|e| e.to_string())?;
+
+    // Java prints its version to stderr
+    let text = String::from_utf8_lossy(&output.stderr);
+    let first_line = text
+        .lines()
+        .next()
+        .ok_or_else(|| "no output from -version".to_string())?;
+
+    Ok(first_line
+        .split('"')
+        .nth(1)
+        .unwrap_or(first_line)
+        .trim()
+        .to_string())
+}
+
+/// Detect and record the Java version if the server runs on Java.
+/// Called at startup and whenever the configured executable changes.
+pub async fn refresh_java_version(executable: &str, state: &AppState) {
+    if !is_java_executable(executable) {
+        state.set_java_version(None);
+        return;
+    }
+
+    match detect_java_version(executable).await {
+        Ok(version) => {
+            state.add_watcher_log(format!("Java version: {}", version));
+            state.set_java_version(Some(version));
+        }
+        Err(e) => {
+            state.set_java_version(None);
+            state.add_log(
+                LogLevel::Warning,
+                LogSource::Watcher,
+                format!("Could not run {} -version: {}", executable, e),
+            );
+        }
+    }
+}
+
+/// Re-probe the Java version whenever a config reload changes the
+/// server executable. Runs beside the config watcher so a slow probe
+/// never holds up reloading.
+pub async fn track_java_version(
+    config: Arc<RwLock<Config>>,
+    state: Arc<AppState>,
+    mut shutdown_rx: watch::Receiver<bool>,
+) {
+    let mut events_rx = state.subscribe();
+    let mut executable = config.read().server.executable.clone();
+
+    loop {
+        tokio::select! {
+            event = events_rx.recv() => match event {
+                Ok(StateChangeEvent::ConfigReloaded) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            _ = shutdown_rx.changed() => {
+                if *shutdown_rx.borrow() {
+                    break;
+                }
+                continue;
+            }
+        }
+
+        let current = config.read().server.executable.clone();
+        if current != executable {
+            executable = current;
+            refresh_java_version(&executable, &state).await;
+        }
+    }
+}
+
+/// Watches server output for `startup_pattern`, records how long after
+/// spawn it first appeared and marks the server as running.
+/// Shared by the stdout and stderr readers.
+struct StartupDetector {
+    pattern: Option<String>,
+    spawned_at: Instant,
+    detected: AtomicBool,
+    /// Notified once when the pattern is seen
+    ready: tokio::sync::Notify,
+}
+
+impl StartupDetector {
+    fn new(pattern: Option<String>, spawned_at: Instant) -> Self {
+        Self {
+            pattern,
+            spawned_at,
+            detected: AtomicBool::new(false),
+            ready: tokio::sync::Notify::new(),
+        }
+    }
+
+    fn is_detected(&self) -> bool {
+        self.detected.load(Ordering::SeqCst)
+    }
+
+    fn check(&self, line: &str, state: &AppState) {
+        let Some(ref pattern) = self.pattern else {
+            return;
+        };
+        if self.is_detected() || !line.contains(pattern.as_str()) {
+            return;
+        }
+        if self.detected.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let latency_ms = self.spawned_at.elapsed().as_millis() as u64;
+        state.record_startup_latency(latency_ms);
+        state.set_status(ServerStatus::Running);
+        state.add_watcher_log(format!("Server started up in {} ms", latency_ms));
+        self.ready.notify_one();
+    }
+}
+
+/// Expand `${...}` tokens in every argument. Arguments with unresolved
+/// tokens are passed through unchanged and logged with the token masked.
+fn interpolate_args(arguments: &[String], state: &AppState) -> Vec<String> {
+    let mut system = None;
+    let mut lookup = |name: &str| match name {
+        "TOTAL_MEM_MB" => {
+            let system = system.get_or_insert_with(|| {
+                let mut system = sysinfo::System::new();
+                system.refresh_memory();
+                system
+            });
+            Some((system.total_memory() / 1024 / 1024).to_string())
+        }
+        _ => std::env::var(name).ok(),
+    };
+
+    arguments
+        .iter()
+        .map(|arg| match interpolate_arg(arg, &mut lookup) {
+            Ok(expanded) => expanded,
+            Err(masked) => {
+                state.add_log(
+                    LogLevel::Warning,
+                    LogSource::Watcher,
+                    format!("Unresolved variable in argument: {}", masked),
+                );
+                arg.clone()
+            }
+        })
+        .collect()
+}
+
+/// Expand one argument. `${NAME}` is replaced by the variable's value,
+/// anything else inside `${}` is evaluated as integer arithmetic
+/// (`+ - * /` and parentheses). On failure returns the argument with the
+/// unresolved tokens replaced by `***`.
+fn interpolate_arg(
+    arg: &str,
+    lookup: &mut impl FnMut(&str) -> Option<String>,
+) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut masked = String::new();
+    let mut unresolved = false;
+    let mut rest = arg;
+
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        let token = rest[start + 2..start + len].trim();
+        expanded.push_str(&rest[..start]);
+        masked.push_str(&rest[..start]);
+
+        let value = if is_identifier(token) {
+            lookup(token)
+        } else {
+            ArgExpr::new(token, lookup)
+                .and_then(|mut expr| expr.evaluate())
+                .map(|n| n.to_string())
+        };
+        match value {
+            Some(value) => {
+                expanded.push_str(&value);
+                masked.push_str(&value);
+            }
+            None => {
+                unresolved = true;
+                masked.push_str("***");
+            }
+        }
+        rest = &rest[start + len + 1..];
+    }
+    expanded.push_str(rest);
+    masked.push_str(rest);
+
+    if unresolved {
+        Err(masked)
+    } else {
+        Ok(expanded)
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    s.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ArgToken {
+    Number(i64),
+    Op(char),
+}
+
+/// Recursive descent over `expr := term (+|- term)*`,
+/// `term := atom (*|/ atom)*`, `atom := number | NAME | ( expr )`
+struct ArgExpr {
+    tokens: Vec<ArgToken>,
+    pos: usize,
+}
+
+impl ArgExpr {
+    /// Tokenize, resolving variable names to numbers up front
+    fn new(input: &str, lookup: &mut impl FnMut(&str) -> Option<String>) -> Option<Self> {
+        let mut tokens = Vec::new();
+        let mut chars = input.chars().peekable();
+
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+            } else if c.is_ascii_digit() {
+                let mut number = String::new();
+                while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                    number.push(d);
+                    chars.next();
+                }
+                tokens.push(ArgToken::Number(number.parse().ok()?));
+            } else if c.is_ascii_alphabetic() || c == '_' {
+                let mut name = String::new();
+                while let Some(&d) = chars
+                    .peek()
+                    .filter(|d| d.is_ascii_alphanumeric() || **d == '_')
+                {
+                    name.push(d);
+                    chars.next();
+                }
+                tokens.push(ArgToken::Number(lookup(&name)?.trim().parse().ok()?));
+            } else if "+-*/()".contains(c) {
+                tokens.push(ArgToken::Op(c));
+                chars.next();
+            } else {
+                return None;
+            }
+        }
+
+        Some(Self { tokens, pos: 0 })
+    }
+
+    /// The whole input must be one expression
+    fn evaluate(&mut self) -> Option<i64> {
+        let value = self.expr()?;
+        (self.pos == self.tokens.len()).then_some(value)
+    }
+
+    fn next_op(&mut self, ops: &str) -> Option<char> {
+        match self.tokens.get(self.pos) {
+            Some(ArgToken::Op(op)) if ops.contains(*op) => {
+                self.pos += 1;
+                Some(*op)
+            }
+            _ => None,
+        }
+    }
+
+    fn expr(&mut self) -> Option<i64> {
+        let mut value = self.term()?;
+        while let Some(op) = self.next_op("+-") {
+            let rhs = self.term()?;
+            value = if op == '+' {
+                value.checked_add(rhs)?
+            } else {
+                value.checked_sub(rhs)?
+            };
+        }
+        Some(value)
+    }
+
+    fn term(&mut self) -> Option<i64> {
+        let mut value = self.atom()?;
+        while let Some(op) = self.next_op("*/") {
+            let rhs = self.atom()?;
+            value = if op == '*' {
+                value.checked_mul(rhs)?
+            } else {
+                value.checked_div(rhs)?
+            };
+        }
+        Some(value)
+    }
+
+    fn atom(&mut self) -> Option<i64> {
+        match self.tokens.get(self.pos)?.clone() {
+            ArgToken::Number(n) => {
+                self.pos += 1;
+                Some(n)
+            }
+            ArgToken::Op('(') => {
+                self.pos += 1;
+                let value = self.expr()?;
+                self.next_op(")")?;
+                Some(value)
+            }
+            ArgToken::Op(_) => None,
+        }
+    }
+}
+
+/// Run an operator hook through the shell. Its output goes to the watcher
+/// log and a failing hook is logged as a warning; returns whether it
+/// succeeded, for callers that want to stop on failure.
+pub async fn run_hook(
+    state: &AppState,
+    name: &str,
+    command: &str,
+    working_dir: Option<&str>,
+    timeout: Duration,
+    env: &[(&str, String)],
+) -> bool {
+    state.add_watcher_log(format!("Running {} hook: {}", name, command));
+
+    let mut cmd = shell_command(command);
+    if let Some(dir) = working_dir {
+        cmd.current_dir(dir);
+    }
+    cmd.envs(env.iter().map(|(k, v)| (k, v)));
+    cmd.stdin(Stdio::null()).kill_on_drop(true);
+
+    let output = match tokio::time::timeout(timeout, cmd.output()).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => {
+            state.add_log(
+                LogLevel::Warning,
+                LogSource::Watcher,
+                format!("Failed to run {} hook: {}", name, e),
+            );
+            return false;
+        }
+        Err(_) => {
+            state.add_log(
+                LogLevel::Warning,
+                LogSource::Watcher,
+                format!("{} hook timed out after {}s", name, timeout.as_secs()),
+            );
+            return false;
+        }
+    };
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    for line in stdout.lines().chain(stderr.lines()) {
+        state.add_watcher_log(format!("[{}] {}", name, line));
+    }
+    if !output.status.success() {
+        state.add_log(
+            LogLevel::Warning,
+            LogSource::Watcher,
+            format!("{} hook exited with {}", name, output.status),
+        );
+    }
+    output.status.success()
+}
+
+#[cfg(unix)]
+fn shell_command(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+#[cfg(not(unix))]
+fn shell_command(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+/// Create `path` as a FIFO unless it already is one
+#[cfg(unix)]
+fn ensure_fifo(path: &Path) -> std::io::Result<()> {
+    use nix::sys::stat::Mode;
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.file_type().is_fifo() => Ok(()),
+        Ok(_) => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{} exists and is not a FIFO", path.display()),
+        )),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            nix::unistd::mkfifo(path, Mode::S_IRUSR | Mode::S_IWUSR).map_err(std::io::Error::from)
         }
+        Err(e) => Err(e),
     }
-    for pattern in &patterns.errors {
-        if line.contains(pattern) {
-            return LogLevel::Error;
+}
+
+/// The server reads its stdin from the FIFO; we keep a writer for our own
+/// input. The child end is opened read-write so it never sees EOF when an
+/// outside writer disconnects.
+#[cfg(unix)]
+fn open_stdin_fifo(path: &Path) -> std::io::Result<(Stdio, ServerStdin)> {
+    ensure_fifo(path)?;
+    let child_end = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)?;
+    let writer = tokio::net::unix::pipe::OpenOptions::new().open_sender(path)?;
+    Ok((Stdio::from(child_end), Box::new(writer)))
+}
+
+/// The server writes its output into the FIFO and we read it from there,
+/// along with anything else writing to it. EOF comes once every writer,
+/// the server included, has closed it.
+#[cfg(unix)]
+fn open_stdout_fifo(path: &Path) -> std::io::Result<(Stdio, ServerStdout)> {
+    ensure_fifo(path)?;
+    // Reader first, so opening the write end does not block
+    let reader = tokio::net::unix::pipe::OpenOptions::new().open_receiver(path)?;
+    let child_end = std::fs::OpenOptions::new().write(true).open(path)?;
+    Ok((Stdio::from(child_end), Box::new(reader)))
+}
+
+#[cfg(not(unix))]
+fn open_stdin_fifo(_path: &Path) -> std::io::Result<(Stdio, ServerStdin)> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "FIFOs are only supported on Unix",
+    ))
+}
+
+#[cfg(not(unix))]
+fn open_stdout_fifo(_path: &Path) -> std::io::Result<(Stdio, ServerStdout)> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "FIFOs are only supported on Unix",
+    ))
+}
+
+/// Why a stdin write did not go through
+#[derive(Debug)]
+pub enum StdinWriteError {
+    /// The pipe stayed full for the whole timeout
+    Timeout,
+    Io(std::io::Error),
+    /// No process, or its stdin is not available
+    NotRunning,
+}
+
+impl std::fmt::Display for StdinWriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StdinWriteError::Timeout => write!(f, "stdin write timed out"),
+            StdinWriteError::Io(e) => write!(f, "stdin write failed: {}", e),
+            StdinWriteError::NotRunning => write!(f, "server is not running"),
         }
     }
-    for pattern in &patterns.warnings {
-        if line.contains(pattern) {
-            return LogLevel::Warning;
+}
+
+/// Write and flush `data` to the server's stdin, giving up after `timeout`
+/// so a server that stopped reading cannot block us forever.
+async fn write_stdin<W: AsyncWrite + Unpin>(
+    stdin: &mut W,
+    data: &[u8],
+    timeout: Duration,
+    state: &AppState,
+) -> Result<(), StdinWriteError> {
+    let write = async {
+        stdin.write_all(data).await?;
+        stdin.flush().await
+    };
+
+    match tokio::time::timeout(timeout, write).await {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(e)) => {
+            state.add_log(
+                LogLevel::Error,
+                LogSource::Watcher,
+                format!("Failed to write to server stdin: {}", e),
+            );
+            Err(StdinWriteError::Io(e))
+        }
+        Err(_) => {
+            state.increment_stdin_write_timeouts();
+            state.add_log(
+                LogLevel::Warning,
+                LogSource::Watcher,
+                format!(
+                    "Server stdin did not accept input within {} seconds",
+                    timeout.as_secs()
+                ),
+            );
+            Err(StdinWriteError::Timeout)
         }
     }
-    LogLevel::Info
+}
+
+/// Flag/ack handshake: write the flag file, poll for the ack file, then
+/// remove both. The flag is removed on timeout as well.
+async fn run_checkpoint(
+    dir: &Path,
+    flag_file: &str,
+    ack_file: &str,
+    timeout: Duration,
+) -> Result<Duration, String> {
+    let flag_path = dir.join(flag_file);
+    let ack_path = dir.join(ack_file);
+    let started = Instant::now();
+
+    // A stale ack from an earlier run must not count
+    let _ = tokio::fs::remove_file(&ack_path).await;
+    tokio::fs::write(&flag_path, Local::now().to_rfc3339())
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", flag_path.display(), e))?;
+
+    let mut ticker = interval(Duration::from_millis(200));
+    loop {
+        ticker.tick().await;
+
+        if tokio::fs::try_exists(&ack_path).await.unwrap_or(false) {
+            let _ = tokio::fs::remove_file(&ack_path).await;
+            let _ = tokio::fs::remove_file(&flag_path).await;
+            return Ok(started.elapsed());
+        }
+
+        if started.elapsed() >= timeout {
+            let _ = tokio::fs::remove_file(&flag_path).await;
+            return Err(format!(
+                "No {} after {} seconds",
+                ack_file,
+                timeout.as_secs()
+            ));
+        }
+    }
+}
+
+/// Joins a JVM exception line and the `\tat ...` lines after it into one
+/// message. A line that looks like an exception is held back until the
+/// next line shows whether a trace follows.
+struct StackTraceAssembler {
+    /// None when `assemble_stack_traces` is off
+    exception: Option<regex::Regex>,
+    pending: Option<String>,
+}
+
+impl StackTraceAssembler {
+    fn new(enabled: bool) -> Self {
+        Self {
+            exception: enabled.then(|| regex::Regex::new(EXCEPTION_LINE_PATTERN).unwrap()),
+            pending: None,
+        }
+    }
+
+    /// Messages ready to be logged after `line`, oldest first
+    fn push(&mut self, line: String) -> Vec<String> {
+        let Some(ref exception) = self.exception else {
+            return vec![line];
+        };
+        if is_stack_trace_continuation(&line) {
+            if let Some(ref mut pending) = self.pending {
+                pending.push('\n');
+                pending.push_str(&line);
+                return Vec::new();
+            }
+            return vec![line];
+        }
+        let mut ready: Vec<String> = self.pending.take().into_iter().collect();
+        if exception.is_match(&line) {
+            self.pending = Some(line);
+        } else {
+            ready.push(line);
+        }
+        ready
+    }
+
+    /// The held message, once the stream has ended or gone quiet
+    fn finish(&mut self) -> Vec<String> {
+        self.pending.take().into_iter().collect()
+    }
+
+    fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+}
+
+/// `\tat ...`, `\t... 5 more`, `Caused by: ...` and `\tSuppressed: ...`
+fn is_stack_trace_continuation(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    let indented = trimmed.len() < line.len();
+    line.starts_with("Caused by: ")
+        || (indented
+            && (trimmed.starts_with("at ")
+                || trimmed.starts_with("... ")
+                || trimmed.starts_with("Suppressed: ")
+                || trimmed.starts_with("Caused by: ")))
+}
+
+/// Keeps a fixed fraction of low-severity lines. Deterministic: the rate is
+/// accumulated per line and a line passes whenever the total reaches 1.
+/// Severe lines always pass and do not count towards the rate.
+struct LogSampler {
+    rate: Option<f32>,
+    acc: f32,
+}
+
+impl LogSampler {
+    fn new(rate: Option<f32>) -> Self {
+        Self {
+            rate: rate.map(|r| r.clamp(0.0, 1.0)),
+            acc: 0.0,
+        }
+    }
+
+    /// `severe` is only checked while sampling is on
+    fn keep(&mut self, severe: impl FnOnce() -> bool) -> bool {
+        let Some(rate) = self.rate else {
+            return true;
+        };
+        if severe() {
+            return true;
+        }
+        self.acc += rate;
+        if self.acc >= 1.0 {
+            self.acc -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Encoding for a WHATWG label such as "utf-8" or "cp1251"; UTF-8 if unknown
+pub fn resolve_encoding(name: &str) -> &'static Encoding {
+    Encoding::for_label(name.as_bytes()).unwrap_or_else(|| {
+        tracing::warn!("Unknown encoding '{}', falling back to UTF-8", name);
+        UTF_8
+    })
+}
+
+/// `base * multiplier^attempt`, capped at `max_secs` (but never below `base`)
+fn restart_backoff_delay(base: u64, multiplier: f64, attempt: u32, max_secs: u64) -> u64 {
+    let cap = max_secs.max(base);
+    if multiplier <= 1.0 {
+        return base;
+    }
+    let delay = base as f64 * multiplier.powi(attempt.min(i32::MAX as u32) as i32);
+    if delay.is_finite() {
+        (delay as u64).min(cap)
+    } else {
+        cap
+    }
+}
+
+/// Write timeout and terminator for console commands, read when a command
+/// is sent so config changes apply to a running server
+fn stdin_settings(config: &Config) -> (Duration, StdinTerminator) {
+    (
+        Duration::from_secs(config.server.stdin_write_timeout_secs),
+        config.server.stdin_command_terminator,
+    )
+}
+
+/// Console bytes for a command: the text in the server's encoding plus
+/// the configured terminator (plain ASCII in every supported encoding)
+fn encode_command(
+    command: &str,
+    encoding: &'static Encoding,
+    terminator: StdinTerminator,
+) -> Vec<u8> {
+    let (encoded, _, _) = encoding.encode(command);
+    let mut bytes = encoded.into_owned();
+    bytes.extend_from_slice(terminator.as_bytes());
+    bytes
+}
+
+/// Next output line decoded from the server's encoding, without the line
+/// ending. `None` at EOF or on a read error. Cancel safe: a partly read
+/// line stays in `buf` for the next call.
+async fn read_line_decoded<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    encoding: &'static Encoding,
+) -> Option<String> {
+    match reader.read_until(b'\n', buf).await {
+        Ok(0) | Err(_) => None,
+        Ok(_) => {
+            let line = buf
+                .strip_suffix(b"\n")
+                .map(|l| l.strip_suffix(b"\r").unwrap_or(l))
+                .unwrap_or(buf);
+            let (decoded, _, _) = encoding.decode(line);
+            let decoded = decoded.into_owned();
+            buf.clear();
+            Some(decoded)
+        }
+    }
+}
+
+/// `read_line_decoded`, giving up after `STACK_TRACE_FLUSH_DELAY` while
+/// `traces` holds an exception line so it is not stuck until more output
+async fn read_line_flushing<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    encoding: &'static Encoding,
+    traces: &StackTraceAssembler,
+) -> Result<Option<String>, tokio::time::error::Elapsed> {
+    let read = read_line_decoded(reader, buf, encoding);
+    if traces.is_pending() {
+        tokio::time::timeout(STACK_TRACE_FLUSH_DELAY, read).await
+    } else {
+        Ok(read.await)
+    }
+}
+
+fn strip_ansi(ansi_regex: Option<&regex::Regex>, line: String) -> String {
+    match ansi_regex {
+        Some(re) if line.contains('\x1b') => re.replace_all(&line, "").into_owned(),
+        _ => line,
+    }
+}
+
+fn push_recent(buffer: &Mutex<VecDeque<String>>, line: &str) {
+    let mut buffer = buffer.lock();
+    if buffer.len() >= EXIT_LINES {
+        buffer.pop_front();
+    }
+    buffer.push_back(line.to_string());
+}
+
+fn append_log_file(log_file: Option<&SharedLogFile>, line: &str) {
+    if let Some(log_file) = log_file {
+        if let Err(e) = log_file.write_line(line) {
+            tracing::warn!("Failed to queue server log line: {}", e);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn send_signal(pid: u32, signum: i32) -> Result<(), String> {
+    use nix::sys::signal::{kill, Signal};
+    use nix::unistd::Pid;
+
+    let signal = Signal::try_from(signum).map_err(|e| e.to_string())?;
+    kill(Pid::from_raw(pid as i32), signal).map_err(|e| e.to_string())
+}
+
+#[cfg(not(unix))]
+fn send_signal(_pid: u32, _signum: i32) -> Result<(), String> {
+    Err("signals are not supported on this platform".to_string())
+}
+
+/// File descriptor held open by the server process
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct OpenFile {
+    pub fd: u32,
+    pub path: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+/// List open file descriptors of `pid` via `/proc/<pid>/fd`, at most `limit`.
+/// Returns the entries and whether the list was truncated.
+#[cfg(target_os = "linux")]
+pub fn list_open_files(pid: u32, limit: usize) -> std::io::Result<(Vec<OpenFile>, bool)> {
+    let mut files = Vec::new();
+    let mut truncated = false;
+
+    for entry in std::fs::read_dir(format!("/proc/{}/fd", pid))? {
+        let entry = entry?;
+        let Some(fd) = entry.file_name().to_str().and_then(|n| n.parse().ok()) else {
+            continue;
+        };
+        // The fd may have been closed since read_dir
+        let Ok(target) = std::fs::read_link(entry.path()) else {
+            continue;
+        };
+
+        if files.len() >= limit {
+            truncated = true;
+            break;
+        }
+
+        let path = target.to_string_lossy().to_string();
+        let kind = if path.starts_with("socket:") {
+            "socket"
+        } else if path.starts_with("pipe:") {
+            "pipe"
+        } else if path.starts_with('/') {
+            "file"
+        } else {
+            "unknown"
+        };
+
+        files.push(OpenFile {
+            fd,
+            path,
+            kind: kind.to_string(),
+        });
+    }
+
+    files.sort_by_key(|f| f.fd);
+    Ok((files, truncated))
+}
+
+/// Resolve a signal name like `SIGUSR1` or `usr1` to its number
+#[cfg(unix)]
+pub fn parse_signal_name(name: &str) -> Option<i32> {
+    use std::str::FromStr;
+
+    let upper = name.trim().to_uppercase();
+    let full = if upper.starts_with("SIG") {
+        upper
+    } else {
+        format!("SIG{}", upper)
+    };
+    nix::sys::signal::Signal::from_str(&full)
+        .ok()
+        .map(|s| s as i32)
+}
+
+#[cfg(not(unix))]
+pub fn parse_signal_name(_name: &str) -> Option<i32> {
+    None
+}
+
+async fn run_pre_launch_check(check: &PreLaunchCheck) -> Result<(), String> {
+    match check {
+        PreLaunchCheck::PathExists { path } => {
+            if tokio::fs::metadata(path).await.is_ok() {
+                Ok(())
+            } else {
+                Err(format!("path {} does not exist", path))
+            }
+        }
+        PreLaunchCheck::TcpConnect { address } => {
+            let connect = tokio::net::TcpStream::connect(address.as_str());
+            match tokio::time::timeout(Duration::from_secs(3), connect).await {
+                Ok(Ok(_)) => Ok(()),
+                Ok(Err(e)) => Err(format!("{}: {}", address, e)),
+                Err(_) => Err(format!("{}: connect timed out", address)),
+            }
+        }
+    }
+}
+
+enum PatternMatcher {
+    Contains(String),
+    /// The flag is `extract_fields`
+    Regex(regex::Regex, bool),
+}
+
+impl PatternMatcher {
+    fn new(entry: &PatternEntry) -> Self {
+        if entry.is_regex {
+            match regex::Regex::new(&entry.pattern) {
+                Ok(re) => return PatternMatcher::Regex(re, entry.extract_fields),
+                // validate() rejects these, so only a hand-built config gets here
+                Err(e) => tracing::warn!(
+                    "Invalid error pattern regex {:?}, matching it literally: {}",
+                    entry.pattern,
+                    e
+                ),
+            }
+        }
+        PatternMatcher::Contains(entry.pattern.clone())
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        match self {
+            PatternMatcher::Contains(pattern) => line.contains(pattern.as_str()),
+            PatternMatcher::Regex(re, _) => re.is_match(line),
+        }
+    }
+
+    /// Add the named groups to `fields`, keeping values already there
+    fn extract_into(&self, line: &str, fields: &mut HashMap<String, String>) {
+        let PatternMatcher::Regex(re, true) = self else {
+            return;
+        };
+        let Some(captures) = re.captures(line) else {
+            return;
+        };
+        for name in re.capture_names().flatten() {
+            if let Some(value) = captures.name(name) {
+                fields
+                    .entry(name.to_string())
+                    .or_insert_with(|| value.as_str().to_string());
+            }
+        }
+    }
+}
+
+/// `ErrorPatterns` with the regex entries compiled, per output stream
+pub struct CompiledPatterns {
+    stdout: Vec<(LogLevel, Vec<PatternMatcher>)>,
+    stderr: Vec<(LogLevel, Vec<PatternMatcher>)>,
+    suppress: Vec<PatternMatcher>,
+}
+
+impl CompiledPatterns {
+    pub fn new(patterns: &ErrorPatterns) -> Self {
+        let compile = |entries: &[PatternEntry]| entries.iter().map(PatternMatcher::new).collect();
+        let levels = |source| {
+            patterns
+                .levels_for(source)
+                .into_iter()
+                .map(|(level, entries)| (level, compile(entries)))
+                .collect()
+        };
+        Self {
+            stdout: levels(LogSource::Server),
+            stderr: levels(LogSource::Stderr),
+            suppress: compile(&patterns.suppress),
+        }
+    }
+
+    fn levels(&self, source: LogSource) -> &[(LogLevel, Vec<PatternMatcher>)] {
+        match source {
+            LogSource::Stderr => &self.stderr,
+            LogSource::Server | LogSource::Watcher => &self.stdout,
+        }
+    }
+
+    /// Whether `line` is exempt from restarts
+    pub fn is_suppressed(&self, line: &str) -> bool {
+        self.suppress.iter().any(|m| m.is_match(line))
+    }
+
+    /// Whether a critical or error pattern matches, without checking the
+    /// other levels. Lets sampling skip lines before `detect_level`.
+    pub fn is_severe(&self, line: &str, source: LogSource) -> bool {
+        self.levels(source)
+            .iter()
+            .filter(|(level, _)| matches!(level, LogLevel::Critical | LogLevel::Error))
+            .any(|(_, matchers)| matchers.iter().any(|m| m.is_match(line)))
+    }
+
+    /// Level of the first list with a matching pattern, source-specific
+    /// lists first; `Info` if none match
+    pub fn detect_level(&self, line: &str, source: LogSource) -> LogLevel {
+        self.levels(source)
+            .iter()
+            .find(|(_, matchers)| matchers.iter().any(|m| m.is_match(line)))
+            .map_or(LogLevel::Info, |(level, _)| *level)
+    }
+
+    /// Named groups of every matching `extract_fields` pattern. When two
+    /// patterns capture the same name, the one checked first wins.
+    pub fn extract_fields(&self, line: &str, source: LogSource) -> HashMap<String, String> {
+        let mut fields = HashMap::new();
+        for (_, matchers) in self.levels(source) {
+            for matcher in matchers {
+                matcher.extract_into(line, &mut fields);
+            }
+        }
+        fields
+    }
+}
+
+fn log_suppressed_restart(state: &AppState, line: &str) {
+    state.add_log(
+        LogLevel::Warning,
+        LogSource::Watcher,
+        format!(
+            "Restart suppressed, line matches a suppress pattern: {}",
+            line
+        ),
+    );
 }
 
 fn should_restart(level: LogLevel, config: &RestartConfig) -> bool {
@@ -413,3 +2456,481 @@ fn should_restart(level: LogLevel, config: &RestartConfig) -> bool {
         LogLevel::Info => false,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::watcher::state::MaintenanceInfo;
+
+    fn test_manager() -> (ProcessManager, CommandSender, watch::Sender<bool>) {
+        let state = AppState::new();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let (command_tx, command_rx) = command_channel(16);
+        let manager = ProcessManager::new(
+            Arc::new(RwLock::new(Config::default_config())),
+            state,
+            None,
+            shutdown_rx,
+            command_rx,
+        );
+        (manager, command_tx, shutdown_tx)
+    }
+
+    #[tokio::test]
+    async fn stop_during_maintenance_keeps_manager_alive() {
+        let (mut manager, command_tx, _shutdown_tx) = test_manager();
+        let state = manager.state.clone();
+        state.set_maintenance(Some(MaintenanceInfo {
+            reason: None,
+            started_at: Local::now(),
+            until: None,
+        }));
+
+        let waiting = tokio::spawn(async move { manager.wait_out_maintenance().await });
+        command_tx.send(ProcessCommand::Stop).await.unwrap();
+
+        // Ending maintenance must not start a stopped server
+        state.set_maintenance(None);
+        sleep(Duration::from_millis(1500)).await;
+        assert!(!waiting.is_finished());
+
+        command_tx.send(ProcessCommand::Start).await.unwrap();
+        assert!(waiting.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn start_waits_for_restore() {
+        let (mut manager, command_tx, _shutdown_tx) = test_manager();
+        let state = manager.state.clone();
+        assert!(state.try_start_restore());
+
+        let waiting = tokio::spawn(async move { manager.wait_for_start().await });
+        command_tx.send(ProcessCommand::Start).await.unwrap();
+        sleep(Duration::from_millis(1000)).await;
+        assert!(!waiting.is_finished());
+
+        state.finish_restore();
+        assert!(waiting.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn held_exception_is_flushed_when_output_stops() {
+        let (mut server, output) = tokio::io::duplex(256);
+        let mut reader = BufReader::new(output);
+        let mut buf = Vec::new();
+        let mut traces = StackTraceAssembler::new(true);
+
+        server
+            .write_all(b"java.lang.IllegalStateException: boom\npartial")
+            .await
+            .unwrap();
+        let line = read_line_flushing(&mut reader, &mut buf, UTF_8, &traces)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(traces.push(line).is_empty());
+
+        // Nothing more arrives: the held line comes out after the delay
+        assert!(read_line_flushing(&mut reader, &mut buf, UTF_8, &traces)
+            .await
+            .is_err());
+        assert_eq!(
+            traces.finish(),
+            vec!["java.lang.IllegalStateException: boom".to_string()]
+        );
+
+        // The line cut off by the timeout is completed, not lost
+        server.write_all(b" line\n").await.unwrap();
+        let line = read_line_flushing(&mut reader, &mut buf, UTF_8, &traces)
+            .await
+            .unwrap();
+        assert_eq!(line.as_deref(), Some("partial line"));
+    }
+
+    #[tokio::test]
+    async fn cancelled_restart_is_not_counted() {
+        let (mut manager, command_tx, _shutdown_tx) = test_manager();
+        manager
+            .shared_config
+            .write()
+            .server
+            .error_restart_delay_secs = 30;
+        command_tx
+            .send(ProcessCommand::CancelRestart)
+            .await
+            .unwrap();
+
+        assert!(
+            !manager
+                .handle_restart(ExitReason::Error, RestartReason::Error)
+                .await
+        );
+        assert_eq!(manager.state.restart_count(), 0);
+        assert_eq!(manager.consecutive_crashes, 0);
+
+        command_tx.send(ProcessCommand::Restart).await.unwrap();
+        assert!(
+            manager
+                .handle_restart(ExitReason::Error, RestartReason::Error)
+                .await
+        );
+        assert_eq!(manager.state.restart_count(), 1);
+        assert_eq!(manager.consecutive_crashes, 1);
+    }
+
+    #[tokio::test]
+    async fn only_crashes_back_off() {
+        let (mut manager, command_tx, _shutdown_tx) = test_manager();
+        for reason in [
+            RestartReason::AutoHourly,
+            RestartReason::MaxRuntime,
+            RestartReason::Manual,
+        ] {
+            command_tx.send(ProcessCommand::Restart).await.unwrap();
+            assert!(
+                manager
+                    .handle_restart(ExitReason::Restart(reason), reason)
+                    .await
+            );
+        }
+        assert_eq!(manager.consecutive_crashes, 0);
+
+        for reason in [RestartReason::Error, RestartReason::ProcessExit] {
+            command_tx.send(ProcessCommand::Restart).await.unwrap();
+            assert!(manager.handle_restart(ExitReason::Error, reason).await);
+        }
+        assert_eq!(manager.consecutive_crashes, 2);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn graceful_stop_keeps_logging_until_exit() {
+        let (mut manager, command_tx, _shutdown_tx) = test_manager();
+        {
+            let server = &mut manager.shared_config.write().server;
+            server.executable = "sh".to_string();
+            server.arguments = vec![
+                "-c".to_string(),
+                "read cmd; echo \"got $cmd\"; echo bye >&2; exit 0".to_string(),
+            ];
+            server.graceful_stop_command = Some("stop".to_string());
+            server.graceful_stop_timeout_secs = 5;
+        }
+        manager.reload_config();
+
+        let (mut child, stdin, stdout) = manager.spawn_with_retries().await.unwrap();
+        command_tx.send(ProcessCommand::Stop).await.unwrap();
+        let exit = manager
+            .monitor_process(&mut child, stdin, stdout, Instant::now())
+            .await;
+        assert!(matches!(exit, ExitReason::Stopped));
+
+        let messages: Vec<String> = manager
+            .state
+            .logs(100)
+            .into_iter()
+            .map(|entry| entry.message)
+            .collect();
+        assert!(messages.iter().any(|m| m == "got stop"), "{:?}", messages);
+        assert!(messages.iter().any(|m| m == "bye"), "{:?}", messages);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn server_receives_configured_terminator() {
+        let state = AppState::new();
+        for (terminator, expected) in [
+            (StdinTerminator::Lf, &b"say hi\n"[..]),
+            (StdinTerminator::CrLf, &b"say hi\r\n"[..]),
+            (StdinTerminator::Null, &b"say hi\0"[..]),
+            (StdinTerminator::Custom(0x04), &b"say hi\x04"[..]),
+        ] {
+            // `cat` echoes exactly the bytes it was sent
+            let mut child = Command::new("cat")
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()
+                .unwrap();
+            let mut stdin = child.stdin.take().unwrap();
+            let data = encode_command("say hi", UTF_8, terminator);
+            write_stdin(&mut stdin, &data, Duration::from_secs(5), &state)
+                .await
+                .unwrap();
+            drop(stdin);
+
+            let output = child.wait_with_output().await.unwrap();
+            assert_eq!(output.stdout, expected, "{:?}", terminator);
+        }
+    }
+
+    fn extracting(pattern: &str) -> PatternEntry {
+        PatternEntry {
+            pattern: pattern.to_string(),
+            is_regex: true,
+            extract_fields: true,
+        }
+    }
+
+    #[test]
+    fn first_checked_pattern_wins_field_extraction() {
+        let patterns = CompiledPatterns::new(&ErrorPatterns {
+            critical: vec![extracting(r"^(?P<who>\w+) crashed")],
+            errors: vec![extracting(r"by (?P<who>\w+) in (?P<world>\w+)")],
+            warnings: vec![],
+            suppress: vec![],
+            stdout_critical: None,
+            stdout_errors: None,
+            stdout_warnings: Some(vec![extracting(r"in (?P<world>\w{4})")]),
+            stderr_critical: None,
+            stderr_errors: None,
+            stderr_warnings: Some(vec![extracting(r"by (?P<who>\w+)")]),
+        });
+        let line = "alice crashed by bob in overworld";
+        let field = |fields: &HashMap<String, String>, name: &str| fields[name].clone();
+
+        // Critical is checked before errors, stdout_warnings before both
+        let fields = patterns.extract_fields(line, LogSource::Server);
+        assert_eq!(field(&fields, "who"), "alice");
+        assert_eq!(field(&fields, "world"), "over");
+
+        // Stderr lines skip the stdout lists; stderr_warnings beats the
+        // global critical list
+        let fields = patterns.extract_fields(line, LogSource::Stderr);
+        assert_eq!(field(&fields, "who"), "bob");
+        assert_eq!(field(&fields, "world"), "overworld");
+        assert_eq!(fields.len(), 2);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn open_files_of_a_subprocess() {
+        let dir = std::env::temp_dir().join(format!("watcher-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let held = dir.join("world.dat");
+        std::fs::write(&held, b"data").unwrap();
+
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(format!("exec 7<'{}'; exec sleep 30", held.display()))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        let pid = child.id().unwrap();
+
+        let mut files = Vec::new();
+        for _ in 0..50 {
+            files = list_open_files(pid, 100).unwrap().0;
+            if files.iter().any(|f| f.fd == 7) {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        let file = files.iter().find(|f| f.fd == 7).expect("fd 7 not listed");
+        assert_eq!(file.path, held.to_string_lossy());
+        assert_eq!(file.kind, "file");
+        assert_eq!(files[0].fd, 0);
+        assert_eq!(files[0].kind, "pipe");
+
+        let (limited, truncated) = list_open_files(pid, 1).unwrap();
+        assert_eq!(limited.len(), 1);
+        assert!(truncated);
+
+        child.kill().await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn interpolate(arg: &str) -> Result<String, String> {
+        let vars = HashMap::from([
+            ("MAX_HEAP", "4"),
+            ("TOTAL_MEM_MB", "8192"),
+            ("NAME", "lobby"),
+        ]);
+        interpolate_arg(arg, &mut |name| vars.get(name).map(|v| v.to_string()))
+    }
+
+    #[test]
+    fn arguments_substitute_variables_and_arithmetic() {
+        assert_eq!(interpolate("-Xmx${MAX_HEAP}G").unwrap(), "-Xmx4G");
+        assert_eq!(
+            interpolate("--world=${NAME}-${ NAME }").unwrap(),
+            "--world=lobby-lobby"
+        );
+        assert_eq!(interpolate("-Xmx${TOTAL_MEM_MB/2}M").unwrap(), "-Xmx4096M");
+        assert_eq!(interpolate("${(MAX_HEAP + 2) * 3 - 1}").unwrap(), "17");
+        assert_eq!(interpolate("${7 / 2}").unwrap(), "3");
+        // No complete token: passed through as is
+        assert_eq!(interpolate("plain").unwrap(), "plain");
+        assert_eq!(interpolate("cost ${5").unwrap(), "cost ${5");
+    }
+
+    #[test]
+    fn unresolved_arguments_are_masked() {
+        assert_eq!(interpolate("-Xmx${MISSING}G").unwrap_err(), "-Xmx***G");
+        assert_eq!(interpolate("${MAX_HEAP}:${MISSING}").unwrap_err(), "4:***");
+        assert_eq!(interpolate("${MISSING * 2}").unwrap_err(), "***");
+        // Not a number, division by zero, bad syntax
+        assert_eq!(interpolate("${NAME + 1}").unwrap_err(), "***");
+        assert_eq!(interpolate("${MAX_HEAP / 0}").unwrap_err(), "***");
+        assert_eq!(interpolate("${(1 + 2}").unwrap_err(), "***");
+        assert_eq!(interpolate("${1 $ 2}").unwrap_err(), "***");
+    }
+
+    #[test]
+    fn unresolved_argument_is_passed_through_with_a_warning() {
+        let state = AppState::new();
+        let arguments = vec![
+            "${WATCHER_TEST_UNSET_VARIABLE}".to_string(),
+            "${TOTAL_MEM_MB / 1}".to_string(),
+        ];
+
+        let expanded = interpolate_args(&arguments, &state);
+        assert_eq!(expanded[0], arguments[0]);
+        assert!(expanded[1].parse::<u64>().unwrap() > 0);
+        let log = &state.logs(1)[0];
+        assert_eq!(log.level, LogLevel::Warning);
+        assert_eq!(log.message, "Unresolved variable in argument: ***");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn spawn_retries_do_not_count_as_restarts() {
+        let (manager, _command_tx, shutdown_tx) = test_manager();
+        let dir = std::env::temp_dir().join(format!("watcher-test-{}", uuid::Uuid::new_v4()));
+        // Spawning fails while the working directory is missing
+        {
+            let server = &mut manager.shared_config.write().server;
+            server.working_directory = Some(dir.display().to_string());
+            server.executable = "sleep".to_string();
+            server.arguments = vec!["30".to_string()];
+            server.spawn_retry_count = 3;
+            server.spawn_retry_delay_ms = 300;
+        }
+        let state = manager.state.clone();
+        let retry_logs = |state: &AppState| {
+            state
+                .logs(100)
+                .into_iter()
+                .filter(|entry| entry.message.starts_with("Failed to start"))
+                .count()
+        };
+
+        let running = tokio::spawn(manager.run());
+        for _ in 0..100 {
+            if retry_logs(&state) == 2 {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(retry_logs(&state), 2);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for _ in 0..100 {
+            if state.status() == ServerStatus::Running {
+                break;
+            }
+            sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(state.status(), ServerStatus::Running);
+        assert_eq!(retry_logs(&state), 2);
+        assert_eq!(state.restart_count(), 0);
+
+        shutdown_tx.send(true).unwrap();
+        tokio::time::timeout(Duration::from_secs(10), running)
+            .await
+            .unwrap()
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn reload_with_new_executable_refreshes_java_version() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("watcher-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let java = dir.join("java");
+        std::fs::write(
+            &java,
+            "#!/bin/sh\necho 'openjdk version \"21.0.2\" 2024-01-16' >&2\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&java, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let state = AppState::new();
+        let config = Arc::new(RwLock::new(Config::default_config()));
+        config.write().server.executable = "sh".to_string();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let tracker = tokio::spawn(track_java_version(
+            Arc::clone(&config),
+            Arc::clone(&state),
+            shutdown_rx,
+        ));
+        // Let the tracker subscribe before the event is sent
+        sleep(Duration::from_millis(100)).await;
+
+        config.write().server.executable = java.display().to_string();
+        state.emit(StateChangeEvent::ConfigReloaded);
+        for _ in 0..50 {
+            if state.java_version().is_some() {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(state.java_version().as_deref(), Some("21.0.2"));
+
+        shutdown_tx.send(true).unwrap();
+        tracker.await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn spawn_failure_backs_off_and_stops_on_shutdown() {
+        let (manager, _command_tx, shutdown_tx) = test_manager();
+        let dir = std::env::temp_dir().join(format!("watcher-test-{}", uuid::Uuid::new_v4()));
+        {
+            let server = &mut manager.shared_config.write().server;
+            server.working_directory = Some(dir.display().to_string());
+            server.executable = "sleep".to_string();
+            server.spawn_retry_count = 0;
+            server.error_restart_delay_secs = 600;
+            server.restart_backoff_multiplier = 2.0;
+            server.restart_max_delay_secs = 3600;
+        }
+        let state = manager.state.clone();
+
+        let running = tokio::spawn(manager.run());
+        for _ in 0..100 {
+            if state.snapshot().restart_delay_secs == 1200 {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(state.snapshot().restart_delay_secs, 1200);
+
+        // Shutdown must not wait out the ten minute delay
+        shutdown_tx.send(true).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), running)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[test]
+    fn sampler_keeps_severe_lines_without_scanning_when_off() {
+        let patterns = CompiledPatterns::new(&Config::default_config().error_patterns);
+        let mut off = LogSampler::new(None);
+        assert!(off.keep(|| unreachable!("no pre-check without sampling")));
+
+        let mut sampler = LogSampler::new(Some(0.5));
+        let lines = ["chunk 1", "chunk 2", "Exception in thread main", "chunk 3"];
+        let kept: Vec<&str> = lines
+            .into_iter()
+            .filter(|line| sampler.keep(|| patterns.is_severe(line, LogSource::Server)))
+            .collect();
+        assert_eq!(kept, ["chunk 2", "Exception in thread main"]);
+    }
+}
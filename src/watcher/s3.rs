@@ -0,0 +1,95 @@
+use crate::config::S3Config;
+use crate::watcher::state::{AppState, BackupUploadProgress};
+use object_store::aws::AmazonS3Builder;
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectStore, WriteMultipart};
+use std::path::Path;
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+
+/// Multipart part size; S3 wants at least 5 MiB for all but the last part
+const UPLOAD_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+/// Parts uploaded at the same time
+const UPLOAD_CONCURRENCY: usize = 4;
+
+/// `<prefix>/<filename>`, without doubled slashes
+pub fn object_key(config: &S3Config, filename: &str) -> String {
+    let prefix = config.prefix.trim_matches('/');
+    if prefix.is_empty() {
+        filename.to_string()
+    } else {
+        format!("{}/{}", prefix, filename)
+    }
+}
+
+/// Path-style URL of an uploaded object, for logs and notifications
+pub fn object_url(config: &S3Config, filename: &str) -> String {
+    format!(
+        "{}/{}/{}",
+        config.endpoint.trim_end_matches('/'),
+        config.bucket,
+        object_key(config, filename)
+    )
+}
+
+/// Upload a backup archive as a multipart upload, keeping the progress in
+/// `AppState` up to date. Returns the object URL.
+pub async fn upload_backup(
+    config: &S3Config,
+    archive_path: &Path,
+    state: &AppState,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let filename = archive_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let store = AmazonS3Builder::new()
+        .with_endpoint(&config.endpoint)
+        .with_allow_http(config.endpoint.starts_with("http://"))
+        .with_bucket_name(&config.bucket)
+        .with_region(&config.region)
+        .with_access_key_id(&config.access_key)
+        .with_secret_access_key(&config.secret_key)
+        .build()?;
+
+    let mut file = File::open(archive_path).await?;
+    let mut progress = BackupUploadProgress {
+        filename: filename.clone(),
+        uploaded_bytes: 0,
+        total_bytes: file.metadata().await?.len(),
+    };
+    state.set_backup_upload(Some(progress.clone()));
+
+    let upload = store
+        .put_multipart(&ObjectPath::from(object_key(config, &filename)))
+        .await?;
+    let mut writer = WriteMultipart::new_with_chunk_size(upload, UPLOAD_CHUNK_SIZE);
+    if let Err(e) = write_parts(&mut file, &mut writer, &mut progress, state).await {
+        // Don't leave orphaned parts in the bucket
+        let _ = writer.abort().await;
+        return Err(e);
+    }
+    writer.finish().await?;
+
+    Ok(object_url(config, &filename))
+}
+
+async fn write_parts(
+    file: &mut File,
+    writer: &mut WriteMultipart,
+    progress: &mut BackupUploadProgress,
+    state: &AppState,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut buf = vec![0u8; UPLOAD_CHUNK_SIZE];
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        writer.wait_for_capacity(UPLOAD_CONCURRENCY).await?;
+        writer.write(&buf[..n]);
+        progress.uploaded_bytes += n as u64;
+        state.set_backup_upload(Some(progress.clone()));
+    }
+}
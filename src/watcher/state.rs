@@ -1,9 +1,12 @@
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Local, Utc};
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
+use tokio::sync::{broadcast, mpsc};
+use utoipa::ToSchema;
 
 /// Server status enum
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -31,7 +34,7 @@ impl ServerStatus {
 }
 
 /// Log severity level
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum LogLevel {
     Critical,
@@ -41,7 +44,7 @@ pub enum LogLevel {
 }
 
 /// Log source
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum LogSource {
     Server,
@@ -52,14 +55,23 @@ pub enum LogSource {
 /// Single log entry
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogEntry {
+    /// Increases by one per entry, for cursor paging
+    #[serde(default)]
+    pub id: u64,
     pub timestamp: DateTime<Local>,
     pub level: LogLevel,
     pub source: LogSource,
     pub message: String,
+    /// Named groups captured by `extract_fields` error patterns
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub fields: HashMap<String, String>,
+    /// Identical lines collapsed into this one, see `log_dedup_window_secs`
+    #[serde(default)]
+    pub repeat_count: u32,
 }
 
 /// Resource statistics
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
 pub struct ResourceStats {
     pub cpu_percent: f32,
     pub memory_mb: u64,
@@ -68,6 +80,66 @@ pub struct ResourceStats {
     pub network_tx_speed: u64,
     pub disk_read_speed: u64,
     pub disk_write_speed: u64,
+    /// Proportional set size, only with `detailed_memory_stats`
+    pub memory_pss_mb: Option<u64>,
+    pub memory_private_dirty_mb: Option<u64>,
+    /// JVM internals from Jolokia, only with `jvm_metrics`
+    pub jvm: Option<JvmStats>,
+    /// Established TCP connections on `server_port`, Linux only
+    pub active_connections: Option<u32>,
+    /// Connections seen since the watcher started
+    pub total_connections: u64,
+    /// Disk holding the server's working directory
+    pub disk_free_bytes: u64,
+    pub disk_total_bytes: u64,
+    pub disk_usage_percent: f32,
+    /// 0 when no server is running or the platform can't tell
+    pub thread_count: u32,
+    pub open_fd_count: u32,
+    /// Whole host, collected whether or not the server runs
+    pub system_cpu_percent: f32,
+    pub system_memory_total_mb: u64,
+    pub system_memory_used_mb: u64,
+    pub system_memory_percent: f32,
+    /// 0 on Windows, which has no load average
+    pub system_load_avg_1min: f64,
+    /// Slope of server memory over `memory_leak_window_minutes`
+    pub memory_growth_mb_per_hour: f64,
+}
+
+/// Heap and GC numbers; fields are null when the agent is unreachable
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct JvmStats {
+    pub heap_used_mb: Option<u64>,
+    pub heap_max_mb: Option<u64>,
+    pub gc_collection_count: Option<u64>,
+    pub gc_collection_time_ms: Option<u64>,
+}
+
+/// A resource currently above its configured threshold
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ResourceAlertState {
+    pub resource: String,
+    pub threshold: f64,
+    pub current_value: f64,
+    pub triggered_at: DateTime<Local>,
+    pub consecutive_ticks: u32,
+}
+
+/// Resource statistics with the time they were collected
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsSnapshot {
+    pub timestamp: DateTime<Utc>,
+    pub stats: ResourceStats,
+}
+
+/// S3 upload of the newest backup, while it runs
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BackupUploadProgress {
+    pub filename: String,
+    /// Bytes handed to the uploader so far
+    pub uploaded_bytes: u64,
+    pub total_bytes: u64,
 }
 
 /// Backup info
@@ -76,6 +148,153 @@ pub struct BackupInfo {
     pub filename: String,
     pub size_bytes: u64,
     pub created_at: DateTime<Local>,
+    /// From the metadata sidecar, when present
+    pub files_count: Option<u64>,
+    pub original_size_bytes: Option<u64>,
+    pub watcher_version: Option<String>,
+    /// Labels from `backup_tags.json`; tagged backups are kept by retention
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// SHA-256 of the archive, from its `.sha256` file
+    #[serde(default)]
+    pub checksum: Option<String>,
+}
+
+/// Active maintenance window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceInfo {
+    pub reason: Option<String>,
+    pub started_at: DateTime<Local>,
+    /// `None` means until switched off manually
+    pub until: Option<DateTime<Local>>,
+}
+
+/// What caused a restart; decides which restart delay applies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartReason {
+    /// Requested from the dashboard or API
+    Manual,
+    /// Error pattern, failed spawn or slow startup
+    Error,
+    AutoHourly,
+    /// `max_runtime_secs` reached
+    MaxRuntime,
+    ProcessExit,
+    /// The server exited right after a console command sent through the API
+    Command,
+}
+
+impl RestartReason {
+    pub fn label(&self) -> &'static str {
+        match self {
+            RestartReason::Manual => "manual restart",
+            RestartReason::Error => "error",
+            RestartReason::AutoHourly => "scheduled auto-restart",
+            RestartReason::MaxRuntime => "maximum runtime reached",
+            RestartReason::ProcessExit => "process exited",
+            RestartReason::Command => "console command",
+        }
+    }
+}
+
+/// Restart history entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestartRecord {
+    pub timestamp: DateTime<Local>,
+    pub reason: String,
+    pub restart_reason: RestartReason,
+    /// Value of the restart counter after this restart
+    #[serde(default)]
+    pub restart_number: u32,
+}
+
+/// What the server printed before it last exited
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExitReport {
+    pub exit_reason: String,
+    pub exit_code: Option<i32>,
+    pub last_lines: Vec<String>,
+    pub timestamp: DateTime<Local>,
+}
+
+/// State change notification for downstream consumers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StateChangeEvent {
+    StatusChanged {
+        from: ServerStatus,
+        to: ServerStatus,
+    },
+    ConfigReloaded,
+}
+
+impl StateChangeEvent {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StateChangeEvent::StatusChanged { .. } => "status_changed",
+            StateChangeEvent::ConfigReloaded => "config_reloaded",
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        match self {
+            StateChangeEvent::StatusChanged { from, to } => {
+                format!("Status changed: {} -> {}", from.as_str(), to.as_str())
+            }
+            StateChangeEvent::ConfigReloaded => "Config reloaded".to_string(),
+        }
+    }
+}
+
+/// State change event with the time it happened
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventRecord {
+    pub timestamp: DateTime<Local>,
+    pub event: StateChangeEvent,
+}
+
+/// Single entry of the combined timeline
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TimelineEvent {
+    pub timestamp: DateTime<Local>,
+    pub event_type: String,
+    pub summary: String,
+    #[schema(value_type = Object)]
+    pub detail: serde_json::Value,
+}
+
+const MAX_HISTORY: usize = 500;
+/// Starts averaged into `avg_startup_latency_ms`
+const STARTUP_LATENCY_SAMPLES: usize = 5;
+/// Backups averaged for the schedule estimate
+const BACKUP_DURATION_SAMPLES: usize = 3;
+
+/// Capacities and tunables for `AppState`
+#[derive(Debug, Clone)]
+pub struct AppStateOptions {
+    /// Log ring buffer size
+    pub max_logs: usize,
+    /// State change events kept for the timeline
+    pub event_log_capacity: usize,
+    /// Resource samples kept for graphs
+    pub stats_history_size: usize,
+    /// Collapse identical consecutive lines
+    pub log_dedup_enabled: bool,
+    /// Identical lines closer together than this are collapsed; 0 disables
+    pub log_dedup_window_secs: u64,
+}
+
+impl Default for AppStateOptions {
+    fn default() -> Self {
+        Self {
+            max_logs: 1000,
+            event_log_capacity: MAX_HISTORY,
+            stats_history_size: 3600,
+            log_dedup_enabled: true,
+            log_dedup_window_secs: 5,
+        }
+    }
 }
 
 /// Application state (thread-safe)
@@ -83,6 +302,49 @@ pub struct AppState {
     inner: RwLock<AppStateInner>,
     /// Start time for uptime calculation (not serialized)
     start_time: RwLock<Option<Instant>>,
+    watcher_started_at: Instant,
+    /// Running time of previous server runs, excluding the current one
+    completed_running_ms: AtomicU64,
+    last_exit: RwLock<Option<ExitReport>>,
+    /// From `java -version`, when the server runs on Java
+    java_version: RwLock<Option<String>>,
+    active_alerts: RwLock<Vec<ResourceAlertState>>,
+    /// Last time the log buffer was cleared through the API
+    logs_cleared_at: RwLock<Option<DateTime<Local>>>,
+    /// Bumped when an alert is raised or resolved, not on every tick
+    alerts_generation: AtomicU64,
+    /// Lines skipped by `log_sample_rate`
+    dropped_log_lines: AtomicU64,
+    /// Stdin writes abandoned after `stdin_write_timeout_secs`
+    stdin_write_timeouts: AtomicU64,
+    /// Scheduled backups deferred because of high CPU
+    skipped_backup_count: AtomicU32,
+    /// A scheduled or manual backup is being created
+    backup_in_progress: AtomicBool,
+    /// A restore is swapping the source folder; the server must not start
+    restore_in_progress: AtomicBool,
+    /// The process manager is waiting out the delay before a restart
+    restart_pending: AtomicBool,
+    backup_upload: RwLock<Option<BackupUploadProgress>>,
+    /// Shared with `TelegramClient` so it can mute notifications
+    maintenance_mode: Arc<AtomicBool>,
+    maintenance: RwLock<Option<MaintenanceInfo>>,
+    /// xz level the next backup will use
+    current_backup_compression_level: AtomicU8,
+    /// Stats collection period, follows `resources.check_interval_seconds`
+    stats_interval_secs: AtomicU64,
+    /// Id of the last log entry added
+    last_log_id: AtomicU64,
+    log_dedup_enabled: AtomicBool,
+    log_dedup_window_secs: AtomicU64,
+    /// Bumped by every setter; cheap version number for ETags and polling
+    change_counter: AtomicU64,
+    /// Bumped instead of `change_counter` by the periodic updates, resource
+    /// samples and countdowns, so an idle server keeps a stable version
+    tick_counter: AtomicU64,
+    events_tx: broadcast::Sender<StateChangeEvent>,
+    /// Feeds the on-disk log writer when `log_file` is configured
+    log_sink: RwLock<Option<mpsc::Sender<LogEntry>>>,
 }
 
 struct AppStateInner {
@@ -91,29 +353,89 @@ struct AppStateInner {
     pub restart_count: u32,
     pub logs: VecDeque<LogEntry>,
     pub max_logs: usize,
-    pub stats: ResourceStats,
+    /// When the newest log entry was last repeated; None starts a new run
+    pub last_log_at: Option<Instant>,
+    pub event_log_capacity: usize,
+    pub stats: StatsSnapshot,
+    /// Past samples, oldest first
+    pub stats_history: VecDeque<(DateTime<Local>, ResourceStats)>,
+    pub stats_history_size: usize,
     pub auto_restart_remaining_secs: Option<u64>,
     pub next_backup_secs: Option<u64>,
     pub last_backup_time: Option<DateTime<Local>>,
     pub backups: Vec<BackupInfo>,
+    pub restart_history: VecDeque<RestartRecord>,
+    pub event_history: VecDeque<EventRecord>,
+    /// Spawn to `startup_pattern` match for the current process
+    pub startup_latency_ms: Option<u64>,
+    /// Latencies of the last few starts, oldest first
+    pub startup_latencies: VecDeque<u64>,
+    /// Seconds taken by the last few scheduled backups, oldest first
+    pub backup_durations: VecDeque<u64>,
+    /// Delay the next crash restart will wait, after backoff
+    pub restart_delay_secs: u64,
 }
 
 impl AppState {
+    /// State with default options; `main` sizes its buffers from the config
+    #[cfg_attr(not(test), allow(dead_code))]
     pub fn new() -> Arc<Self> {
+        Self::new_with_options(AppStateOptions::default())
+    }
+
+    pub fn new_with_options(options: AppStateOptions) -> Arc<Self> {
         Arc::new(Self {
             inner: RwLock::new(AppStateInner {
                 status: ServerStatus::Stopped,
                 pid: None,
                 restart_count: 0,
-                logs: VecDeque::with_capacity(1000),
-                max_logs: 1000,
-                stats: ResourceStats::default(),
+                logs: VecDeque::with_capacity(options.max_logs),
+                max_logs: options.max_logs,
+                last_log_at: None,
+                event_log_capacity: options.event_log_capacity,
+                stats: StatsSnapshot {
+                    timestamp: Utc::now(),
+                    stats: ResourceStats::default(),
+                },
+                stats_history: VecDeque::new(),
+                stats_history_size: options.stats_history_size,
                 auto_restart_remaining_secs: None,
                 next_backup_secs: None,
                 last_backup_time: None,
                 backups: vec![],
+                restart_history: VecDeque::new(),
+                startup_latency_ms: None,
+                startup_latencies: VecDeque::with_capacity(STARTUP_LATENCY_SAMPLES),
+                backup_durations: VecDeque::with_capacity(BACKUP_DURATION_SAMPLES),
+                restart_delay_secs: 0,
+                event_history: VecDeque::new(),
             }),
             start_time: RwLock::new(None),
+            watcher_started_at: Instant::now(),
+            completed_running_ms: AtomicU64::new(0),
+            last_exit: RwLock::new(None),
+            java_version: RwLock::new(None),
+            active_alerts: RwLock::new(Vec::new()),
+            logs_cleared_at: RwLock::new(None),
+            alerts_generation: AtomicU64::new(0),
+            dropped_log_lines: AtomicU64::new(0),
+            stdin_write_timeouts: AtomicU64::new(0),
+            skipped_backup_count: AtomicU32::new(0),
+            backup_in_progress: AtomicBool::new(false),
+            restore_in_progress: AtomicBool::new(false),
+            restart_pending: AtomicBool::new(false),
+            backup_upload: RwLock::new(None),
+            change_counter: AtomicU64::new(0),
+            tick_counter: AtomicU64::new(0),
+            current_backup_compression_level: AtomicU8::new(6),
+            stats_interval_secs: AtomicU64::new(1),
+            last_log_id: AtomicU64::new(0),
+            log_dedup_enabled: AtomicBool::new(options.log_dedup_enabled),
+            log_dedup_window_secs: AtomicU64::new(options.log_dedup_window_secs),
+            maintenance_mode: Arc::new(AtomicBool::new(false)),
+            maintenance: RwLock::new(None),
+            events_tx: broadcast::channel(64).0,
+            log_sink: RwLock::new(None),
         })
     }
 
@@ -130,6 +452,35 @@ impl AppState {
         self.inner.read().restart_count
     }
 
+    pub fn change_count(&self) -> u64 {
+        self.change_counter.load(Ordering::Relaxed)
+    }
+
+    pub fn tick_count(&self) -> u64 {
+        self.tick_counter.load(Ordering::Relaxed)
+    }
+
+    pub fn in_maintenance(&self) -> bool {
+        self.maintenance_mode.load(Ordering::Relaxed)
+    }
+
+    pub fn maintenance(&self) -> Option<MaintenanceInfo> {
+        self.maintenance.read().clone()
+    }
+
+    pub fn maintenance_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.maintenance_mode)
+    }
+
+    pub fn backup_compression_level(&self) -> u8 {
+        self.current_backup_compression_level
+            .load(Ordering::Relaxed)
+    }
+
+    pub fn stats_interval_secs(&self) -> u64 {
+        self.stats_interval_secs.load(Ordering::Relaxed)
+    }
+
     pub fn uptime_secs(&self) -> u64 {
         self.start_time
             .read()
@@ -137,10 +488,78 @@ impl AppState {
             .unwrap_or(0)
     }
 
+    pub fn watcher_uptime_secs(&self) -> u64 {
+        self.watcher_started_at.elapsed().as_secs()
+    }
+
+    fn total_running_ms(&self) -> u64 {
+        let current = self
+            .start_time
+            .read()
+            .map(|t| t.elapsed().as_millis() as u64)
+            .unwrap_or(0);
+        self.completed_running_ms.load(Ordering::Relaxed) + current
+    }
+
+    /// Time the server has been running since the watcher started, across restarts
+    pub fn total_running_secs(&self) -> u64 {
+        self.total_running_ms() / 1000
+    }
+
+    /// Time since the watcher started that the server was not running
+    pub fn total_downtime_secs(&self) -> u64 {
+        self.watcher_uptime_secs()
+            .saturating_sub(self.total_running_secs())
+    }
+
+    pub fn availability_percent(&self) -> f64 {
+        let watcher_ms = self.watcher_started_at.elapsed().as_millis() as u64;
+        if watcher_ms == 0 {
+            return 0.0;
+        }
+        (self.total_running_ms().min(watcher_ms) as f64 / watcher_ms as f64) * 100.0
+    }
+
+    pub fn last_exit(&self) -> Option<ExitReport> {
+        self.last_exit.read().clone()
+    }
+
+    pub fn active_alerts(&self) -> Vec<ResourceAlertState> {
+        self.active_alerts.read().clone()
+    }
+
+    pub fn alerts_generation(&self) -> u64 {
+        self.alerts_generation.load(Ordering::Relaxed)
+    }
+
+    pub fn java_version(&self) -> Option<String> {
+        self.java_version.read().clone()
+    }
+
     pub fn stats(&self) -> ResourceStats {
+        self.stats_snapshot().stats
+    }
+
+    pub fn stats_snapshot(&self) -> StatsSnapshot {
         self.inner.read().stats.clone()
     }
 
+    /// Samples from the last `minutes`, oldest first
+    pub fn stats_history(&self, minutes: u64) -> Vec<(DateTime<Local>, ResourceStats)> {
+        // A window longer than chrono can represent covers the whole history
+        let since = i64::try_from(minutes)
+            .ok()
+            .and_then(chrono::Duration::try_minutes)
+            .and_then(|window| Local::now().checked_sub_signed(window));
+        self.inner
+            .read()
+            .stats_history
+            .iter()
+            .filter(|(timestamp, _)| since.is_none_or(|since| *timestamp >= since))
+            .cloned()
+            .collect()
+    }
+
     pub fn auto_restart_remaining(&self) -> Option<u64> {
         self.inner.read().auto_restart_remaining_secs
     }
@@ -149,6 +568,11 @@ impl AppState {
         self.inner.read().next_backup_secs
     }
 
+    /// Durations of the last few backups in seconds, oldest first
+    pub fn backup_durations(&self) -> Vec<u64> {
+        self.inner.read().backup_durations.iter().copied().collect()
+    }
+
     pub fn last_backup_time(&self) -> Option<DateTime<Local>> {
         self.inner.read().last_backup_time
     }
@@ -162,61 +586,535 @@ impl AppState {
         inner.logs.iter().rev().take(limit).cloned().collect()
     }
 
+    /// Up to `limit` entries with an id above `after_id`, oldest first,
+    /// and whether more follow
+    pub fn logs_after(&self, after_id: u64, limit: usize) -> (Vec<LogEntry>, bool) {
+        let inner = self.inner.read();
+        let start = inner.logs.partition_point(|e| e.id <= after_id);
+        let page: Vec<LogEntry> = inner.logs.range(start..).take(limit).cloned().collect();
+        let has_more = inner.logs.len() - start > page.len();
+        (page, has_more)
+    }
+
+    /// Up to `limit` entries with an id below `before_id`, newest first,
+    /// and whether older ones remain
+    pub fn logs_before(&self, before_id: u64, limit: usize) -> (Vec<LogEntry>, bool) {
+        let inner = self.inner.read();
+        let end = inner.logs.partition_point(|e| e.id < before_id);
+        let page: Vec<LogEntry> = inner.logs.range(..end).rev().take(limit).cloned().collect();
+        let has_more = end > page.len();
+        (page, has_more)
+    }
+
+    pub fn last_log_id(&self) -> u64 {
+        self.last_log_id.load(Ordering::Relaxed)
+    }
+
+    fn next_log_id(&self) -> u64 {
+        self.last_log_id.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Most recent restarts, newest first
+    pub fn restart_history(&self, limit: usize) -> Vec<RestartRecord> {
+        let inner = self.inner.read();
+        inner
+            .restart_history
+            .iter()
+            .rev()
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Restarts, backups and state changes merged newest first.
+    /// `from` is inclusive, `to` is exclusive.
+    pub fn timeline(
+        &self,
+        limit: usize,
+        from: Option<DateTime<Local>>,
+        to: Option<DateTime<Local>>,
+    ) -> Vec<TimelineEvent> {
+        let inner = self.inner.read();
+        let mut events: Vec<TimelineEvent> = Vec::new();
+
+        for r in &inner.restart_history {
+            events.push(TimelineEvent {
+                timestamp: r.timestamp,
+                event_type: "restart".to_string(),
+                summary: format!("Server restarted ({})", r.reason),
+                detail: serde_json::to_value(r).unwrap_or_default(),
+            });
+        }
+
+        for b in &inner.backups {
+            events.push(TimelineEvent {
+                timestamp: b.created_at,
+                event_type: "backup".to_string(),
+                summary: format!("Backup created: {}", b.filename),
+                detail: serde_json::to_value(b).unwrap_or_default(),
+            });
+        }
+
+        for e in &inner.event_history {
+            events.push(TimelineEvent {
+                timestamp: e.timestamp,
+                event_type: e.event.kind().to_string(),
+                summary: e.event.summary(),
+                detail: serde_json::to_value(&e.event).unwrap_or_default(),
+            });
+        }
+
+        events.retain(|e| {
+            from.is_none_or(|f| e.timestamp >= f) && to.is_none_or(|t| e.timestamp < t)
+        });
+        events.sort_by_key(|e| std::cmp::Reverse(e.timestamp));
+        events.truncate(limit);
+        events
+    }
+
     // Setters
+    fn touch(&self) {
+        self.change_counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn tick(&self) {
+        self.tick_counter.fetch_add(1, Ordering::Relaxed);
+    }
+
     pub fn set_status(&self, status: ServerStatus) {
-        self.inner.write().status = status;
+        let previous = std::mem::replace(&mut self.inner.write().status, status);
+        self.touch();
+        if previous != status {
+            self.emit(StateChangeEvent::StatusChanged {
+                from: previous,
+                to: status,
+            });
+        }
     }
 
     pub fn set_pid(&self, pid: Option<u32>) {
         self.inner.write().pid = pid;
+        self.touch();
+    }
+
+    /// Called once the startup pattern shows up in the server output
+    pub fn record_startup_latency(&self, latency_ms: u64) {
+        {
+            let mut inner = self.inner.write();
+            inner.startup_latency_ms = Some(latency_ms);
+            inner.startup_latencies.push_back(latency_ms);
+            while inner.startup_latencies.len() > STARTUP_LATENCY_SAMPLES {
+                inner.startup_latencies.pop_front();
+            }
+        }
+        self.touch();
+    }
+
+    /// A new process has not reported startup yet
+    pub fn clear_startup_latency(&self) {
+        self.inner.write().startup_latency_ms = None;
+        self.touch();
+    }
+
+    pub fn set_restart_delay_secs(&self, secs: u64) {
+        self.inner.write().restart_delay_secs = secs;
+        self.touch();
+    }
+
+    pub fn is_restart_pending(&self) -> bool {
+        self.restart_pending.load(Ordering::SeqCst)
+    }
+
+    pub fn set_restart_pending(&self, pending: bool) {
+        self.restart_pending.store(pending, Ordering::SeqCst);
+        self.touch();
     }
 
     pub fn set_start_time(&self, time: Option<Instant>) {
-        *self.start_time.write() = time;
+        let previous = std::mem::replace(&mut *self.start_time.write(), time);
+        if let Some(started) = previous {
+            self.completed_running_ms
+                .fetch_add(started.elapsed().as_millis() as u64, Ordering::Relaxed);
+        }
+        self.touch();
+    }
+
+    pub fn set_last_exit(&self, report: ExitReport) {
+        *self.last_exit.write() = Some(report);
+        self.touch();
+    }
+
+    /// Record this tick's value for `resource`, raising or resolving its alert
+    pub fn update_resource_alert(&self, resource: &str, threshold: f64, current_value: f64) {
+        let mut alerts = self.active_alerts.write();
+        let existing = alerts.iter().position(|a| a.resource == resource);
+
+        match (existing, current_value > threshold) {
+            (Some(i), true) => {
+                alerts[i].threshold = threshold;
+                alerts[i].current_value = current_value;
+                alerts[i].consecutive_ticks += 1;
+                return;
+            }
+            (Some(i), false) => {
+                alerts.remove(i);
+                drop(alerts);
+                self.add_watcher_log(format!(
+                    "{} back below threshold ({:.1} <= {:.1})",
+                    resource, current_value, threshold
+                ));
+            }
+            (None, true) => {
+                alerts.push(ResourceAlertState {
+                    resource: resource.to_string(),
+                    threshold,
+                    current_value,
+                    triggered_at: Local::now(),
+                    consecutive_ticks: 1,
+                });
+                drop(alerts);
+                self.add_log(
+                    LogLevel::Warning,
+                    LogSource::Watcher,
+                    format!(
+                        "{} above threshold ({:.1} > {:.1})",
+                        resource, current_value, threshold
+                    ),
+                );
+            }
+            (None, false) => return,
+        }
+
+        self.alerts_generation.fetch_add(1, Ordering::Relaxed);
+        self.touch();
+    }
+
+    pub fn set_java_version(&self, version: Option<String>) {
+        *self.java_version.write() = version;
+        self.touch();
     }
 
-    pub fn increment_restart_count(&self) {
-        self.inner.write().restart_count += 1;
+    pub fn reset_restart_count(&self) {
+        self.inner.write().restart_count = 0;
+        self.touch();
     }
 
-    pub fn set_stats(&self, stats: ResourceStats) {
-        self.inner.write().stats = stats;
+    /// Count a restart and add it to the history
+    pub fn record_restart(&self, reason: &str, restart_reason: RestartReason) {
+        let mut inner = self.inner.write();
+        inner.restart_count += 1;
+        let restart_number = inner.restart_count;
+        inner.restart_history.push_back(RestartRecord {
+            timestamp: Local::now(),
+            reason: reason.to_string(),
+            restart_reason,
+            restart_number,
+        });
+        while inner.restart_history.len() > MAX_HISTORY {
+            inner.restart_history.pop_front();
+        }
+        self.touch();
+    }
+
+    pub fn set_stats_snapshot(&self, snapshot: StatsSnapshot) {
+        {
+            let mut inner = self.inner.write();
+            let timestamp = snapshot.timestamp.with_timezone(&Local);
+            inner
+                .stats_history
+                .push_back((timestamp, snapshot.stats.clone()));
+            while inner.stats_history.len() > inner.stats_history_size {
+                inner.stats_history.pop_front();
+            }
+            inner.stats = snapshot;
+        }
+        self.tick();
     }
 
     pub fn set_auto_restart_remaining(&self, secs: Option<u64>) {
         self.inner.write().auto_restart_remaining_secs = secs;
+        self.tick();
     }
 
     pub fn set_next_backup_secs(&self, secs: Option<u64>) {
         self.inner.write().next_backup_secs = secs;
+        self.tick();
+    }
+
+    pub fn record_backup_duration(&self, secs: u64) {
+        {
+            let mut inner = self.inner.write();
+            inner.backup_durations.push_back(secs);
+            while inner.backup_durations.len() > BACKUP_DURATION_SAMPLES {
+                inner.backup_durations.pop_front();
+            }
+        }
+        self.touch();
     }
 
     pub fn set_last_backup_time(&self, time: Option<DateTime<Local>>) {
         self.inner.write().last_backup_time = time;
+        self.touch();
+    }
+
+    pub fn set_maintenance(&self, maintenance: Option<MaintenanceInfo>) {
+        let mut current = self.maintenance.write();
+        self.maintenance_mode
+            .store(maintenance.is_some(), Ordering::Relaxed);
+        *current = maintenance;
+        self.touch();
+    }
+
+    /// End maintenance, but only the window that started at `started_at`,
+    /// so an expired countdown cannot end a newer window
+    pub fn end_maintenance_started_at(&self, started_at: DateTime<Local>) -> bool {
+        let mut current = self.maintenance.write();
+        if current.as_ref().is_some_and(|m| m.started_at == started_at) {
+            *current = None;
+            self.maintenance_mode.store(false, Ordering::Relaxed);
+            self.touch();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn set_backup_compression_level(&self, level: u8) {
+        self.current_backup_compression_level
+            .store(level, Ordering::Relaxed);
+        self.touch();
+    }
+
+    pub fn set_stats_interval_secs(&self, secs: u64) {
+        self.stats_interval_secs.store(secs, Ordering::Relaxed);
+        self.touch();
     }
 
     pub fn set_backups(&self, backups: Vec<BackupInfo>) {
         self.inner.write().backups = backups;
+        self.touch();
     }
 
     pub fn add_log(&self, level: LogLevel, source: LogSource, message: String) {
+        self.add_log_with_fields(level, source, message, HashMap::new());
+    }
+
+    pub fn add_log_with_fields(
+        &self,
+        level: LogLevel,
+        source: LogSource,
+        message: String,
+        fields: HashMap<String, String>,
+    ) {
+        // Ids are taken under the lock so the buffer stays sorted by id
         let mut inner = self.inner.write();
-        inner.logs.push_back(LogEntry {
+        let now = Instant::now();
+        let enabled = self.log_dedup_enabled.load(Ordering::Relaxed);
+        let window = self.log_dedup_window_secs.load(Ordering::Relaxed);
+        let within_window = inner
+            .last_log_at
+            .is_some_and(|at| enabled && window > 0 && now.duration_since(at).as_secs() < window);
+        let repeated = within_window
+            && inner.logs.back().is_some_and(|last| {
+                last.level == level && last.source == source && last.message == message
+            });
+        if repeated {
+            if let Some(last) = inner.logs.back_mut() {
+                last.repeat_count = last.repeat_count.saturating_add(1);
+            }
+            inner.last_log_at = Some(now);
+            drop(inner);
+            // The entry keeps its id, so pollers only see the new count
+            // through the change counter
+            self.touch();
+            return;
+        }
+
+        let mut entries = Vec::with_capacity(2);
+        if let Some(last) = inner.logs.back().filter(|last| last.repeat_count > 1) {
+            entries.push(LogEntry {
+                id: 0,
+                timestamp: Local::now(),
+                level: last.level,
+                source: LogSource::Watcher,
+                message: format!("Last message repeated {} times", last.repeat_count),
+                fields: HashMap::new(),
+                repeat_count: 0,
+            });
+        }
+        entries.push(LogEntry {
+            id: 0,
             timestamp: Local::now(),
             level,
             source,
             message,
+            fields,
+            repeat_count: 0,
         });
+        let sink = self.log_sink.read();
+        for mut entry in entries {
+            entry.id = self.next_log_id();
+            if let Some(ref sink) = *sink {
+                // Never block logging on the disk; a full queue drops the line
+                let _ = sink.try_send(entry.clone());
+            }
+            inner.logs.push_back(entry);
+        }
+        inner.last_log_at = Some(now);
 
         while inner.logs.len() > inner.max_logs {
             inner.logs.pop_front();
         }
+        drop(sink);
+        drop(inner);
+        self.touch();
+    }
+
+    pub fn set_log_dedup_enabled(&self, enabled: bool) {
+        self.log_dedup_enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn set_log_dedup_window_secs(&self, secs: u64) {
+        self.log_dedup_window_secs.store(secs, Ordering::Relaxed);
+    }
+
+    /// Start a new dedup run so the next line is always logged, even if
+    /// it matches the last one. Called when the server (re)starts.
+    pub fn reset_log_dedup(&self) {
+        self.inner.write().last_log_at = None;
+    }
+
+    pub fn set_log_sink(&self, sink: mpsc::Sender<LogEntry>) {
+        *self.log_sink.write() = Some(sink);
+    }
+
+    /// Change the log buffer size, dropping the oldest entries that no
+    /// longer fit. The buffer always keeps at least one entry.
+    pub fn set_max_logs(&self, max_logs: usize) {
+        let max_logs = max_logs.max(1);
+        {
+            let mut inner = self.inner.write();
+            inner.max_logs = max_logs;
+            while inner.logs.len() > max_logs {
+                inner.logs.pop_front();
+            }
+        }
+        self.touch();
     }
 
     pub fn add_watcher_log(&self, message: String) {
         self.add_log(LogLevel::Info, LogSource::Watcher, message);
     }
 
+    /// Empty the log buffer. The clear is logged right away, so the
+    /// buffer is never left completely empty.
+    pub fn clear_logs(&self) -> DateTime<Local> {
+        let cleared_at = Local::now();
+        {
+            let mut inner = self.inner.write();
+            inner.logs.clear();
+            inner.logs.push_back(LogEntry {
+                id: self.next_log_id(),
+                timestamp: cleared_at,
+                level: LogLevel::Info,
+                source: LogSource::Watcher,
+                message: "Log buffer cleared".to_string(),
+                fields: HashMap::new(),
+                repeat_count: 0,
+            });
+            inner.last_log_at = None;
+        }
+        *self.logs_cleared_at.write() = Some(cleared_at);
+        self.touch();
+        cleared_at
+    }
+
+    pub fn logs_cleared_at(&self) -> Option<DateTime<Local>> {
+        *self.logs_cleared_at.read()
+    }
+
+    pub fn dropped_log_lines(&self) -> u64 {
+        self.dropped_log_lines.load(Ordering::Relaxed)
+    }
+
+    pub fn increment_dropped_log_lines(&self) {
+        self.dropped_log_lines.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn stdin_write_timeouts(&self) -> u64 {
+        self.stdin_write_timeouts.load(Ordering::Relaxed)
+    }
+
+    pub fn increment_stdin_write_timeouts(&self) {
+        self.stdin_write_timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn skipped_backup_count(&self) -> u32 {
+        self.skipped_backup_count.load(Ordering::Relaxed)
+    }
+
+    pub fn increment_skipped_backups(&self) {
+        self.skipped_backup_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn backup_in_progress(&self) -> bool {
+        self.backup_in_progress.load(Ordering::SeqCst)
+    }
+
+    /// Claim the backup slot; false if another backup holds it
+    pub fn try_start_backup(&self) -> bool {
+        self.backup_in_progress
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
+
+    pub fn finish_backup(&self) {
+        self.backup_in_progress.store(false, Ordering::SeqCst);
+    }
+
+    pub fn restore_in_progress(&self) -> bool {
+        self.restore_in_progress.load(Ordering::SeqCst)
+    }
+
+    /// Claim the restore slot; false if another restore holds it
+    pub fn try_start_restore(&self) -> bool {
+        self.restore_in_progress
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
+
+    pub fn finish_restore(&self) {
+        self.restore_in_progress.store(false, Ordering::SeqCst);
+    }
+
+    pub fn backup_upload(&self) -> Option<BackupUploadProgress> {
+        self.backup_upload.read().clone()
+    }
+
+    pub fn set_backup_upload(&self, progress: Option<BackupUploadProgress>) {
+        *self.backup_upload.write() = progress;
+    }
+
+    // Events
+    pub fn subscribe(&self) -> broadcast::Receiver<StateChangeEvent> {
+        self.events_tx.subscribe()
+    }
+
+    pub fn emit(&self, event: StateChangeEvent) {
+        {
+            let mut inner = self.inner.write();
+            inner.event_history.push_back(EventRecord {
+                timestamp: Local::now(),
+                event: event.clone(),
+            });
+            while inner.event_history.len() > inner.event_log_capacity {
+                inner.event_history.pop_front();
+            }
+        }
+        // No receivers is fine
+        let _ = self.events_tx.send(event);
+    }
+
     /// Get full snapshot for API
     pub fn snapshot(&self) -> StateSnapshot {
         let inner = self.inner.read();
@@ -225,10 +1123,18 @@ impl AppState {
             pid: inner.pid,
             uptime_secs: self.uptime_secs(),
             restart_count: inner.restart_count,
-            stats: inner.stats.clone(),
+            stats: inner.stats.stats.clone(),
             auto_restart_remaining_secs: inner.auto_restart_remaining_secs,
             next_backup_secs: inner.next_backup_secs,
             last_backup_time: inner.last_backup_time,
+            startup_latency_ms: inner.startup_latency_ms,
+            avg_startup_latency_ms: (!inner.startup_latencies.is_empty()).then(|| {
+                inner.startup_latencies.iter().sum::<u64>() / inner.startup_latencies.len() as u64
+            }),
+            restart_delay_secs: inner.restart_delay_secs,
+            max_logs: inner.max_logs,
+            current_log_count: inner.logs.len(),
+            pending_restart: self.is_restart_pending(),
         }
     }
 }
@@ -244,4 +1150,122 @@ pub struct StateSnapshot {
     pub auto_restart_remaining_secs: Option<u64>,
     pub next_backup_secs: Option<u64>,
     pub last_backup_time: Option<DateTime<Local>>,
+    pub startup_latency_ms: Option<u64>,
+    pub avg_startup_latency_ms: Option<u64>,
+    /// Current crash restart delay including backoff
+    #[serde(default)]
+    pub restart_delay_secs: u64,
+    #[serde(default)]
+    pub max_logs: usize,
+    #[serde(default)]
+    pub current_log_count: usize,
+    #[serde(default)]
+    pub pending_restart: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_history_accepts_huge_window() {
+        let state = AppState::new();
+        state.set_stats_snapshot(StatsSnapshot {
+            timestamp: Utc::now(),
+            stats: ResourceStats::default(),
+        });
+        assert_eq!(state.stats_history(u64::MAX).len(), 1);
+        assert_eq!(state.stats_history(i64::MAX as u64).len(), 1);
+    }
+
+    #[test]
+    fn max_logs_never_drops_to_zero() {
+        let state = AppState::new();
+        state.add_watcher_log("first".to_string());
+        state.add_watcher_log("second".to_string());
+        state.set_max_logs(0);
+        let snapshot = state.snapshot();
+        assert_eq!(snapshot.max_logs, 1);
+        assert_eq!(snapshot.current_log_count, 1);
+    }
+
+    #[test]
+    fn custom_max_logs_caps_the_ring_buffer() {
+        let state = AppState::new_with_options(AppStateOptions {
+            max_logs: 3,
+            ..AppStateOptions::default()
+        });
+        for i in 0..5 {
+            state.add_watcher_log(format!("line {}", i));
+        }
+
+        let snapshot = state.snapshot();
+        assert_eq!(snapshot.max_logs, 3);
+        assert_eq!(snapshot.current_log_count, 3);
+        let messages: Vec<String> = state.logs(10).into_iter().map(|e| e.message).collect();
+        assert_eq!(messages, ["line 4", "line 3", "line 2"]);
+    }
+
+    #[test]
+    fn repeats_are_counted_on_the_entry() {
+        let state = AppState::new();
+        state.add_watcher_log("tick".to_string());
+        let first_id = state.last_log_id();
+        let changes = state.change_count();
+        state.add_watcher_log("tick".to_string());
+        state.add_watcher_log("tick".to_string());
+
+        // Collapsed into the first entry, which pollers see change
+        assert_eq!(state.last_log_id(), first_id);
+        assert!(state.change_count() > changes);
+        assert_eq!(state.logs(1)[0].repeat_count, 2);
+
+        state.add_watcher_log("tock".to_string());
+        let (entries, _) = state.logs_after(first_id, 10);
+        let messages: Vec<&str> = entries.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, ["Last message repeated 2 times", "tock"]);
+    }
+
+    #[test]
+    fn single_repeat_has_no_summary() {
+        let state = AppState::new();
+        state.add_watcher_log("tick".to_string());
+        state.add_watcher_log("tick".to_string());
+        let first_id = state.last_log_id();
+        state.add_watcher_log("tock".to_string());
+
+        let (entries, _) = state.logs_after(first_id, 10);
+        let messages: Vec<&str> = entries.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, ["tock"]);
+    }
+
+    #[test]
+    fn disabled_dedup_keeps_every_line() {
+        let state = AppState::new_with_options(AppStateOptions {
+            max_logs: 2,
+            log_dedup_enabled: false,
+            ..AppStateOptions::default()
+        });
+        for _ in 0..3 {
+            state.add_watcher_log("tick".to_string());
+        }
+
+        assert_eq!(state.snapshot().current_log_count, 2);
+        assert_eq!(state.last_log_id(), 3);
+    }
+
+    #[test]
+    fn periodic_updates_leave_the_change_count_alone() {
+        let state = AppState::new();
+        let changes = state.change_count();
+        state.set_stats_snapshot(StatsSnapshot {
+            timestamp: Utc::now(),
+            stats: ResourceStats::default(),
+        });
+        state.set_auto_restart_remaining(Some(60));
+        state.set_next_backup_secs(Some(60));
+
+        assert_eq!(state.change_count(), changes);
+        assert_eq!(state.tick_count(), 3);
+    }
 }
@@ -1,28 +1,178 @@
-use crate::watcher::state::{AppState, ResourceStats};
+use crate::config::{CpuThresholdAction, JvmMetricsConfig, ResourceConfig};
+use crate::watcher::backup::format_bytes;
+use crate::watcher::process::{CommandSender, ProcessCommand};
+use crate::watcher::state::{
+    AppState, JvmStats, LogLevel, LogSource, ResourceStats, StatsSnapshot,
+};
+use crate::watcher::telegram::{NotifyType, TelegramClient};
+use chrono::Utc;
+use parking_lot::Mutex;
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use sysinfo::{Networks, Pid, System};
+use sysinfo::{Disk, Disks, Networks, Pid, System};
 use tokio::sync::watch;
-use tokio::time::{interval, Duration};
+use tokio::time::{interval, interval_at, Duration, Instant};
+
+/// How often smaps_rollup is read when detailed memory stats are on
+const DETAILED_MEMORY_INTERVAL: Duration = Duration::from_secs(30);
+/// Minimum gap between two connection count alerts
+const CONNECTION_ALERT_INTERVAL: Duration = Duration::from_secs(600);
+/// Minimum gap between two thread or file descriptor alerts
+const LEAK_ALERT_INTERVAL: Duration = Duration::from_secs(600);
+/// Minimum gap between two disk space alerts
+const DISK_SPACE_ALERT_INTERVAL: Duration = Duration::from_secs(3600);
+/// Minimum gap between two memory leak alerts
+const MEMORY_LEAK_ALERT_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Server memory samples for the current process, oldest first
+#[derive(Default)]
+struct MemoryTrend {
+    pid: Option<u32>,
+    /// First sample for this pid
+    since: Option<Instant>,
+    samples: VecDeque<(Instant, f64)>,
+}
+
+impl MemoryTrend {
+    /// Record a sample and drop those older than `window`. A new pid
+    /// starts over, since a restart frees everything.
+    fn push(&mut self, pid: Option<u32>, memory_mb: u64, window: Duration) {
+        let now = Instant::now();
+        if pid != self.pid {
+            self.pid = pid;
+            self.since = pid.map(|_| now);
+            self.samples.clear();
+        }
+        if pid.is_none() {
+            return;
+        }
+        self.samples.push_back((now, memory_mb as f64));
+        while self
+            .samples
+            .front()
+            .is_some_and(|(t, _)| now.duration_since(*t) > window)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Whether the process has been sampled for at least `window`
+    fn covers(&self, window: Duration) -> bool {
+        self.since.is_some_and(|t| t.elapsed() >= window)
+    }
+
+    /// Least-squares slope in MB per hour, 0 with fewer than two samples
+    fn growth_mb_per_hour(&self) -> f64 {
+        let Some((origin, _)) = self.samples.front() else {
+            return 0.0;
+        };
+        let points: Vec<(f64, f64)> = self
+            .samples
+            .iter()
+            .map(|(t, mb)| (t.duration_since(*origin).as_secs_f64() / 3600.0, *mb))
+            .collect();
+        let n = points.len() as f64;
+        let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+        let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+        let (cov, var) = points.iter().fold((0.0, 0.0), |(cov, var), (x, y)| {
+            (
+                cov + (x - mean_x) * (y - mean_y),
+                var + (x - mean_x) * (x - mean_x),
+            )
+        });
+        if var > 0.0 {
+            cov / var
+        } else {
+            0.0
+        }
+    }
+}
 
 pub struct StatsCollector {
+    config: ResourceConfig,
+    server_port: Option<u16>,
+    /// Disk space is reported for the disk holding this directory
+    working_dir: PathBuf,
     state: Arc<AppState>,
+    telegram: Option<TelegramClient>,
+    /// Needed for `CpuThresholdAction::Restart`
+    commands: Option<CommandSender>,
     shutdown_rx: watch::Receiver<bool>,
 }
 
 impl StatsCollector {
-    pub fn new(state: Arc<AppState>, shutdown_rx: watch::Receiver<bool>) -> Self {
-        Self { state, shutdown_rx }
+    pub fn new(
+        config: &ResourceConfig,
+        interval: Duration,
+        server_port: Option<u16>,
+        working_dir: Option<String>,
+        state: Arc<AppState>,
+        telegram: Option<TelegramClient>,
+        shutdown_rx: watch::Receiver<bool>,
+    ) -> Self {
+        let working_dir = working_dir
+            .map(PathBuf::from)
+            .unwrap_or_else(|| std::env::current_dir().unwrap_or_default());
+        // The state copy is what config reloads update
+        state.set_stats_interval_secs(interval.as_secs().max(1));
+
+        Self {
+            config: config.clone(),
+            server_port,
+            working_dir,
+            state,
+            telegram,
+            commands: None,
+            shutdown_rx,
+        }
+    }
+
+    pub fn with_commands(mut self, commands: CommandSender) -> Self {
+        self.commands = Some(commands);
+        self
     }
 
     pub async fn run(mut self) {
         let mut system = System::new_all();
         let mut networks = Networks::new_with_refreshed_list();
+        let mut disks = Disks::new_with_refreshed_list();
+        let working_dir = self
+            .working_dir
+            .canonicalize()
+            .unwrap_or_else(|_| self.working_dir.clone());
+        let mut last_disk_space_alert: Option<Instant> = None;
+        let mut last_thread_alert: Option<Instant> = None;
+        let mut last_fd_alert: Option<Instant> = None;
+        let mut cpu_high_secs: u64 = 0;
+        let mut last_cpu_alert: Option<Instant> = None;
+        let mut memory_trend = MemoryTrend::default();
+        let mut last_memory_leak_alert: Option<Instant> = None;
         let mut last_rx: u64 = 0;
         let mut last_tx: u64 = 0;
         let mut last_disk_read: u64 = 0;
         let mut last_disk_write: u64 = 0;
+        let mut detailed_memory: (Option<u64>, Option<u64>) = (None, None);
+        let mut last_detailed_sample: Option<Instant> = None;
+        let mut known_connections: HashSet<String> = HashSet::new();
+        let mut total_connections: u64 = 0;
+        let mut last_connection_alert: Option<Instant> = None;
+
+        // Jolokia is polled on its own task so a slow agent never delays the tick
+        let jvm_stats = self.config.jvm_metrics.clone().map(|jvm_config| {
+            let latest = Arc::new(Mutex::new(JvmStats::default()));
+            tokio::spawn(poll_jvm_metrics(
+                jvm_config,
+                Arc::clone(&self.state),
+                Arc::clone(&latest),
+                self.shutdown_rx.clone(),
+            ));
+            latest
+        });
 
-        let mut ticker = interval(Duration::from_secs(1));
+        let mut interval_secs = self.state.stats_interval_secs();
+        let mut ticker = interval(Duration::from_secs(interval_secs));
+        let mut last_sample = Instant::now();
 
         loop {
             tokio::select! {
@@ -34,10 +184,24 @@ impl StatsCollector {
                 }
             }
 
+            let current_interval = self.state.stats_interval_secs().max(1);
+            if current_interval != interval_secs {
+                interval_secs = current_interval;
+                let period = Duration::from_secs(interval_secs);
+                ticker = interval_at(Instant::now() + period, period);
+                tracing::info!("Stats interval changed to {}s", interval_secs);
+            }
+
+            // Counters below are totals, turned into per-second rates
+            let elapsed = last_sample.elapsed().as_secs_f64().max(0.001);
+            last_sample = Instant::now();
+            let per_sec = |bytes: u64| (bytes as f64 / elapsed) as u64;
+
             let pid = self.state.pid();
 
             system.refresh_all();
             networks.refresh();
+            disks.refresh();
 
             let (cpu, mem_mb, mem_percent) = if let Some(p) = pid {
                 if let Some(process) = system.process(Pid::from_u32(p)) {
@@ -53,6 +217,15 @@ impl StatsCollector {
                 (0.0, 0, 0.0)
             };
 
+            // Whole host
+            let system_memory_total_mb = system.total_memory() / 1024 / 1024;
+            let system_memory_used_mb = system.used_memory() / 1024 / 1024;
+            let system_memory_percent = if system_memory_total_mb > 0 {
+                system_memory_used_mb as f32 / system_memory_total_mb as f32 * 100.0
+            } else {
+                0.0
+            };
+
             // Network stats
             let mut total_rx: u64 = 0;
             let mut total_tx: u64 = 0;
@@ -61,8 +234,8 @@ impl StatsCollector {
                 total_tx += data.total_transmitted();
             }
 
-            let rx_speed = total_rx.saturating_sub(last_rx);
-            let tx_speed = total_tx.saturating_sub(last_tx);
+            let rx_speed = per_sec(total_rx.saturating_sub(last_rx));
+            let tx_speed = per_sec(total_tx.saturating_sub(last_tx));
             last_rx = total_rx;
             last_tx = total_tx;
 
@@ -70,8 +243,9 @@ impl StatsCollector {
             let (disk_read_speed, disk_write_speed) = if let Some(p) = pid {
                 if let Some(process) = system.process(Pid::from_u32(p)) {
                     let disk_usage = process.disk_usage();
-                    let read_speed = disk_usage.read_bytes.saturating_sub(last_disk_read);
-                    let write_speed = disk_usage.written_bytes.saturating_sub(last_disk_write);
+                    let read_speed = per_sec(disk_usage.read_bytes.saturating_sub(last_disk_read));
+                    let write_speed =
+                        per_sec(disk_usage.written_bytes.saturating_sub(last_disk_write));
                     last_disk_read = disk_usage.read_bytes;
                     last_disk_write = disk_usage.written_bytes;
                     (read_speed, write_speed)
@@ -82,17 +256,434 @@ impl StatsCollector {
                 (0, 0)
             };
 
-            self.state.set_stats(ResourceStats {
-                cpu_percent: cpu,
-                memory_mb: mem_mb,
-                memory_percent: mem_percent,
-                network_rx_speed: rx_speed,
-                network_tx_speed: tx_speed,
-                disk_read_speed,
-                disk_write_speed,
+            // Detailed memory (expensive, sampled less often)
+            if self.config.detailed_memory_stats {
+                let due =
+                    last_detailed_sample.is_none_or(|t| t.elapsed() >= DETAILED_MEMORY_INTERVAL);
+                match pid {
+                    None => {
+                        detailed_memory = (None, None);
+                        last_detailed_sample = None;
+                    }
+                    Some(p) if due => {
+                        last_detailed_sample = Some(Instant::now());
+                        detailed_memory = read_smaps_rollup(p).unwrap_or((None, None));
+                    }
+                    Some(_) => {}
+                }
+            }
+
+            // Established connections on the game port
+            let connections = self.server_port.and_then(read_tcp_connections);
+            if let Some(ref current) = connections {
+                total_connections += current.difference(&known_connections).count() as u64;
+                known_connections = current.clone();
+            }
+            let active_connections = connections.map(|c| c.len() as u32);
+
+            if let (Some(active), Some(limit)) =
+                (active_connections, self.config.max_connections_warning)
+            {
+                let due =
+                    last_connection_alert.is_none_or(|t| t.elapsed() >= CONNECTION_ALERT_INTERVAL);
+                if active > limit && due {
+                    last_connection_alert = Some(Instant::now());
+                    self.state.add_watcher_log(format!(
+                        "{} active connections, warning threshold {}",
+                        active, limit
+                    ));
+                    if let Some(ref tg) = self.telegram {
+                        tg.notify(
+                            NotifyType::Resources,
+                            &format!("{} active connections (threshold {})", active, limit),
+                        )
+                        .await;
+                    }
+                }
+            }
+
+            // Threads and descriptors climb steadily when the server leaks them
+            let thread_count = pid.map_or(0, read_thread_count);
+            let open_fd_count = pid.map_or(0, read_open_fd_count);
+            self.warn_over_limit(
+                &mut last_thread_alert,
+                "threads",
+                thread_count,
+                self.config.thread_threshold,
+            )
+            .await;
+            self.warn_over_limit(
+                &mut last_fd_alert,
+                "open file descriptors",
+                open_fd_count,
+                self.config.fd_threshold,
+            )
+            .await;
+
+            // Free space where the server writes its world and logs
+            let (disk_free, disk_total) = disk_for_path(&disks, &working_dir)
+                .map(|d| (d.available_space(), d.total_space()))
+                .unwrap_or((0, 0));
+            let disk_usage = if disk_total > 0 {
+                (disk_total - disk_free) as f32 / disk_total as f32 * 100.0
+            } else {
+                0.0
+            };
+            let due =
+                last_disk_space_alert.is_none_or(|t| t.elapsed() >= DISK_SPACE_ALERT_INTERVAL);
+            if disk_usage > self.config.disk_threshold_percent && due {
+                last_disk_space_alert = Some(Instant::now());
+                let message = format!(
+                    "Disk {:.1}% full ({} free), threshold {:.1}%",
+                    disk_usage,
+                    format_bytes(disk_free),
+                    self.config.disk_threshold_percent
+                );
+                self.state
+                    .add_log(LogLevel::Warning, LogSource::Watcher, message.clone());
+                if let Some(ref tg) = self.telegram {
+                    tg.notify(NotifyType::Resources, &message).await;
+                }
+            }
+
+            self.check_sustained_cpu(cpu, interval_secs, &mut cpu_high_secs, &mut last_cpu_alert)
+                .await;
+
+            // Steady growth over the whole window looks like a leak
+            let leak_window = Duration::from_secs(self.config.memory_leak_window_minutes * 60);
+            memory_trend.push(pid, mem_mb, leak_window);
+            let memory_growth = memory_trend.growth_mb_per_hour();
+            let due =
+                last_memory_leak_alert.is_none_or(|t| t.elapsed() >= MEMORY_LEAK_ALERT_INTERVAL);
+            if memory_growth > self.config.memory_leak_threshold_mb_per_hour
+                && memory_trend.covers(leak_window)
+                && due
+            {
+                last_memory_leak_alert = Some(Instant::now());
+                let hours_to_oom =
+                    system_memory_total_mb.saturating_sub(mem_mb) as f64 / memory_growth;
+                let message = format!(
+                    "Possible memory leak: server memory growing {:.0} MB/h over the last {} min, \
+                     host memory exhausted in ~{:.1} h",
+                    memory_growth, self.config.memory_leak_window_minutes, hours_to_oom
+                );
+                self.state
+                    .add_log(LogLevel::Warning, LogSource::Watcher, message.clone());
+                if let Some(ref tg) = self.telegram {
+                    tg.notify(NotifyType::Resources, &message).await;
+                }
+            }
+
+            self.state.update_resource_alert(
+                "cpu",
+                self.config.cpu_threshold_percent as f64,
+                cpu as f64,
+            );
+            self.state.update_resource_alert(
+                "memory",
+                self.config.memory_threshold_mb as f64,
+                mem_mb as f64,
+            );
+            if let Some(threshold) = self.config.disk_io_threshold_mb_per_sec {
+                let disk_mb = (disk_read_speed + disk_write_speed) as f64 / 1024.0 / 1024.0;
+                self.state
+                    .update_resource_alert("disk_io", threshold as f64, disk_mb);
+            }
+
+            self.state.set_stats_snapshot(StatsSnapshot {
+                timestamp: Utc::now(),
+                stats: ResourceStats {
+                    cpu_percent: cpu,
+                    memory_mb: mem_mb,
+                    memory_percent: mem_percent,
+                    network_rx_speed: rx_speed,
+                    network_tx_speed: tx_speed,
+                    disk_read_speed,
+                    disk_write_speed,
+                    memory_pss_mb: detailed_memory.0,
+                    memory_private_dirty_mb: detailed_memory.1,
+                    jvm: jvm_stats.as_ref().map(|latest| latest.lock().clone()),
+                    active_connections,
+                    total_connections,
+                    disk_free_bytes: disk_free,
+                    disk_total_bytes: disk_total,
+                    disk_usage_percent: disk_usage,
+                    thread_count,
+                    open_fd_count,
+                    system_cpu_percent: system.global_cpu_usage(),
+                    system_memory_total_mb,
+                    system_memory_used_mb,
+                    system_memory_percent,
+                    system_load_avg_1min: System::load_average().one,
+                    memory_growth_mb_per_hour: memory_growth,
+                },
             });
         }
 
         tracing::info!("Stats collector stopped");
     }
+
+    /// Alert once CPU has been above the threshold for
+    /// `cpu_threshold_sustained_seconds` in a row, and restart the server
+    /// if configured to
+    async fn check_sustained_cpu(
+        &self,
+        cpu: f32,
+        tick_secs: u64,
+        high_secs: &mut u64,
+        last_alert: &mut Option<Instant>,
+    ) {
+        if cpu <= self.config.cpu_threshold_percent {
+            *high_secs = 0;
+            return;
+        }
+        *high_secs += tick_secs;
+        if *high_secs < self.config.cpu_threshold_sustained_seconds {
+            return;
+        }
+        *high_secs = 0;
+
+        let cooldown = Duration::from_secs(self.config.cpu_alert_cooldown_seconds);
+        if last_alert.is_some_and(|t| t.elapsed() < cooldown) {
+            return;
+        }
+        *last_alert = Some(Instant::now());
+
+        let restart = self.config.cpu_threshold_action == Some(CpuThresholdAction::Restart);
+        let message = format!(
+            "CPU above {:.1}% for {}s (now {:.1}%){}",
+            self.config.cpu_threshold_percent,
+            self.config.cpu_threshold_sustained_seconds,
+            cpu,
+            if restart { ", restarting server" } else { "" }
+        );
+        self.state
+            .add_log(LogLevel::Warning, LogSource::Watcher, message.clone());
+        if let Some(ref tg) = self.telegram {
+            tg.notify(NotifyType::Resources, &message).await;
+        }
+
+        if restart {
+            if let Some(ref commands) = self.commands {
+                if commands.send(ProcessCommand::Restart).await.is_err() {
+                    tracing::warn!("Process manager is gone, cannot restart on high CPU");
+                }
+            }
+        }
+    }
+
+    /// Log and notify when `value` is above `limit`, at most once per
+    /// `LEAK_ALERT_INTERVAL`
+    async fn warn_over_limit(
+        &self,
+        last_alert: &mut Option<Instant>,
+        what: &str,
+        value: u32,
+        limit: Option<u32>,
+    ) {
+        let Some(limit) = limit else {
+            return;
+        };
+        let due = last_alert.is_none_or(|t| t.elapsed() >= LEAK_ALERT_INTERVAL);
+        if value <= limit || !due {
+            return;
+        }
+        *last_alert = Some(Instant::now());
+
+        let message = format!("{} {}, warning threshold {}", value, what, limit);
+        self.state
+            .add_log(LogLevel::Warning, LogSource::Watcher, message.clone());
+        if let Some(ref tg) = self.telegram {
+            tg.notify(NotifyType::Resources, &message).await;
+        }
+    }
+}
+
+/// The disk whose mount point is the longest prefix of `path`
+fn disk_for_path<'a>(disks: &'a Disks, path: &Path) -> Option<&'a Disk> {
+    disks
+        .iter()
+        .filter(|d| path.starts_with(d.mount_point()))
+        .max_by_key(|d| d.mount_point().as_os_str().len())
+}
+
+/// Poll the Jolokia agent every `poll_interval_secs` while the server runs
+async fn poll_jvm_metrics(
+    config: JvmMetricsConfig,
+    state: Arc<AppState>,
+    latest: Arc<Mutex<JvmStats>>,
+    mut shutdown_rx: watch::Receiver<bool>,
+) {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(2))
+        .build()
+        .unwrap_or_default();
+    let base = config.jolokia_url.trim_end_matches('/').to_string();
+    let mut ticker = interval(Duration::from_secs(config.poll_interval_secs.max(1)));
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown_rx.changed() => {
+                if *shutdown_rx.borrow() {
+                    break;
+                }
+            }
+        }
+
+        let stats = if state.pid().is_some() {
+            read_jvm_stats(&client, &base).await
+        } else {
+            JvmStats::default()
+        };
+        *latest.lock() = stats;
+    }
+}
+
+async fn jolokia_value(client: &reqwest::Client, url: &str) -> Option<serde_json::Value> {
+    let response: serde_json::Value = client.get(url).send().await.ok()?.json().await.ok()?;
+    response.get("value").cloned()
+}
+
+async fn read_jvm_stats(client: &reqwest::Client, base: &str) -> JvmStats {
+    let mut stats = JvmStats::default();
+
+    let heap_url = format!("{}/read/java.lang:type=Memory/HeapMemoryUsage", base);
+    if let Some(heap) = jolokia_value(client, &heap_url).await {
+        let mb = |key: &str| heap.get(key)?.as_u64().map(|b| b / 1024 / 1024);
+        stats.heap_used_mb = mb("used");
+        // max is -1 when undefined, which as_u64 turns into None
+        stats.heap_max_mb = mb("max");
+    }
+
+    // One entry per collector, keyed by MBean name
+    let gc_url = format!(
+        "{}/read/java.lang:type=GarbageCollector,name=*/CollectionCount,CollectionTime",
+        base
+    );
+    if let Some(serde_json::Value::Object(collectors)) = jolokia_value(client, &gc_url).await {
+        let sum = |key: &str| {
+            collectors
+                .values()
+                .filter_map(|c| c.get(key)?.as_u64())
+                .sum::<u64>()
+        };
+        stats.gc_collection_count = Some(sum("CollectionCount"));
+        stats.gc_collection_time_ms = Some(sum("CollectionTime"));
+    }
+
+    stats
+}
+
+/// Read `Pss` and `Private_Dirty` (in MB) from `/proc/<pid>/smaps_rollup`
+#[cfg(target_os = "linux")]
+fn read_smaps_rollup(pid: u32) -> Option<(Option<u64>, Option<u64>)> {
+    let content = std::fs::read_to_string(format!("/proc/{}/smaps_rollup", pid)).ok()?;
+    let field_mb = |name: &str| {
+        content.lines().find_map(|line| {
+            let rest = line.strip_prefix(name)?.strip_prefix(':')?;
+            let kb: u64 = rest.trim().trim_end_matches("kB").trim().parse().ok()?;
+            Some(kb / 1024)
+        })
+    };
+    Some((field_mb("Pss"), field_mb("Private_Dirty")))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_smaps_rollup(_pid: u32) -> Option<(Option<u64>, Option<u64>)> {
+    None
+}
+
+/// Entries in `/proc/<pid>/task`. Read directly because sysinfo only fills
+/// `Process::tasks()` when it first sees a process.
+#[cfg(target_os = "linux")]
+fn read_thread_count(pid: u32) -> u32 {
+    std::fs::read_dir(format!("/proc/{}/task", pid)).map_or(0, |entries| entries.count() as u32)
+}
+
+/// Threads of `pid` from a Toolhelp process snapshot
+#[cfg(windows)]
+fn read_thread_count(pid: u32) -> u32 {
+    use windows_sys::Win32::Foundation::{CloseHandle, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Process32First, Process32Next, PROCESSENTRY32, TH32CS_SNAPPROCESS,
+    };
+
+    // SAFETY: the snapshot handle is checked before use and closed, and
+    // `entry.dwSize` is set as the API requires
+    unsafe {
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
+        if snapshot == INVALID_HANDLE_VALUE {
+            return 0;
+        }
+        let mut entry: PROCESSENTRY32 = std::mem::zeroed();
+        entry.dwSize = std::mem::size_of::<PROCESSENTRY32>() as u32;
+
+        let mut count = 0;
+        let mut more = Process32First(snapshot, &mut entry) != 0;
+        while more {
+            if entry.th32ProcessID == pid {
+                count = entry.cntThreads;
+                break;
+            }
+            more = Process32Next(snapshot, &mut entry) != 0;
+        }
+        CloseHandle(snapshot);
+        count
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn read_thread_count(_pid: u32) -> u32 {
+    0
+}
+
+/// Entries in `/proc/<pid>/fd`
+#[cfg(target_os = "linux")]
+fn read_open_fd_count(pid: u32) -> u32 {
+    std::fs::read_dir(format!("/proc/{}/fd", pid)).map_or(0, |entries| entries.count() as u32)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_open_fd_count(_pid: u32) -> u32 {
+    0
+}
+
+/// `ESTABLISHED` in the `st` column of `/proc/net/tcp`
+#[cfg(target_os = "linux")]
+const TCP_ESTABLISHED: &str = "01";
+
+/// Established connections whose local port is `port`, from `/proc/net/tcp`
+/// and `/proc/net/tcp6`, keyed by their local and remote addresses
+#[cfg(target_os = "linux")]
+fn read_tcp_connections(port: u16) -> Option<HashSet<String>> {
+    let port_hex = format!("{:04X}", port);
+    let mut connections = HashSet::new();
+    let mut readable = false;
+
+    for table in ["/proc/net/tcp", "/proc/net/tcp6"] {
+        let Ok(content) = std::fs::read_to_string(table) else {
+            continue;
+        };
+        readable = true;
+        for line in content.lines().skip(1) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (Some(local), Some(remote), Some(st)) =
+                (fields.get(1), fields.get(2), fields.get(3))
+            else {
+                continue;
+            };
+            let local_port = local.rsplit_once(':').map(|(_, p)| p);
+            if *st == TCP_ESTABLISHED && local_port == Some(port_hex.as_str()) {
+                connections.insert(format!("{}-{}", local, remote));
+            }
+        }
+    }
+
+    readable.then_some(connections)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_tcp_connections(_port: u16) -> Option<HashSet<String>> {
+    None
 }
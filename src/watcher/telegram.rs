@@ -1,6 +1,19 @@
 use crate::config::TelegramConfig;
-use chrono::Local;
+use chrono::{DateTime, Local};
+use html_escape::encode_text;
+use parking_lot::{Mutex, RwLock};
 use serde_json::json;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A send that takes longer than this counts as failed and is queued
+const SEND_TIMEOUT: Duration = Duration::from_secs(15);
+
+const TELEGRAM_API_URL: &str = "https://api.telegram.org";
 
 #[derive(Debug, Clone, Copy)]
 pub enum NotifyType {
@@ -15,17 +28,151 @@ pub enum NotifyType {
     Backup,
 }
 
+/// A notification that failed to send and is waiting for a retry
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingMessage {
+    pub text: String,
+    pub retries: u32,
+    pub queued_at: DateTime<Local>,
+}
+
 #[derive(Clone)]
 pub struct TelegramClient {
     config: TelegramConfig,
+    /// Shared between clones so renames apply everywhere
+    server_name: Arc<RwLock<String>>,
+    /// While set, only critical notifications go out
+    maintenance: Arc<AtomicBool>,
+    /// Failed sends, oldest first
+    queue: Arc<Mutex<VecDeque<PendingMessage>>>,
     client: reqwest::Client,
+    api_url: String,
+    /// Dropped with the last clone, which stops the retry task
+    alive: Arc<()>,
 }
 
 impl TelegramClient {
-    pub fn new(config: TelegramConfig) -> Self {
-        Self {
+    pub fn new(config: TelegramConfig, server_name: String) -> Self {
+        let client = Self {
             config,
-            client: reqwest::Client::new(),
+            server_name: Arc::new(RwLock::new(server_name)),
+            maintenance: Arc::new(AtomicBool::new(false)),
+            queue: Arc::new(Mutex::new(VecDeque::new())),
+            client: reqwest::Client::builder()
+                .timeout(SEND_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            api_url: TELEGRAM_API_URL.to_string(),
+            alive: Arc::new(()),
+        };
+
+        if client.config.enabled {
+            // The task's own copy must not keep the client alive
+            let retrier = Self {
+                alive: Arc::new(()),
+                ..client.clone()
+            };
+            tokio::spawn(retrier.retry_queued(Arc::downgrade(&client.alive)));
+        }
+
+        client
+    }
+
+    /// Follow the maintenance flag from `AppState::maintenance_flag`
+    pub fn with_maintenance_flag(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.maintenance = flag;
+        self
+    }
+
+    pub fn set_server_name(&self, name: String) {
+        *self.server_name.write() = name;
+    }
+
+    pub fn verbose(&self) -> bool {
+        self.config.verbose_notifications
+    }
+
+    /// Number of notifications waiting for a retry
+    pub fn queue_depth(&self) -> usize {
+        self.queue.lock().len()
+    }
+
+    /// The most recently queued notifications, oldest first
+    pub fn pending_messages(&self, limit: usize) -> Vec<PendingMessage> {
+        let queue = self.queue.lock();
+        queue
+            .iter()
+            .skip(queue.len().saturating_sub(limit))
+            .cloned()
+            .collect()
+    }
+
+    fn enqueue(&self, message: PendingMessage) {
+        let mut queue = self.queue.lock();
+        queue.push_back(message);
+        while queue.len() > self.config.queue_size {
+            if let Some(dropped) = queue.pop_front() {
+                tracing::warn!(
+                    "Telegram queue full, dropping notification queued at {}",
+                    dropped.queued_at.format("%Y-%m-%d %H:%M:%S")
+                );
+            }
+        }
+    }
+
+    /// Resend queued notifications every 30 seconds, oldest first, until
+    /// every clone of the client is gone
+    async fn retry_queued(self, alive: Weak<()>) {
+        let mut interval = tokio::time::interval(RETRY_INTERVAL);
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+            if alive.strong_count() == 0 {
+                break;
+            }
+            self.retry_round().await;
+        }
+    }
+
+    /// Send queued notifications until the queue is empty or a send fails,
+    /// since then the network is likely down. A message leaves the queue
+    /// only once Telegram has accepted it.
+    async fn retry_round(&self) {
+        loop {
+            let Some(message) = self.queue.lock().front().cloned() else {
+                break;
+            };
+
+            let result = self.send(&message.text).await;
+            let mut queue = self.queue.lock();
+            // The queue may have overflowed while sending
+            if queue.front() != Some(&message) {
+                continue;
+            }
+            match result {
+                Ok(()) => {
+                    queue.pop_front();
+                    tracing::info!(
+                        "Queued telegram notification sent after {} retries",
+                        message.retries + 1
+                    );
+                }
+                Err(e) => {
+                    let retries = message.retries + 1;
+                    if retries > self.config.max_retries {
+                        queue.pop_front();
+                        tracing::warn!(
+                            "Dropping telegram notification after {} retries: {}",
+                            self.config.max_retries,
+                            e
+                        );
+                    } else if let Some(front) = queue.front_mut() {
+                        front.retries = retries;
+                    }
+                    break;
+                }
+            }
         }
     }
 
@@ -34,10 +181,7 @@ impl TelegramClient {
             return Ok(());
         }
 
-        let url = format!(
-            "https://api.telegram.org/bot{}/sendMessage",
-            self.config.token
-        );
+        let url = format!("{}/bot{}/sendMessage", self.api_url, self.config.token);
 
         let body = json!({
             "chat_id": self.config.chat_id,
@@ -45,11 +189,67 @@ impl TelegramClient {
             "parse_mode": "HTML"
         });
 
-        self.client.post(&url).json(&body).send().await?;
+        // Telegram answers a rejected message with an error status
+        self.client
+            .post(&url)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
         Ok(())
     }
 
+    /// Send a plain-text notification (escaped for HTML)
     pub async fn notify(&self, event_type: NotifyType, message: &str) {
+        self.notify_html(event_type, &encode_text(message)).await;
+    }
+
+    /// Server started, with PID and working directory in verbose mode
+    pub async fn notify_start(&self, pid: u32, working_dir: &str) {
+        let body = if self.verbose() {
+            format!(
+                "Server started\nPID: <code>{}</code>\nDirectory: <code>{}</code>",
+                pid,
+                encode_text(working_dir)
+            )
+        } else {
+            format!("Server started (PID: {})", pid)
+        };
+        self.notify_html(NotifyType::Start, &body).await;
+    }
+
+    /// Error triggered by a log line, quoted in `<pre>` in verbose mode
+    pub async fn notify_log_line(&self, event_type: NotifyType, line: &str) {
+        let body = if self.verbose() {
+            format!("Triggered by log line:\n<pre>{}</pre>", encode_text(line))
+        } else {
+            encode_text(line).into_owned()
+        };
+        self.notify_html(event_type, &body).await;
+    }
+
+    /// Backup created, with checksum in verbose mode if known
+    pub async fn notify_backup(&self, filename: &str, size: &str, sha256: Option<&str>) {
+        let mut body = format!(
+            "Backup created: <code>{}</code> ({})",
+            encode_text(filename),
+            encode_text(size)
+        );
+        if self.verbose() {
+            if let Some(sum) = sha256 {
+                body.push_str(&format!("\nSHA-256: <code>{}</code>", encode_text(sum)));
+            }
+        }
+        self.notify_html(NotifyType::Backup, &body).await;
+    }
+
+    /// Send a notification whose body is already valid Telegram HTML
+    pub async fn notify_html(&self, event_type: NotifyType, body: &str) {
+        if self.maintenance.load(Ordering::Relaxed) && !matches!(event_type, NotifyType::Critical) {
+            tracing::debug!("Maintenance mode, notification suppressed");
+            return;
+        }
+
         let (emoji, label) = match event_type {
             NotifyType::Start => ("🚀", "START"),
             NotifyType::Error => ("⚠️", "ERROR"),
@@ -63,10 +263,111 @@ impl TelegramClient {
         };
 
         let time = Local::now().format("%H:%M:%S");
-        let text = format!("{} <b>[{}]</b> {}\n<i>{}</i>", emoji, time, label, message);
+        let text = format!(
+            "{} <b>[{}]</b> {} · <b>{}</b>\n{}",
+            emoji,
+            time,
+            label,
+            encode_text(self.server_name.read().as_str()),
+            body
+        );
 
         if let Err(e) = self.send(&text).await {
-            tracing::error!("Failed to send telegram notification: {}", e);
+            tracing::error!(
+                "Failed to send telegram notification, queued for retry: {}",
+                e
+            );
+            self.enqueue(PendingMessage {
+                text,
+                retries: 0,
+                queued_at: Local::now(),
+            });
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode, routing::post, Router};
+    use std::sync::atomic::AtomicUsize;
+
+    /// Answers with `statuses` in turn, then 200
+    async fn fake_api(statuses: Vec<StatusCode>) -> String {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = Router::new().route(
+            "/:bot/sendMessage",
+            post(move || {
+                let call = calls.fetch_add(1, Ordering::SeqCst);
+                let status = statuses.get(call).copied().unwrap_or(StatusCode::OK);
+                async move { status }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    fn test_client(api_url: String) -> TelegramClient {
+        let mut client = TelegramClient::new(
+            TelegramConfig {
+                enabled: true,
+                token: "token".to_string(),
+                chat_id: "1".to_string(),
+                verbose_notifications: false,
+                queue_size: 10,
+                max_retries: 3,
+            },
+            "test".to_string(),
+        );
+        client.api_url = api_url;
+        client
+    }
+
+    #[tokio::test]
+    async fn error_status_fails_the_send() {
+        let client = test_client(fake_api(vec![StatusCode::INTERNAL_SERVER_ERROR]).await);
+        assert!(client.send("hello").await.is_err());
+
+        client.enqueue(PendingMessage {
+            text: "hello".to_string(),
+            retries: 0,
+            queued_at: Local::now(),
+        });
+        client.retry_round().await;
+        assert_eq!(client.queue_depth(), 0);
+    }
+
+    #[tokio::test]
+    async fn failed_retry_stays_at_the_front() {
+        let client = test_client(fake_api(vec![StatusCode::BAD_GATEWAY]).await);
+        client.enqueue(PendingMessage {
+            text: "hello".to_string(),
+            retries: 0,
+            queued_at: Local::now(),
+        });
+
+        client.retry_round().await;
+        assert_eq!(client.pending_messages(10)[0].retries, 1);
+        client.retry_round().await;
+        assert_eq!(client.queue_depth(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_task_stops_with_the_client() {
+        let client = test_client(TELEGRAM_API_URL.to_string());
+        let alive = Arc::downgrade(&client.alive);
+        let retrier = TelegramClient {
+            alive: Arc::new(()),
+            ..client.clone()
+        };
+        let task = tokio::spawn(retrier.retry_queued(alive));
+        drop(client);
+
+        tokio::time::timeout(RETRY_INTERVAL * 3, task)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
@@ -1,33 +1,87 @@
-use crate::config::Config;
-use crate::watcher::backup::{delete_backup, format_bytes, list_backups};
-use crate::watcher::process::ProcessCommand;
-use crate::watcher::state::AppState;
+use crate::config::{
+    changed_fields, Config, ErrorPatterns, IdentityConfig, MaintenanceWindow, RemoteServer,
+    ServerConfig,
+};
+use crate::watcher::analytics::{crash_correlation, CrashCorrelation};
+use crate::watcher::backup::{
+    add_backup_tag, archive_manifest, check_passphrase, delete_backup, diff_backups, format_bytes,
+    is_backup_filename, is_encrypted_backup, list_backups, list_backups_paginated, read_checksum,
+    remove_backup_tag, restore_backup, search_backups, test_backup, BackupCommand, BackupDiff,
+    BackupQuery, RestoreReport, TestBackupReport,
+};
+use crate::watcher::heapdump::{delete_heap_dump, heap_dump_dirs, list_heap_dumps};
+use crate::watcher::logsink::read_log_files;
+use crate::watcher::process::{
+    parse_signal_name, refresh_java_version, CommandSender, CompiledPatterns, OpenFile,
+    ProcessCommand, StdinWriteError,
+};
+use crate::watcher::state::{
+    AppState, BackupInfo, BackupUploadProgress, JvmStats, LogEntry, LogLevel, LogSource,
+    MaintenanceInfo, ResourceAlertState, ResourceStats, RestartReason, RestartRecord, ServerStatus,
+    TimelineEvent,
+};
+use crate::watcher::telegram::{PendingMessage, TelegramClient};
+use crate::web::auth::token_from_parts;
+use crate::web::export::{render_logs, LogExportFormat};
+use crate::web::middleware::RequestId;
+use crate::web::openapi::ApiDoc;
+use crate::web::report::{generate_report, ReportFormat};
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{Extension, Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode, Uri},
+    response::{Html, IntoResponse, Response},
     Json,
 };
+use chrono::{DateTime, Local};
+use futures_util::future::join_all;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc;
+use utoipa::{IntoParams, OpenApi, ToSchema};
 
 /// Shared state for API handlers
 #[derive(Clone)]
 pub struct ApiState {
     pub app_state: Arc<AppState>,
     pub config: Arc<RwLock<Config>>,
-    pub process_tx: mpsc::Sender<ProcessCommand>,
+    /// Where config changes made through the API are saved
+    pub config_path: PathBuf,
+    pub process_tx: CommandSender,
+    pub backup_tx: mpsc::Sender<BackupCommand>,
     pub backup_path: PathBuf,
+    pub source_path: PathBuf,
+    pub telegram: Option<TelegramClient>,
+}
+
+#[cfg(test)]
+impl ApiState {
+    /// State for handler tests; commands and backup requests go nowhere
+    pub fn for_tests(config: Config) -> Self {
+        use crate::watcher::process::command_channel;
+
+        let dir = std::env::temp_dir().join(format!("watcher-test-{}", uuid::Uuid::new_v4()));
+        Self {
+            app_state: AppState::new(),
+            config: Arc::new(RwLock::new(config)),
+            config_path: dir.join("config-watcher.json"),
+            process_tx: command_channel(16).0,
+            backup_tx: mpsc::channel(1).0,
+            backup_path: dir.join("backups"),
+            source_path: dir.join("universe"),
+            telegram: None,
+        }
+    }
 }
 
 // ============================================================================
 // Response types
 // ============================================================================
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct StatusResponse {
     pub status: String,
     pub pid: Option<u32>,
@@ -35,9 +89,16 @@ pub struct StatusResponse {
     pub restart_count: u32,
     pub auto_restart_remaining_secs: Option<u64>,
     pub next_backup_secs: Option<u64>,
+    pub startup_latency_ms: Option<u64>,
+    pub avg_startup_latency_ms: Option<u64>,
+    pub restart_delay_secs: u64,
+    pub max_logs: usize,
+    pub current_log_count: usize,
+    /// Waiting out the delay before a restart; see `POST /api/restart/cancel`
+    pub pending_restart: bool,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct StatsResponse {
     pub cpu_percent: f32,
     pub memory_mb: u64,
@@ -46,31 +107,234 @@ pub struct StatsResponse {
     pub network_tx_speed: u64,
     pub disk_read_speed: u64,
     pub disk_write_speed: u64,
+    pub memory_pss_mb: Option<u64>,
+    pub memory_private_dirty_mb: Option<u64>,
+    pub jvm: Option<JvmStats>,
+    pub active_connections: Option<u32>,
+    pub total_connections: u64,
+    pub disk_free_bytes: u64,
+    pub disk_total_bytes: u64,
+    pub disk_usage_percent: f32,
+    pub thread_count: u32,
+    pub open_fd_count: u32,
+    pub system_cpu_percent: f32,
+    pub system_memory_total_mb: u64,
+    pub system_memory_used_mb: u64,
+    pub system_memory_percent: f32,
+    pub system_load_avg_1min: f64,
+    pub memory_growth_mb_per_hour: f64,
+    pub dropped_log_lines: u64,
+    pub stdin_write_timeouts: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct StatsHistoryEntry {
+    pub timestamp: String,
+    #[serde(flatten)]
+    pub stats: ResourceStats,
+}
+
+impl StatsResponse {
+    fn from_state(app_state: &AppState) -> Self {
+        let stats = app_state.stats();
+        Self {
+            cpu_percent: stats.cpu_percent,
+            memory_mb: stats.memory_mb,
+            memory_percent: stats.memory_percent,
+            network_rx_speed: stats.network_rx_speed,
+            network_tx_speed: stats.network_tx_speed,
+            disk_read_speed: stats.disk_read_speed,
+            disk_write_speed: stats.disk_write_speed,
+            memory_pss_mb: stats.memory_pss_mb,
+            memory_private_dirty_mb: stats.memory_private_dirty_mb,
+            jvm: stats.jvm,
+            active_connections: stats.active_connections,
+            total_connections: stats.total_connections,
+            disk_free_bytes: stats.disk_free_bytes,
+            disk_total_bytes: stats.disk_total_bytes,
+            disk_usage_percent: stats.disk_usage_percent,
+            thread_count: stats.thread_count,
+            open_fd_count: stats.open_fd_count,
+            system_cpu_percent: stats.system_cpu_percent,
+            system_memory_total_mb: stats.system_memory_total_mb,
+            system_memory_used_mb: stats.system_memory_used_mb,
+            system_memory_percent: stats.system_memory_percent,
+            system_load_avg_1min: stats.system_load_avg_1min,
+            memory_growth_mb_per_hour: stats.memory_growth_mb_per_hour,
+            dropped_log_lines: app_state.dropped_log_lines(),
+            stdin_write_timeouts: app_state.stdin_write_timeouts(),
+        }
+    }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct LogResponse {
+    #[serde(default)]
+    pub id: u64,
     pub timestamp: String,
     pub level: String,
     pub source: String,
     pub message: String,
+    /// Which watcher the line came from; only set on proxied or merged logs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_id: Option<String>,
+    /// Named groups captured by `extract_fields` error patterns
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub fields: HashMap<String, String>,
+    /// Identical lines collapsed into this one
+    #[serde(default)]
+    pub repeat_count: u32,
+}
+
+impl From<LogEntry> for LogResponse {
+    fn from(log: LogEntry) -> Self {
+        Self {
+            id: log.id,
+            timestamp: log.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+            level: format!("{:?}", log.level).to_lowercase(),
+            source: format!("{:?}", log.source).to_lowercase(),
+            message: log.message,
+            server_id: None,
+            fields: log.fields,
+            repeat_count: log.repeat_count,
+        }
+    }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct BackupResponse {
     pub filename: String,
     pub size: String,
     pub size_bytes: u64,
     pub created_at: String,
+    pub files_count: Option<u64>,
+    pub original_size_bytes: Option<u64>,
+    pub watcher_version: Option<String>,
+    pub tags: Vec<String>,
+    pub checksum: Option<String>,
+}
+
+impl From<BackupInfo> for BackupResponse {
+    fn from(b: BackupInfo) -> Self {
+        Self {
+            filename: b.filename,
+            size: format_bytes(b.size_bytes),
+            size_bytes: b.size_bytes,
+            created_at: b.created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+            files_count: b.files_count,
+            original_size_bytes: b.original_size_bytes,
+            watcher_version: b.watcher_version,
+            tags: b.tags,
+            checksum: b.checksum,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MaintenanceResponse {
+    pub enabled: bool,
+    pub reason: Option<String>,
+    pub started_at: Option<String>,
+    pub remaining_secs: Option<u64>,
+    /// Configured `maintenance_windows`
+    pub windows: Vec<MaintenanceWindow>,
+    /// Auto-restarts are currently held by a window
+    pub in_window: bool,
+    pub window_ends_at: Option<String>,
+}
+
+impl MaintenanceResponse {
+    fn from_info(info: Option<MaintenanceInfo>, server: &ServerConfig) -> Self {
+        let window_end = server
+            .active_maintenance_window(Local::now().naive_local())
+            .map(|(_, end)| end.format("%Y-%m-%d %H:%M:%S").to_string());
+        let (enabled, reason, started_at, remaining_secs) = match info {
+            Some(m) => (
+                true,
+                m.reason,
+                Some(m.started_at.format("%Y-%m-%d %H:%M:%S").to_string()),
+                m.until
+                    .map(|until| (until - Local::now()).num_seconds().max(0) as u64),
+            ),
+            None => (false, None, None, None),
+        };
+        Self {
+            enabled,
+            reason,
+            started_at,
+            remaining_secs,
+            windows: server.maintenance_windows.clone(),
+            in_window: window_end.is_some(),
+            window_ends_at: window_end,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct HeapDumpResponse {
+    pub filename: String,
+    pub size: String,
+    pub size_bytes: u64,
+    pub created_at: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CheckpointResponse {
+    pub acknowledged_at: String,
+    pub ack_ms: u64,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
+pub struct BackupStatsResponse {
+    pub compression_level: u8,
+    pub auto_compression: bool,
+    pub skipped_backup_count: u32,
+    pub in_progress: bool,
+    pub upload: Option<BackupUploadProgress>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UptimeResponse {
+    pub watcher_uptime_secs: u64,
+    pub total_running_secs: u64,
+    pub total_downtime_secs: u64,
+    pub availability_percent: f64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SystemResponse {
+    pub watcher_version: &'static str,
+    pub os: String,
+    pub arch: &'static str,
+    pub java_version: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BackupScheduleResponse {
+    /// RFC 3339, local time zone
+    pub next_backup_at: String,
+    pub next_backup_in_secs: u64,
+    pub interval_hours: f64,
+    pub last_backup_at: Option<String>,
+    pub estimated_duration_secs: Option<u64>,
+    pub last_3_durations_secs: Vec<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LogCategoryResponse {
+    pub name: String,
+    pub pattern: String,
+    pub color: String,
+    pub count: usize,
+}
+
+#[derive(Serialize, ToSchema)]
 pub struct SuccessResponse {
     pub success: bool,
     pub message: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct FullStateResponse {
     pub status: StatusResponse,
     pub stats: StatsResponse,
@@ -78,94 +342,906 @@ pub struct FullStateResponse {
     pub backups: Vec<BackupResponse>,
 }
 
+#[derive(Serialize, ToSchema)]
+pub struct PatternTestResponse {
+    pub matches: Vec<LogResponse>,
+    pub match_count: u32,
+    pub total_tested: u32,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CrashReportResponse {
+    pub exit_reason: String,
+    pub exit_code: Option<i32>,
+    pub last_lines: Vec<String>,
+    pub timestamp: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct OpenFilesResponse {
+    pub pid: u32,
+    pub open_files: Vec<OpenFile>,
+    pub truncated: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TimelineResponse {
+    pub events: Vec<TimelineEvent>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PendingMessageResponse {
+    pub text: String,
+    pub retries: u32,
+    pub queued_at: String,
+}
+
+impl From<PendingMessage> for PendingMessageResponse {
+    fn from(m: PendingMessage) -> Self {
+        Self {
+            text: m.text,
+            retries: m.retries,
+            queued_at: m.queued_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TriggerBackupResponse {
+    pub filename: String,
+    pub size: String,
+    pub size_bytes: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DecryptTestResponse {
+    pub filename: String,
+    pub valid: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RestartHistoryResponse {
+    pub timestamp: String,
+    pub reason: String,
+    pub restart_reason: RestartReason,
+    pub restart_number: u32,
+}
+
+impl From<RestartRecord> for RestartHistoryResponse {
+    fn from(r: RestartRecord) -> Self {
+        Self {
+            timestamp: r.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+            reason: r.reason,
+            restart_reason: r.restart_reason,
+            restart_number: r.restart_number,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TelegramQueueResponse {
+    pub depth: usize,
+    pub pending: Vec<PendingMessageResponse>,
+}
+
 // ============================================================================
 // Query params
 // ============================================================================
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct LogsQuery {
     #[serde(default = "default_limit")]
     pub limit: usize,
+    /// Name from `log_categories`
+    pub category: Option<String>,
+    /// Page forward: entries with a larger id, oldest first
+    pub after_id: Option<u64>,
+    /// Page backward: entries with a smaller id, newest first
+    pub before_id: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LogPageResponse {
+    pub entries: Vec<LogResponse>,
+    /// Pass as the same `after_id` / `before_id` parameter for the next page
+    pub next_cursor: u64,
+    pub has_more: bool,
 }
 
 fn default_limit() -> usize {
     100
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StatsHistoryQuery {
+    #[serde(default = "default_stats_history_minutes")]
+    pub minutes: u64,
+}
+
+fn default_stats_history_minutes() -> u64 {
+    60
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ClearLogsQuery {
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ClearLogsResponse {
+    pub cleared_at: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BackupsQuery {
+    #[serde(default = "default_page")]
+    pub page: usize,
+    #[serde(default = "default_page_size")]
+    pub page_size: usize,
+    /// Only backups carrying this tag
+    pub tag: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct BackupTagRequest {
+    pub tag: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BackupTagsResponse {
+    pub filename: String,
+    pub tags: Vec<String>,
+}
+
+fn default_page() -> usize {
+    1
+}
+
+fn default_page_size() -> usize {
+    20
+}
+
+const MAX_BACKUP_PAGE_SIZE: usize = 500;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LogExportQuery {
+    #[serde(default)]
+    pub format: LogExportFormat,
+    /// RFC 3339, or local time as `2024-01-15T10:00:00`
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub level: Option<LogLevel>,
+    pub source: Option<LogSource>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ReportQuery {
+    #[serde(default)]
+    pub format: ReportFormat,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BackupSearchQuery {
+    pub q: String,
+}
+
+const MAX_BACKUP_SEARCH_RESULTS: usize = 50;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BackupDiffQuery {
+    pub a: String,
+    pub b: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RestoreQuery {
+    /// Only list the files that would be overwritten
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct MaintenanceRequest {
+    pub enabled: bool,
+    /// Omit to stay in maintenance until disabled
+    #[serde(default)]
+    pub duration_secs: Option<u64>,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CheckpointRequest {
+    pub flag_file: String,
+    pub ack_file: String,
+    #[serde(default = "default_checkpoint_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_checkpoint_timeout() -> u64 {
+    30
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SendCommandRequest {
+    pub command: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct PatternTestRequest {
+    pub pattern: String,
+    #[serde(default)]
+    pub is_regex: bool,
+    #[serde(default = "default_pattern_test_lines")]
+    pub test_against_last_n_lines: usize,
+}
+
+fn default_pattern_test_lines() -> usize {
+    500
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct LineLevelRequest {
+    pub line: String,
+    /// Defaults to the configured `error_patterns`
+    #[serde(default)]
+    pub patterns: Option<ErrorPatterns>,
+    /// Stream the line came from, picks the source-specific lists
+    #[serde(default = "default_line_source")]
+    pub source: LogSource,
+}
+
+fn default_line_source() -> LogSource {
+    LogSource::Server
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LineLevelResponse {
+    pub level: LogLevel,
+    pub fields: HashMap<String, String>,
+    /// The line matches a `suppress` pattern and would not restart the server
+    pub suppressed: bool,
+}
+
+/// Signal given as a number or a name like "SIGUSR1"
+#[derive(Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum SignalSpec {
+    Number(i32),
+    Name(String),
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SignalRequest {
+    pub signal: SignalSpec,
+}
+
+/// Partial identity update, absent fields are left unchanged
+#[derive(Deserialize, ToSchema)]
+pub struct IdentityPatch {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub icon: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TimelineQuery {
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+    pub from: Option<DateTime<Local>>,
+    pub to: Option<DateTime<Local>>,
+    /// `next_cursor` from the previous page
+    pub cursor: Option<DateTime<Local>>,
+}
+
 // ============================================================================
 // Handlers
 // ============================================================================
 
 /// GET /api/status
-pub async fn get_status(State(state): State<ApiState>) -> Json<StatusResponse> {
+#[utoipa::path(
+    get,
+    path = "/api/status",
+    tag = "status",
+    responses(
+        (status = 200, description = "Server status", body = StatusResponse),
+        (status = 304, description = "Not modified (ETag matched)"),
+    )
+)]
+pub async fn get_status(State(state): State<ApiState>, headers: HeaderMap) -> Response {
+    // Countdowns, like uptime, move without changing the tag
+    let etag = state_etag(&state.app_state);
     let snapshot = state.app_state.snapshot();
-    Json(StatusResponse {
-        status: snapshot.status.as_str().to_string(),
-        pid: snapshot.pid,
-        uptime_secs: snapshot.uptime_secs,
-        restart_count: snapshot.restart_count,
-        auto_restart_remaining_secs: snapshot.auto_restart_remaining_secs,
-        next_backup_secs: snapshot.next_backup_secs,
-    })
+    with_etag(
+        &headers,
+        etag,
+        StatusResponse {
+            status: snapshot.status.as_str().to_string(),
+            pid: snapshot.pid,
+            uptime_secs: snapshot.uptime_secs,
+            restart_count: snapshot.restart_count,
+            auto_restart_remaining_secs: snapshot.auto_restart_remaining_secs,
+            next_backup_secs: snapshot.next_backup_secs,
+            startup_latency_ms: snapshot.startup_latency_ms,
+            avg_startup_latency_ms: snapshot.avg_startup_latency_ms,
+            restart_delay_secs: snapshot.restart_delay_secs,
+            max_logs: snapshot.max_logs,
+            current_log_count: snapshot.current_log_count,
+            pending_restart: snapshot.pending_restart,
+        },
+    )
 }
 
 /// GET /api/stats
-pub async fn get_stats(State(state): State<ApiState>) -> Json<StatsResponse> {
-    let stats = state.app_state.stats();
-    Json(StatsResponse {
-        cpu_percent: stats.cpu_percent,
-        memory_mb: stats.memory_mb,
-        memory_percent: stats.memory_percent,
-        network_rx_speed: stats.network_rx_speed,
-        network_tx_speed: stats.network_tx_speed,
-        disk_read_speed: stats.disk_read_speed,
-        disk_write_speed: stats.disk_write_speed,
+#[utoipa::path(
+    get,
+    path = "/api/stats",
+    tag = "status",
+    responses(
+        (status = 200, description = "Resource usage", body = StatsResponse),
+        (status = 304, description = "Not modified (ETag matched)"),
+    )
+)]
+pub async fn get_stats(State(state): State<ApiState>, headers: HeaderMap) -> Response {
+    let etag = format!("W/\"{}\"", state.app_state.tick_count());
+    with_etag(&headers, etag, StatsResponse::from_state(&state.app_state))
+}
+
+/// GET /api/stats/history?minutes= - Samples for graphs, oldest first
+#[utoipa::path(
+    get,
+    path = "/api/stats/history",
+    tag = "status",
+    params(
+        StatsHistoryQuery,
+    ),
+    responses(
+        (status = 200, description = "Samples, oldest first", body = Vec<StatsHistoryEntry>),
+    )
+)]
+pub async fn get_stats_history(
+    State(state): State<ApiState>,
+    axum::extract::Query(query): axum::extract::Query<StatsHistoryQuery>,
+) -> Json<Vec<StatsHistoryEntry>> {
+    let history = state.app_state.stats_history(query.minutes);
+    Json(
+        history
+            .into_iter()
+            .map(|(timestamp, stats)| StatsHistoryEntry {
+                timestamp: timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+                stats,
+            })
+            .collect(),
+    )
+}
+
+/// GET /api/stats/alerts - Resources currently above their thresholds
+#[utoipa::path(
+    get,
+    path = "/api/stats/alerts",
+    tag = "status",
+    responses(
+        (status = 200, description = "Active resource alerts", body = Vec<ResourceAlertState>),
+    )
+)]
+pub async fn get_active_alerts(State(state): State<ApiState>) -> Json<Vec<ResourceAlertState>> {
+    Json(state.app_state.active_alerts())
+}
+
+/// GET /api/system
+#[utoipa::path(
+    get,
+    path = "/api/system",
+    tag = "status",
+    responses(
+        (status = 200, description = "Watcher, OS and Java versions", body = SystemResponse),
+    )
+)]
+pub async fn get_system(State(state): State<ApiState>) -> Json<SystemResponse> {
+    Json(SystemResponse {
+        watcher_version: env!("CARGO_PKG_VERSION"),
+        os: format!(
+            "{} {}",
+            sysinfo::System::name().unwrap_or_default(),
+            sysinfo::System::os_version().unwrap_or_default()
+        ),
+        arch: std::env::consts::ARCH,
+        java_version: state.app_state.java_version(),
+    })
+}
+
+/// GET /api/uptime - Server availability since the watcher started
+#[utoipa::path(
+    get,
+    path = "/api/uptime",
+    tag = "status",
+    responses(
+        (status = 200, description = "Availability since the watcher started", body = UptimeResponse),
+    )
+)]
+pub async fn get_uptime(State(state): State<ApiState>) -> Json<UptimeResponse> {
+    Json(UptimeResponse {
+        watcher_uptime_secs: state.app_state.watcher_uptime_secs(),
+        total_running_secs: state.app_state.total_running_secs(),
+        total_downtime_secs: state.app_state.total_downtime_secs(),
+        availability_percent: state.app_state.availability_percent(),
     })
 }
 
-/// GET /api/logs
+/// GET /api/history - Restarts, newest first
+#[utoipa::path(
+    get,
+    path = "/api/history",
+    tag = "status",
+    responses(
+        (status = 200, description = "Restarts, newest first", body = Vec<RestartHistoryResponse>),
+    )
+)]
+pub async fn get_restart_history(State(state): State<ApiState>) -> Json<Vec<RestartHistoryResponse>> {
+    let history = state.app_state.restart_history(usize::MAX);
+    Json(history.into_iter().map(Into::into).collect())
+}
+
+/// GET /api/analytics/crash-correlation - Crashes binned by hour of day
+#[utoipa::path(
+    get,
+    path = "/api/analytics/crash-correlation",
+    tag = "status",
+    responses(
+        (status = 200, description = "Crashes by hour of day", body = Vec<CrashCorrelation>),
+    )
+)]
+pub async fn get_crash_correlation(
+    State(state): State<ApiState>,
+) -> Json<Vec<CrashCorrelation>> {
+    let history = state.app_state.restart_history(usize::MAX);
+    Json(crash_correlation(&history))
+}
+
+/// GET /api/logs - Latest entries, or a cursor page with `after_id` /
+/// `before_id`
+#[utoipa::path(
+    get,
+    path = "/api/logs",
+    tag = "logs",
+    params(
+        LogsQuery,
+    ),
+    responses(
+        (status = 200, description = "Log lines, newest first, or a cursor page when `after_id` or `before_id` is set", body = Vec<LogResponse>),
+        (status = 400, description = "Invalid query", body = String),
+        (status = 404, description = "Unknown category", body = String),
+    )
+)]
 pub async fn get_logs(
     State(state): State<ApiState>,
     axum::extract::Query(query): axum::extract::Query<LogsQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    let page = match (query.after_id, query.before_id) {
+        (Some(_), Some(_)) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "after_id and before_id are mutually exclusive".to_string(),
+            ))
+        }
+        (Some(after_id), None) => {
+            Some((after_id, state.app_state.logs_after(after_id, query.limit)))
+        }
+        (None, Some(before_id)) => Some((
+            before_id,
+            state.app_state.logs_before(before_id, query.limit),
+        )),
+        (None, None) => None,
+    };
+    if let Some((cursor, (entries, has_more))) = page {
+        if query.category.is_some() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "category can't be combined with a cursor".to_string(),
+            ));
+        }
+        return Ok(Json(LogPageResponse {
+            next_cursor: entries.last().map_or(cursor, |e| e.id),
+            entries: entries.into_iter().map(Into::into).collect(),
+            has_more,
+        })
+        .into_response());
+    }
+
+    let Some(ref name) = query.category else {
+        let logs = state.app_state.logs(query.limit);
+        let response: Vec<LogResponse> = logs.into_iter().map(Into::into).collect();
+        return Ok(Json(response).into_response());
+    };
+
+    let category = state
+        .config
+        .read()
+        .log_categories
+        .iter()
+        .find(|c| &c.name == name)
+        .cloned()
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                format!("Unknown log category: {}", name),
+            )
+        })?;
+    let regex = regex::Regex::new(&category.pattern)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let response: Vec<LogResponse> = state
+        .app_state
+        .logs(usize::MAX)
+        .into_iter()
+        .filter(|log| is_server_output(log) && regex.is_match(&log.message))
+        .take(query.limit)
+        .map(Into::into)
+        .collect();
+    Ok(Json(response).into_response())
+}
+
+/// DELETE /api/logs?confirm=true - Empty the in-memory log buffer
+#[utoipa::path(
+    delete,
+    path = "/api/logs",
+    tag = "logs",
+    params(
+        ClearLogsQuery,
+    ),
+    responses(
+        (status = 200, description = "Buffer cleared", body = ClearLogsResponse),
+        (status = 400, description = "`confirm=true` missing", body = String),
+        (status = 401, description = "Missing or wrong token", body = String),
+    ),
+    security(("bearerAuth" = []))
+)]
+pub async fn clear_logs(
+    State(state): State<ApiState>,
+    axum::extract::Query(query): axum::extract::Query<ClearLogsQuery>,
+) -> Result<Json<ClearLogsResponse>, (StatusCode, String)> {
+    if !query.confirm {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Pass confirm=true to clear the logs".to_string(),
+        ));
+    }
+
+    let cleared_at = state.app_state.clear_logs();
+    Ok(Json(ClearLogsResponse {
+        cleared_at: cleared_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+    }))
+}
+
+/// Per-remote limit for proxied log requests
+const REMOTE_LOGS_TIMEOUT: Duration = Duration::from_secs(5);
+/// `server_id` given to this watcher's own lines in merged logs
+const LOCAL_SERVER_ID: &str = "local";
+
+/// Fetch `/api/logs` from a remote watcher, tagging each line with its id
+async fn fetch_remote_logs(
+    client: &reqwest::Client,
+    remote: &RemoteServer,
+    limit: usize,
+) -> Result<Vec<LogResponse>, reqwest::Error> {
+    let url = format!(
+        "http://{}:{}/api/logs?limit={}",
+        remote.host, remote.port, limit
+    );
+    let mut request = client.get(url).timeout(REMOTE_LOGS_TIMEOUT);
+    if let Some(ref token) = remote.token {
+        request = request.bearer_auth(token);
+    }
+    let mut logs: Vec<LogResponse> = request.send().await?.error_for_status()?.json().await?;
+    for log in &mut logs {
+        log.server_id = Some(remote.id.clone());
+    }
+    Ok(logs)
+}
+
+/// GET /api/remote/:id/logs - Logs of one remote watcher
+#[utoipa::path(
+    get,
+    path = "/api/remote/{id}/logs",
+    tag = "logs",
+    params(
+        ("id" = String, Path, description = "Remote watcher id"),
+        LogsQuery,
+    ),
+    responses(
+        (status = 200, description = "Logs of the remote watcher", body = Vec<LogResponse>),
+        (status = 404, description = "Unknown remote", body = String),
+        (status = 502, description = "Remote watcher unreachable", body = String),
+    )
+)]
+pub async fn get_remote_logs(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+    axum::extract::Query(query): axum::extract::Query<LogsQuery>,
+) -> Result<Json<Vec<LogResponse>>, (StatusCode, String)> {
+    let remote = state
+        .config
+        .read()
+        .remote_servers
+        .iter()
+        .find(|r| r.id == id)
+        .cloned()
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                format!("Unknown remote server: {}", id),
+            )
+        })?;
+
+    fetch_remote_logs(&reqwest::Client::new(), &remote, query.limit)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("{}: {}", remote.id, e)))
+}
+
+/// GET /api/logs/all - Local and remote logs merged, newest first.
+/// Remotes that fail or time out are left out.
+#[utoipa::path(
+    get,
+    path = "/api/logs/all",
+    tag = "logs",
+    params(
+        LogsQuery,
+    ),
+    responses(
+        (status = 200, description = "Local and remote logs, newest first", body = Vec<LogResponse>),
+    )
+)]
+pub async fn get_all_logs(
+    State(state): State<ApiState>,
+    axum::extract::Query(query): axum::extract::Query<LogsQuery>,
 ) -> Json<Vec<LogResponse>> {
-    let logs = state.app_state.logs(query.limit);
-    let response: Vec<LogResponse> = logs
+    let remotes = state.config.read().remote_servers.clone();
+    let client = reqwest::Client::new();
+
+    let mut logs: Vec<LogResponse> = state
+        .app_state
+        .logs(query.limit)
         .into_iter()
         .map(|log| LogResponse {
-            timestamp: log.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
-            level: format!("{:?}", log.level).to_lowercase(),
-            source: format!("{:?}", log.source).to_lowercase(),
-            message: log.message,
+            server_id: Some(LOCAL_SERVER_ID.to_string()),
+            ..log.into()
         })
         .collect();
-    Json(response)
+
+    let results = join_all(
+        remotes
+            .iter()
+            .map(|remote| fetch_remote_logs(&client, remote, query.limit)),
+    )
+    .await;
+    for (remote, result) in remotes.iter().zip(results) {
+        match result {
+            Ok(remote_logs) => logs.extend(remote_logs),
+            Err(e) => tracing::warn!("Failed to fetch logs from remote {}: {}", remote.id, e),
+        }
+    }
+
+    // Timestamps are zero-padded, so they sort as strings
+    logs.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+    let mut seen = HashSet::new();
+    logs.retain(|log| seen.insert((log.timestamp.clone(), log.message.clone())));
+    logs.truncate(query.limit);
+    Json(logs)
 }
 
-/// GET /api/backups
-pub async fn get_backups(State(state): State<ApiState>) -> Json<Vec<BackupResponse>> {
-    let backups = list_backups(&state.backup_path).unwrap_or_default();
-    let response: Vec<BackupResponse> = backups
+/// Categories describe server output, not the watcher's own messages
+fn is_server_output(log: &LogEntry) -> bool {
+    !matches!(log.source, LogSource::Watcher)
+}
+
+/// GET /api/logs/categories - Match counts over the current log buffer
+#[utoipa::path(
+    get,
+    path = "/api/logs/categories",
+    tag = "logs",
+    responses(
+        (status = 200, description = "Categories with match counts", body = Vec<LogCategoryResponse>),
+        (status = 500, description = "Invalid category pattern", body = String),
+    )
+)]
+pub async fn get_log_categories(
+    State(state): State<ApiState>,
+) -> Result<Json<Vec<LogCategoryResponse>>, (StatusCode, String)> {
+    let categories = state.config.read().log_categories.clone();
+    let logs = state.app_state.logs(usize::MAX);
+
+    categories
         .into_iter()
-        .map(|b| BackupResponse {
-            filename: b.filename,
-            size: format_bytes(b.size_bytes),
-            size_bytes: b.size_bytes,
-            created_at: b.created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+        .map(|category| {
+            let regex = regex::Regex::new(&category.pattern)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            let count = logs
+                .iter()
+                .filter(|l| is_server_output(l) && regex.is_match(&l.message))
+                .count();
+            Ok(LogCategoryResponse {
+                name: category.name,
+                pattern: category.pattern,
+                color: category.color,
+                count,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Json)
+}
+
+fn parse_export_time(value: &str) -> Result<DateTime<Local>, (StatusCode, String)> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Local))
+        .ok()
+        .or_else(|| {
+            chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")
+                .ok()?
+                .and_local_timezone(Local)
+                .earliest()
         })
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, format!("Invalid time: {}", value)))
+}
+
+/// GET /api/logs/export - Download logs as JSON Lines, CSV or text. Reads
+/// the persisted log files when `log_file` is set, the buffer otherwise.
+#[utoipa::path(
+    get,
+    path = "/api/logs/export",
+    tag = "logs",
+    params(
+        LogExportQuery,
+    ),
+    responses(
+        (status = 200, description = "Log file attachment (JSON Lines, CSV or text)", body = String),
+        (status = 400, description = "Invalid query", body = String),
+        (status = 500, description = "Reading the log files failed", body = String),
+    )
+)]
+pub async fn export_logs(
+    State(state): State<ApiState>,
+    axum::extract::Query(query): axum::extract::Query<LogExportQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    let from = query.from.as_deref().map(parse_export_time).transpose()?;
+    let to = query.to.as_deref().map(parse_export_time).transpose()?;
+
+    let log_file = state.config.read().log_file.clone();
+    let entries = match log_file {
+        Some(log_file) => tokio::task::spawn_blocking(move || read_log_files(&log_file))
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?,
+        None => {
+            let mut logs = state.app_state.logs(usize::MAX);
+            logs.reverse();
+            logs
+        }
+    };
+
+    let entries: Vec<LogEntry> = entries
+        .into_iter()
+        .filter(|e| from.is_none_or(|from| e.timestamp >= from))
+        .filter(|e| to.is_none_or(|to| e.timestamp <= to))
+        .filter(|e| query.level.is_none_or(|level| e.level == level))
+        .filter(|e| query.source.is_none_or(|source| e.source == source))
         .collect();
-    Json(response)
+
+    let body = render_logs(&entries, query.format);
+    let filename = format!(
+        "logs-{}.{}",
+        Local::now().format("%Y%m%d-%H%M%S"),
+        query.format.extension()
+    );
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                query.format.content_type().to_string(),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        body,
+    )
+        .into_response())
 }
 
-/// GET /api/state - Full state in one request
-pub async fn get_full_state(
+/// GET /api/logs/export/server-report - Diagnostic report for support tickets
+#[utoipa::path(
+    get,
+    path = "/api/logs/export/server-report",
+    tag = "logs",
+    params(
+        ReportQuery,
+    ),
+    responses(
+        (status = 200, description = "Report as text or Markdown", body = String),
+    )
+)]
+pub async fn export_server_report(
+    State(state): State<ApiState>,
+    axum::extract::Query(query): axum::extract::Query<ReportQuery>,
+) -> impl IntoResponse {
+    let report = generate_report(&state.app_state, &state.config.read(), query.format);
+    (
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        report,
+    )
+}
+
+/// GET /api/backups
+#[utoipa::path(
+    get,
+    path = "/api/backups",
+    tag = "backups",
+    params(
+        BackupsQuery,
+    ),
+    responses(
+        (status = 200, description = "One page of backups, newest first; totals in `X-Total-Count` and `X-Page-Count`", body = Vec<BackupResponse>),
+    )
+)]
+pub async fn get_backups(
     State(state): State<ApiState>,
-) -> Json<FullStateResponse> {
+    axum::extract::Query(query): axum::extract::Query<BackupsQuery>,
+) -> Response {
+    let page = query.page.max(1);
+    let page_size = query.page_size.clamp(1, MAX_BACKUP_PAGE_SIZE);
+
+    let (backups, total) =
+        list_backups_paginated(&state.backup_path, page, page_size, query.tag.as_deref())
+            .unwrap_or_default();
+    let response: Vec<BackupResponse> = backups.into_iter().map(Into::into).collect();
+
+    (
+        [
+            ("X-Total-Count", total.to_string()),
+            ("X-Page-Count", total.div_ceil(page_size).to_string()),
+        ],
+        Json(response),
+    )
+        .into_response()
+}
+
+/// GET /api/state - Full state in one request
+#[utoipa::path(
+    get,
+    path = "/api/state",
+    tag = "status",
+    responses(
+        (status = 200, description = "Status, stats, logs and backups", body = FullStateResponse),
+        (status = 304, description = "Not modified (ETag matched)"),
+    )
+)]
+pub async fn get_full_state(State(state): State<ApiState>, headers: HeaderMap) -> Response {
+    // Includes the stats, so resource samples change the tag too
+    let etag = format!(
+        "W/\"{}-{}\"",
+        state.app_state.change_count(),
+        state.app_state.tick_count()
+    );
     let snapshot = state.app_state.snapshot();
-    let stats = state.app_state.stats();
     let logs = state.app_state.logs(100);
     let backups = list_backups(&state.backup_path).unwrap_or_default();
 
-    Json(FullStateResponse {
+    let response = FullStateResponse {
         status: StatusResponse {
             status: snapshot.status.as_str().to_string(),
             pid: snapshot.pid,
@@ -173,41 +1249,95 @@ pub async fn get_full_state(
             restart_count: snapshot.restart_count,
             auto_restart_remaining_secs: snapshot.auto_restart_remaining_secs,
             next_backup_secs: snapshot.next_backup_secs,
+            startup_latency_ms: snapshot.startup_latency_ms,
+            avg_startup_latency_ms: snapshot.avg_startup_latency_ms,
+            restart_delay_secs: snapshot.restart_delay_secs,
+            max_logs: snapshot.max_logs,
+            current_log_count: snapshot.current_log_count,
+            pending_restart: snapshot.pending_restart,
         },
-        stats: StatsResponse {
-            cpu_percent: stats.cpu_percent,
-            memory_mb: stats.memory_mb,
-            memory_percent: stats.memory_percent,
-            network_rx_speed: stats.network_rx_speed,
-            network_tx_speed: stats.network_tx_speed,
-            disk_read_speed: stats.disk_read_speed,
-            disk_write_speed: stats.disk_write_speed,
-        },
-        logs: logs
-            .into_iter()
-            .map(|log| LogResponse {
-                timestamp: log.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
-                level: format!("{:?}", log.level).to_lowercase(),
-                source: format!("{:?}", log.source).to_lowercase(),
-                message: log.message,
-            })
-            .collect(),
-        backups: backups
-            .into_iter()
-            .map(|b| BackupResponse {
-                filename: b.filename,
-                size: format_bytes(b.size_bytes),
-                size_bytes: b.size_bytes,
-                created_at: b.created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
-            })
-            .collect(),
+        stats: StatsResponse::from_state(&state.app_state),
+        logs: logs.into_iter().map(Into::into).collect(),
+        backups: backups.into_iter().map(Into::into).collect(),
+    };
+    with_etag(&headers, etag, response)
+}
+
+/// Weak ETag from the state change counter. Read it before the state
+/// itself so a concurrent change never hides behind an old tag.
+fn state_etag(app_state: &AppState) -> String {
+    format!("W/\"{}\"", app_state.change_count())
+}
+
+/// Reply `304 Not Modified` if the client already has `etag`, otherwise
+/// the JSON body. Pollers must revalidate on every request.
+fn with_etag<T: Serialize>(headers: &HeaderMap, etag: String, body: T) -> Response {
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|candidate| candidate.trim() == etag));
+
+    let mut response = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        Json(body).into_response()
+    };
+
+    let response_headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response_headers.insert(header::ETAG, value);
+    }
+    response_headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    response
+}
+
+/// GET /api/timeline
+#[utoipa::path(
+    get,
+    path = "/api/timeline",
+    tag = "status",
+    params(
+        TimelineQuery,
+    ),
+    responses(
+        (status = 200, description = "Events, newest first", body = TimelineResponse),
+    )
+)]
+pub async fn get_timeline(
+    State(state): State<ApiState>,
+    axum::extract::Query(query): axum::extract::Query<TimelineQuery>,
+) -> Json<TimelineResponse> {
+    let to = match (query.to, query.cursor) {
+        (Some(to), Some(cursor)) => Some(to.min(cursor)),
+        (to, cursor) => to.or(cursor),
+    };
+    let events = state.app_state.timeline(query.limit, query.from, to);
+    let next_cursor = if query.limit > 0 && events.len() == query.limit {
+        events.last().map(|e| e.timestamp.to_rfc3339())
+    } else {
+        None
+    };
+
+    Json(TimelineResponse {
+        events,
+        next_cursor,
     })
 }
 
 /// POST /api/restart
+#[utoipa::path(
+    post,
+    path = "/api/restart",
+    tag = "control",
+    responses(
+        (status = 200, description = "Restart requested", body = SuccessResponse),
+    )
+)]
 pub async fn restart_server(
     State(state): State<ApiState>,
+    Extension(request_id): Extension<RequestId>,
 ) -> Result<Json<SuccessResponse>, StatusCode> {
+    tracing::info!(request_id = %request_id.0, "Restart requested via API");
     state
         .process_tx
         .send(ProcessCommand::Restart)
@@ -220,10 +1350,92 @@ pub async fn restart_server(
     }))
 }
 
+/// POST /api/restart/cancel - Abort a delayed restart, leaving the server
+/// stopped, or skip the scheduled auto-restart of the current run
+#[utoipa::path(
+    post,
+    path = "/api/restart/cancel",
+    tag = "control",
+    responses(
+        (status = 200, description = "Restart cancelled", body = SuccessResponse),
+        (status = 401, description = "Missing or wrong token", body = String),
+        (status = 409, description = "No restart to cancel", body = String),
+    ),
+    security(("bearerAuth" = []))
+)]
+pub async fn cancel_restart(
+    State(state): State<ApiState>,
+    Extension(request_id): Extension<RequestId>,
+) -> Result<Json<SuccessResponse>, (StatusCode, String)> {
+    let scheduled = state
+        .app_state
+        .snapshot()
+        .auto_restart_remaining_secs
+        .is_some();
+    if !state.app_state.is_restart_pending() && !scheduled {
+        return Err((StatusCode::CONFLICT, "No restart pending".to_string()));
+    }
+
+    tracing::info!(request_id = %request_id.0, "Restart cancel requested via API");
+    state
+        .process_tx
+        .send(ProcessCommand::CancelRestart)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        message: Some("Restart cancel command sent".to_string()),
+    }))
+}
+
+/// POST /api/start - Start a stopped server
+#[utoipa::path(
+    post,
+    path = "/api/start",
+    tag = "control",
+    responses(
+        (status = 200, description = "Start requested", body = SuccessResponse),
+        (status = 401, description = "Missing or wrong token", body = String),
+        (status = 409, description = "Server is not stopped", body = String),
+    ),
+    security(("bearerAuth" = []))
+)]
+pub async fn start_server(
+    State(state): State<ApiState>,
+    Extension(request_id): Extension<RequestId>,
+) -> Result<Json<SuccessResponse>, (StatusCode, String)> {
+    if state.app_state.status() != ServerStatus::Stopped {
+        return Err((StatusCode::CONFLICT, "Server is not stopped".to_string()));
+    }
+
+    tracing::info!(request_id = %request_id.0, "Start requested via API");
+    state
+        .process_tx
+        .send(ProcessCommand::Start)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        message: Some("Start command sent".to_string()),
+    }))
+}
+
 /// POST /api/stop
+#[utoipa::path(
+    post,
+    path = "/api/stop",
+    tag = "control",
+    responses(
+        (status = 200, description = "Stop requested", body = SuccessResponse),
+    )
+)]
 pub async fn stop_server(
     State(state): State<ApiState>,
+    Extension(request_id): Extension<RequestId>,
 ) -> Result<Json<SuccessResponse>, StatusCode> {
+    tracing::info!(request_id = %request_id.0, "Stop requested via API");
     state
         .process_tx
         .send(ProcessCommand::Stop)
@@ -236,13 +1448,530 @@ pub async fn stop_server(
     }))
 }
 
+/// POST /api/patterns/test - Level a server output line would be logged at
+#[utoipa::path(
+    post,
+    path = "/api/patterns/test",
+    tag = "logs",
+    request_body = LineLevelRequest,
+    responses(
+        (status = 200, description = "Level, extracted fields and suppression", body = LineLevelResponse),
+        (status = 400, description = "Invalid pattern", body = String),
+    )
+)]
+pub async fn test_line_level(
+    State(state): State<ApiState>,
+    Json(req): Json<LineLevelRequest>,
+) -> Result<Json<LineLevelResponse>, (StatusCode, String)> {
+    let patterns = req
+        .patterns
+        .unwrap_or_else(|| state.config.read().error_patterns.clone());
+    for entry in patterns.all().filter(|e| e.is_regex) {
+        regex::Regex::new(&entry.pattern).map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid regex {:?}: {}", entry.pattern, e),
+            )
+        })?;
+    }
+
+    let compiled = CompiledPatterns::new(&patterns);
+    Ok(Json(LineLevelResponse {
+        level: compiled.detect_level(&req.line, req.source),
+        fields: compiled.extract_fields(&req.line, req.source),
+        suppressed: compiled.is_suppressed(&req.line),
+    }))
+}
+
+/// POST /api/error-patterns/test - Try a pattern against recent logs
+#[utoipa::path(
+    post,
+    path = "/api/error-patterns/test",
+    tag = "logs",
+    request_body = PatternTestRequest,
+    responses(
+        (status = 200, description = "Matching lines", body = PatternTestResponse),
+        (status = 400, description = "Invalid pattern", body = String),
+    )
+)]
+pub async fn test_error_pattern(
+    State(state): State<ApiState>,
+    Json(req): Json<PatternTestRequest>,
+) -> Result<Json<PatternTestResponse>, (StatusCode, String)> {
+    let regex = if req.is_regex {
+        Some(
+            regex::Regex::new(&req.pattern)
+                .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?,
+        )
+    } else {
+        None
+    };
+
+    let logs = state.app_state.logs(req.test_against_last_n_lines);
+    let total_tested = logs.len() as u32;
+    let matches: Vec<LogResponse> = logs
+        .into_iter()
+        .filter(|log| match regex {
+            Some(ref re) => re.is_match(&log.message),
+            None => log.message.contains(&req.pattern),
+        })
+        .map(Into::into)
+        .collect();
+
+    Ok(Json(PatternTestResponse {
+        match_count: matches.len() as u32,
+        matches,
+        total_tested,
+    }))
+}
+
+/// GET /api/crash-report - Output captured before the last server exit
+#[utoipa::path(
+    get,
+    path = "/api/crash-report",
+    tag = "status",
+    responses(
+        (status = 200, description = "Last crash report", body = CrashReportResponse),
+        (status = 404, description = "No crash recorded"),
+    )
+)]
+pub async fn get_crash_report(
+    State(state): State<ApiState>,
+) -> Result<Json<CrashReportResponse>, StatusCode> {
+    let report = state.app_state.last_exit().ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(CrashReportResponse {
+        exit_reason: report.exit_reason,
+        exit_code: report.exit_code,
+        last_lines: report.last_lines,
+        timestamp: report.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+    }))
+}
+
+/// GET /api/process/open-files
+#[cfg(target_os = "linux")]
+#[utoipa::path(
+    get,
+    path = "/api/process/open-files",
+    tag = "control",
+    responses(
+        (status = 200, description = "Files held open by the server process", body = OpenFilesResponse),
+        (status = 401, description = "Missing or wrong token", body = String),
+        (status = 409, description = "Server is not running", body = String),
+    ),
+    security(("bearerAuth" = []))
+)]
+pub async fn get_open_files(
+    State(state): State<ApiState>,
+) -> Result<Json<OpenFilesResponse>, (StatusCode, String)> {
+    use crate::watcher::process::list_open_files;
+
+    let pid = state
+        .app_state
+        .pid()
+        .ok_or((StatusCode::CONFLICT, "Server is not running".to_string()))?;
+
+    let (open_files, truncated) = tokio::task::spawn_blocking(move || list_open_files(pid, 1000))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(OpenFilesResponse {
+        pid,
+        open_files,
+        truncated,
+    }))
+}
+
+/// GET /api/process/open-files
+#[cfg(not(target_os = "linux"))]
+#[utoipa::path(
+    get,
+    path = "/api/process/open-files",
+    tag = "control",
+    responses(
+        (status = 200, description = "Files held open by the server process", body = OpenFilesResponse),
+        (status = 401, description = "Missing or wrong token", body = String),
+        (status = 501, description = "Not supported on this platform", body = String),
+    ),
+    security(("bearerAuth" = []))
+)]
+pub async fn get_open_files() -> Result<Json<OpenFilesResponse>, (StatusCode, String)> {
+    Err((
+        StatusCode::NOT_IMPLEMENTED,
+        "Open file listing is only supported on Linux".to_string(),
+    ))
+}
+
+/// POST /api/signal
+#[utoipa::path(
+    post,
+    path = "/api/signal",
+    tag = "control",
+    request_body = SignalRequest,
+    responses(
+        (status = 200, description = "Signal sent", body = SuccessResponse),
+        (status = 400, description = "Unknown signal", body = String),
+        (status = 401, description = "Missing or wrong token", body = String),
+        (status = 403, description = "Signal not allowed", body = String),
+    ),
+    security(("bearerAuth" = []))
+)]
+pub async fn send_signal(
+    State(state): State<ApiState>,
+    Json(req): Json<SignalRequest>,
+) -> Result<Json<SuccessResponse>, (StatusCode, String)> {
+    let signum = match req.signal {
+        SignalSpec::Number(n) => n,
+        SignalSpec::Name(name) => parse_signal_name(&name)
+            .ok_or_else(|| (StatusCode::BAD_REQUEST, format!("Unknown signal: {}", name)))?,
+    };
+
+    if !state.config.read().server.allowed_signals.contains(&signum) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            format!("Signal {} is not in allowed_signals", signum),
+        ));
+    }
+
+    state
+        .process_tx
+        .send(ProcessCommand::Signal(signum))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        message: Some(format!("Signal {} sent", signum)),
+    }))
+}
+
+/// GET /api/maintenance
+#[utoipa::path(
+    get,
+    path = "/api/maintenance",
+    tag = "control",
+    responses(
+        (status = 200, description = "Maintenance mode and windows", body = MaintenanceResponse),
+    )
+)]
+pub async fn get_maintenance(State(state): State<ApiState>) -> Json<MaintenanceResponse> {
+    Json(MaintenanceResponse::from_info(
+        state.app_state.maintenance(),
+        &state.config.read().server,
+    ))
+}
+
+/// POST /api/maintenance - Suppress restarts and non-critical notifications
+#[utoipa::path(
+    post,
+    path = "/api/maintenance",
+    tag = "control",
+    request_body = MaintenanceRequest,
+    responses(
+        (status = 200, description = "New maintenance state", body = MaintenanceResponse),
+        (status = 401, description = "Missing or wrong token", body = String),
+    ),
+    security(("bearerAuth" = []))
+)]
+pub async fn set_maintenance(
+    State(state): State<ApiState>,
+    Json(req): Json<MaintenanceRequest>,
+) -> Result<Json<MaintenanceResponse>, (StatusCode, String)> {
+    if !req.enabled {
+        state.app_state.set_maintenance(None);
+        state
+            .app_state
+            .add_watcher_log("Maintenance mode disabled".to_string());
+        return Ok(Json(MaintenanceResponse::from_info(
+            None,
+            &state.config.read().server,
+        )));
+    }
+
+    let started_at = Local::now();
+    let until = match req.duration_secs {
+        Some(secs) => Some(
+            i64::try_from(secs)
+                .ok()
+                .and_then(chrono::Duration::try_seconds)
+                .and_then(|duration| started_at.checked_add_signed(duration))
+                .ok_or((
+                    StatusCode::BAD_REQUEST,
+                    "duration_secs is too large".to_string(),
+                ))?,
+        ),
+        None => None,
+    };
+    let info = MaintenanceInfo {
+        reason: req.reason,
+        started_at,
+        until,
+    };
+    state.app_state.set_maintenance(Some(info.clone()));
+    state.app_state.add_watcher_log(format!(
+        "Maintenance mode enabled{}{}",
+        req.duration_secs
+            .map(|secs| format!(" for {}s", secs))
+            .unwrap_or_default(),
+        info.reason
+            .as_ref()
+            .map(|r| format!(": {}", r))
+            .unwrap_or_default()
+    ));
+
+    if let Some(secs) = req.duration_secs {
+        let app_state = Arc::clone(&state.app_state);
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_secs(secs)).await;
+            if app_state.end_maintenance_started_at(started_at) {
+                app_state.add_watcher_log("Maintenance mode expired".to_string());
+            }
+        });
+    }
+
+    Ok(Json(MaintenanceResponse::from_info(
+        Some(info),
+        &state.config.read().server,
+    )))
+}
+
+/// POST /api/checkpoint - Flag/ack handshake with the server
+#[utoipa::path(
+    post,
+    path = "/api/checkpoint",
+    tag = "control",
+    request_body = CheckpointRequest,
+    responses(
+        (status = 200, description = "Server acknowledged", body = CheckpointResponse),
+        (status = 401, description = "Missing or wrong token", body = String),
+        (status = 504, description = "No acknowledgement in time", body = String),
+    ),
+    security(("bearerAuth" = []))
+)]
+pub async fn checkpoint(
+    State(state): State<ApiState>,
+    Json(req): Json<CheckpointRequest>,
+) -> Result<Json<CheckpointResponse>, (StatusCode, String)> {
+    for name in [&req.flag_file, &req.ack_file] {
+        if !is_plain_file_name(name) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Invalid file name: {}", name),
+            ));
+        }
+    }
+
+    if state.app_state.status() != ServerStatus::Running {
+        return Err((StatusCode::CONFLICT, "Server is not running".to_string()));
+    }
+
+    let (reply, reply_rx) = tokio::sync::oneshot::channel();
+    state
+        .process_tx
+        .send(ProcessCommand::Checkpoint {
+            flag_file: req.flag_file,
+            ack_file: req.ack_file,
+            timeout_secs: req.timeout_secs,
+            reply,
+        })
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // The process manager enforces timeout_secs; this only guards against
+    // the reply never arriving (e.g. the server exits mid-checkpoint)
+    let wait = std::time::Duration::from_secs(req.timeout_secs.saturating_add(5));
+    let took = tokio::time::timeout(wait, reply_rx)
+        .await
+        .map_err(|_| {
+            (
+                StatusCode::GATEWAY_TIMEOUT,
+                "Checkpoint did not complete".to_string(),
+            )
+        })?
+        .map_err(|_| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Checkpoint was dropped".to_string(),
+            )
+        })?
+        .map_err(|e| (StatusCode::GATEWAY_TIMEOUT, e))?;
+
+    Ok(Json(CheckpointResponse {
+        acknowledged_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        ack_ms: took.as_millis() as u64,
+    }))
+}
+
+/// POST /api/send-command - Write a console command to the server's stdin
+#[utoipa::path(
+    post,
+    path = "/api/send-command",
+    tag = "control",
+    request_body = SendCommandRequest,
+    responses(
+        (status = 200, description = "Command written", body = SuccessResponse),
+        (status = 400, description = "Empty command", body = String),
+        (status = 401, description = "Missing or wrong token", body = String),
+        (status = 409, description = "Server is not running", body = String),
+        (status = 504, description = "Write timed out", body = String),
+    ),
+    security(("bearerAuth" = []))
+)]
+pub async fn send_command(
+    State(state): State<ApiState>,
+    Json(req): Json<SendCommandRequest>,
+) -> Result<Json<SuccessResponse>, (StatusCode, String)> {
+    let command = req.command.trim().to_string();
+    if command.is_empty() || command.contains(['\n', '\r']) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Command must be a single non-empty line".to_string(),
+        ));
+    }
+
+    if state.app_state.status() != ServerStatus::Running {
+        return Err((StatusCode::CONFLICT, "Server is not running".to_string()));
+    }
+
+    let (reply, reply_rx) = tokio::sync::oneshot::channel();
+    state
+        .process_tx
+        .send(ProcessCommand::SendInput {
+            command: command.clone(),
+            reply,
+        })
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // The process manager enforces the stdin write timeout itself
+    let wait = Duration::from_secs(
+        state
+            .config
+            .read()
+            .server
+            .stdin_write_timeout_secs
+            .saturating_add(5),
+    );
+    let result = tokio::time::timeout(wait, reply_rx)
+        .await
+        .map_err(|_| {
+            (
+                StatusCode::GATEWAY_TIMEOUT,
+                "Command was not processed".to_string(),
+            )
+        })?
+        .map_err(|_| (StatusCode::CONFLICT, "Server is not running".to_string()))?;
+
+    match result {
+        Ok(()) => Ok(Json(SuccessResponse {
+            success: true,
+            message: Some(format!("Command sent: {}", command)),
+        })),
+        Err(e @ StdinWriteError::Timeout) => Err((StatusCode::REQUEST_TIMEOUT, e.to_string())),
+        Err(e @ StdinWriteError::NotRunning) => Err((StatusCode::CONFLICT, e.to_string())),
+        Err(e @ StdinWriteError::Io(_)) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+/// File name without any directory component
+fn is_plain_file_name(name: &str) -> bool {
+    let mut components = std::path::Path::new(name).components();
+    matches!(components.next(), Some(std::path::Component::Normal(_)))
+        && components.next().is_none()
+        && !name.contains(['/', '\\', ':'])
+}
+
+/// POST /api/backup/trigger - Create a backup now, outside the schedule
+#[utoipa::path(
+    post,
+    path = "/api/backup/trigger",
+    tag = "backups",
+    responses(
+        (status = 200, description = "Backup created", body = TriggerBackupResponse),
+        (status = 401, description = "Missing or wrong token", body = String),
+        (status = 409, description = "A backup is already running", body = String),
+        (status = 500, description = "Backup failed", body = String),
+        (status = 503, description = "Backups are disabled", body = String),
+    ),
+    security(("bearerAuth" = []))
+)]
+pub async fn trigger_backup(
+    State(state): State<ApiState>,
+) -> Result<Json<TriggerBackupResponse>, (StatusCode, String)> {
+    if !state.config.read().backup.enabled {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Backups are disabled".to_string(),
+        ));
+    }
+    if !state.app_state.try_start_backup() {
+        return Err((
+            StatusCode::CONFLICT,
+            "A backup is already in progress".to_string(),
+        ));
+    }
+
+    let (reply, reply_rx) = tokio::sync::oneshot::channel();
+    if state
+        .backup_tx
+        .send(BackupCommand::Trigger { reply })
+        .await
+        .is_err()
+    {
+        state.app_state.finish_backup();
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Backup manager is not running".to_string(),
+        ));
+    }
+
+    let backup_file = reply_rx
+        .await
+        .map_err(|_| {
+            state.app_state.finish_backup();
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Backup manager stopped".to_string(),
+            )
+        })?
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    let size_bytes = std::fs::metadata(&backup_file)
+        .map(|m| m.len())
+        .unwrap_or(0);
+    Ok(Json(TriggerBackupResponse {
+        filename: backup_file
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+        size: format_bytes(size_bytes),
+        size_bytes,
+    }))
+}
+
 /// DELETE /api/backups/:filename
+#[utoipa::path(
+    delete,
+    path = "/api/backups/{filename}",
+    tag = "backups",
+    params(
+        ("filename" = String, Path, description = "Backup file name"),
+    ),
+    responses(
+        (status = 200, description = "Backup deleted", body = SuccessResponse),
+        (status = 400, description = "Invalid file name", body = String),
+    )
+)]
 pub async fn delete_backup_handler(
     State(state): State<ApiState>,
     Path(filename): Path<String>,
 ) -> Result<Json<SuccessResponse>, (StatusCode, String)> {
     delete_backup(&state.backup_path, &filename)
         .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    state
+        .app_state
+        .set_backups(list_backups(&state.backup_path).unwrap_or_default());
 
     Ok(Json(SuccessResponse {
         success: true,
@@ -250,7 +1979,433 @@ pub async fn delete_backup_handler(
     }))
 }
 
+fn backup_tag_error(e: std::io::Error) -> (StatusCode, String) {
+    let status = match e.kind() {
+        std::io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        std::io::ErrorKind::InvalidInput => StatusCode::BAD_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, e.to_string())
+}
+
+/// POST /api/backups/:filename/tag - Label a backup and protect it from retention
+#[utoipa::path(
+    post,
+    path = "/api/backups/{filename}/tag",
+    tag = "backups",
+    params(
+        ("filename" = String, Path, description = "Backup file name"),
+    ),
+    request_body = BackupTagRequest,
+    responses(
+        (status = 200, description = "Tags of the backup", body = BackupTagsResponse),
+        (status = 400, description = "Invalid tag", body = String),
+        (status = 401, description = "Missing or wrong token", body = String),
+        (status = 404, description = "Unknown backup", body = String),
+    ),
+    security(("bearerAuth" = []))
+)]
+pub async fn add_backup_tag_handler(
+    State(state): State<ApiState>,
+    Path(filename): Path<String>,
+    Json(request): Json<BackupTagRequest>,
+) -> Result<Json<BackupTagsResponse>, (StatusCode, String)> {
+    let tags =
+        add_backup_tag(&state.backup_path, &filename, &request.tag).map_err(backup_tag_error)?;
+    state
+        .app_state
+        .set_backups(list_backups(&state.backup_path).unwrap_or_default());
+
+    Ok(Json(BackupTagsResponse { filename, tags }))
+}
+
+/// DELETE /api/backups/:filename/tag/:tag
+#[utoipa::path(
+    delete,
+    path = "/api/backups/{filename}/tag/{tag}",
+    tag = "backups",
+    params(
+        ("filename" = String, Path, description = "Backup file name"),
+        ("tag" = String, Path, description = "Tag to remove"),
+    ),
+    responses(
+        (status = 200, description = "Tags of the backup", body = BackupTagsResponse),
+        (status = 401, description = "Missing or wrong token", body = String),
+        (status = 404, description = "Unknown backup or tag", body = String),
+    ),
+    security(("bearerAuth" = []))
+)]
+pub async fn remove_backup_tag_handler(
+    State(state): State<ApiState>,
+    Path((filename, tag)): Path<(String, String)>,
+) -> Result<Json<BackupTagsResponse>, (StatusCode, String)> {
+    let tags = remove_backup_tag(&state.backup_path, &filename, &tag).map_err(backup_tag_error)?;
+    state
+        .app_state
+        .set_backups(list_backups(&state.backup_path).unwrap_or_default());
+
+    Ok(Json(BackupTagsResponse { filename, tags }))
+}
+
+/// POST /api/backups/test - Create and verify a throwaway backup
+#[utoipa::path(
+    post,
+    path = "/api/backups/test",
+    tag = "backups",
+    responses(
+        (status = 200, description = "Verification report", body = TestBackupReport),
+        (status = 401, description = "Missing or wrong token", body = String),
+        (status = 500, description = "Test backup failed", body = String),
+    ),
+    security(("bearerAuth" = []))
+)]
+pub async fn test_backup_handler(
+    State(state): State<ApiState>,
+) -> Result<Json<TestBackupReport>, (StatusCode, String)> {
+    test_backup(
+        state.source_path.clone(),
+        &state.app_state,
+        state.telegram.as_ref(),
+    )
+    .await
+    .map(Json)
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+/// POST /api/backup/restore/:filename?dry_run= - Replace the source folder
+/// with a backup. Only while the server is stopped, except for dry runs.
+#[utoipa::path(
+    post,
+    path = "/api/backup/restore/{filename}",
+    tag = "backups",
+    params(
+        ("filename" = String, Path, description = "Backup file name"),
+        RestoreQuery,
+    ),
+    responses(
+        (status = 200, description = "Restore report", body = RestoreReport),
+        (status = 400, description = "Invalid file name", body = String),
+        (status = 401, description = "Missing or wrong token", body = String),
+        (status = 404, description = "Unknown backup", body = String),
+        (status = 409, description = "Server is running or a restore is in progress", body = String),
+        (status = 500, description = "Backup failed verification or could not be unpacked", body = String),
+    ),
+    security(("bearerAuth" = []))
+)]
+pub async fn restore_backup_handler(
+    State(state): State<ApiState>,
+    Path(filename): Path<String>,
+    axum::extract::Query(query): axum::extract::Query<RestoreQuery>,
+) -> Result<Json<RestoreReport>, (StatusCode, String)> {
+    // Same security check as download_backup
+    if !is_backup_filename(&filename) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Invalid backup filename: {}", filename),
+        ));
+    }
+    if !state.backup_path.join(&filename).is_file() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Backup not found: {}", filename),
+        ));
+    }
+    if is_encrypted_backup(&filename) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Encrypted backups cannot be restored: {}", filename),
+        ));
+    }
+
+    if query.dry_run {
+        return restore_backup(&state.backup_path, &state.source_path, &filename, true)
+            .await
+            .map(Json)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e));
+    }
+
+    // Claimed before the status check so a start cannot slip in between
+    if !state.app_state.try_start_restore() {
+        return Err((StatusCode::CONFLICT, "A restore is in progress".to_string()));
+    }
+    let status = state.app_state.status();
+    if status != ServerStatus::Stopped {
+        state.app_state.finish_restore();
+        return Err((
+            StatusCode::CONFLICT,
+            format!(
+                "Stop the server before restoring (status: {})",
+                status.as_str()
+            ),
+        ));
+    }
+    // Keep scheduled backups from reading the source while it is swapped
+    if !state.app_state.try_start_backup() {
+        state.app_state.finish_restore();
+        return Err((StatusCode::CONFLICT, "A backup is in progress".to_string()));
+    }
+
+    state
+        .app_state
+        .add_watcher_log(format!("Restoring backup {}...", filename));
+    let result = restore_backup(&state.backup_path, &state.source_path, &filename, false).await;
+    state.app_state.finish_backup();
+    state.app_state.finish_restore();
+
+    match result {
+        Ok(report) => {
+            state.app_state.add_watcher_log(format!(
+                "Restored backup {} ({} files)",
+                filename, report.files_count
+            ));
+            Ok(Json(report))
+        }
+        Err(e) => {
+            state.app_state.add_log(
+                LogLevel::Error,
+                LogSource::Watcher,
+                format!("Restore of {} failed: {}", filename, e),
+            );
+            Err((StatusCode::INTERNAL_SERVER_ERROR, e))
+        }
+    }
+}
+
+/// GET /api/heapdumps
+#[utoipa::path(
+    get,
+    path = "/api/heapdumps",
+    tag = "control",
+    responses(
+        (status = 200, description = "Heap dumps left by OutOfMemoryError", body = Vec<HeapDumpResponse>),
+    )
+)]
+pub async fn get_heap_dumps(State(state): State<ApiState>) -> Json<Vec<HeapDumpResponse>> {
+    let dirs = heap_dump_dirs(&state.config.read().server);
+    let dumps = tokio::task::spawn_blocking(move || list_heap_dumps(&dirs))
+        .await
+        .unwrap_or_default();
+
+    Json(
+        dumps
+            .into_iter()
+            .map(|d| HeapDumpResponse {
+                filename: d.filename,
+                size: format_bytes(d.size_bytes),
+                size_bytes: d.size_bytes,
+                created_at: d.modified.format("%Y-%m-%d %H:%M:%S").to_string(),
+            })
+            .collect(),
+    )
+}
+
+/// DELETE /api/heapdumps/:filename
+#[utoipa::path(
+    delete,
+    path = "/api/heapdumps/{filename}",
+    tag = "control",
+    params(
+        ("filename" = String, Path, description = "Heap dump file name"),
+    ),
+    responses(
+        (status = 200, description = "Heap dump deleted", body = SuccessResponse),
+        (status = 400, description = "Invalid file name", body = String),
+        (status = 401, description = "Missing or wrong token", body = String),
+        (status = 404, description = "Unknown heap dump", body = String),
+    ),
+    security(("bearerAuth" = []))
+)]
+pub async fn delete_heap_dump_handler(
+    State(state): State<ApiState>,
+    Path(filename): Path<String>,
+) -> Result<Json<SuccessResponse>, (StatusCode, String)> {
+    let dirs = heap_dump_dirs(&state.config.read().server);
+    delete_heap_dump(&dirs, &filename).map_err(|e| {
+        let status = match e.kind() {
+            std::io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        (status, e.to_string())
+    })?;
+
+    state
+        .app_state
+        .add_watcher_log(format!("Deleted heap dump: {}", filename));
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        message: Some(format!("Deleted {}", filename)),
+    }))
+}
+
+/// GET /api/backups/stats
+#[utoipa::path(
+    get,
+    path = "/api/backups/stats",
+    tag = "backups",
+    responses(
+        (status = 200, description = "Compression level and skipped backups", body = BackupStatsResponse),
+    )
+)]
+pub async fn get_backup_stats(State(state): State<ApiState>) -> Json<BackupStatsResponse> {
+    Json(BackupStatsResponse {
+        compression_level: state.app_state.backup_compression_level(),
+        auto_compression: state.config.read().backup.compression_level.is_none(),
+        skipped_backup_count: state.app_state.skipped_backup_count(),
+        in_progress: state.app_state.backup_in_progress(),
+        upload: state.app_state.backup_upload(),
+    })
+}
+
+/// Throughput assumed for a first backup, when no duration is on record
+const ESTIMATED_BACKUP_BYTES_PER_SEC: u64 = 50 * 1024 * 1024;
+
+/// `time + secs`, or None past the range chrono can represent
+fn checked_add_secs(time: DateTime<Local>, secs: u64) -> Option<DateTime<Local>> {
+    i64::try_from(secs)
+        .ok()
+        .and_then(chrono::Duration::try_seconds)
+        .and_then(|duration| time.checked_add_signed(duration))
+}
+
+/// GET /api/backups/schedule
+#[utoipa::path(
+    get,
+    path = "/api/backups/schedule",
+    tag = "backups",
+    responses(
+        (status = 200, description = "Next scheduled backup", body = BackupScheduleResponse),
+        (status = 404, description = "Backups are disabled", body = String),
+    )
+)]
+pub async fn get_backup_schedule(
+    State(state): State<ApiState>,
+) -> Result<Json<BackupScheduleResponse>, (StatusCode, String)> {
+    let interval_hours = state.config.read().backup.interval_hours;
+    let Some(next_backup_in_secs) = state.app_state.next_backup_secs() else {
+        return Err((StatusCode::NOT_FOUND, "Backups are disabled".to_string()));
+    };
+
+    let last_backup = state.app_state.last_backup_time();
+    let next_backup_at = match last_backup {
+        Some(last) => checked_add_secs(last, interval_hours.saturating_mul(3600)),
+        None => checked_add_secs(Local::now(), next_backup_in_secs),
+    }
+    .ok_or((
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "backup.interval_hours is out of range".to_string(),
+    ))?;
+
+    let durations = state.app_state.backup_durations();
+    let estimated_duration_secs = if durations.is_empty() {
+        // Fall back to the source size recorded with the newest archive
+        state
+            .app_state
+            .backups()
+            .first()
+            .and_then(|b| b.original_size_bytes)
+            .map(|bytes| bytes / ESTIMATED_BACKUP_BYTES_PER_SEC)
+    } else {
+        Some(durations.iter().sum::<u64>() / durations.len() as u64)
+    };
+
+    Ok(Json(BackupScheduleResponse {
+        next_backup_at: next_backup_at.to_rfc3339(),
+        next_backup_in_secs,
+        interval_hours: interval_hours as f64,
+        last_backup_at: last_backup.map(|t| t.to_rfc3339()),
+        estimated_duration_secs,
+        last_3_durations_secs: durations,
+    }))
+}
+
+/// GET /api/backups/search?q= - By size (`>100MB`), date prefix (`2024-01`) or file name
+#[utoipa::path(
+    get,
+    path = "/api/backups/search",
+    tag = "backups",
+    params(
+        BackupSearchQuery,
+    ),
+    responses(
+        (status = 200, description = "Matching backups", body = Vec<BackupResponse>),
+        (status = 400, description = "Invalid query", body = String),
+    )
+)]
+pub async fn search_backups_handler(
+    State(state): State<ApiState>,
+    axum::extract::Query(query): axum::extract::Query<BackupSearchQuery>,
+) -> Result<Json<Vec<BackupResponse>>, (StatusCode, String)> {
+    let query = BackupQuery::parse(&query.q);
+    let backups = search_backups(&state.backup_path, &query, MAX_BACKUP_SEARCH_RESULTS)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(backups.into_iter().map(Into::into).collect()))
+}
+
+/// GET /api/backups/diff?a=&b= - Compare two archives by file listing
+#[utoipa::path(
+    get,
+    path = "/api/backups/diff",
+    tag = "backups",
+    params(
+        BackupDiffQuery,
+    ),
+    responses(
+        (status = 200, description = "Added, removed and changed files", body = BackupDiff),
+        (status = 400, description = "Invalid file name", body = String),
+    )
+)]
+pub async fn diff_backups_handler(
+    State(state): State<ApiState>,
+    axum::extract::Query(query): axum::extract::Query<BackupDiffQuery>,
+) -> Result<Json<BackupDiff>, (StatusCode, String)> {
+    let mut paths = Vec::with_capacity(2);
+    for filename in [&query.a, &query.b] {
+        // Same security check as download_backup
+        if !is_backup_filename(filename) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Invalid backup filename: {}", filename),
+            ));
+        }
+        let path = state.backup_path.join(filename);
+        if !path.is_file() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Backup not found: {}", filename),
+            ));
+        }
+        if is_encrypted_backup(filename) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Encrypted backups cannot be compared: {}", filename),
+            ));
+        }
+        paths.push(path);
+    }
+
+    let (a, b) = (paths[0].clone(), paths[1].clone());
+    tokio::task::spawn_blocking(move || diff_backups(&a, &b))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
 /// GET /api/backups/:filename - Download backup
+#[utoipa::path(
+    get,
+    path = "/api/backups/{filename}",
+    tag = "backups",
+    params(
+        ("filename" = String, Path, description = "Backup file name"),
+    ),
+    responses(
+        (status = 200, description = "Backup archive; SHA-256 in `X-Checksum-SHA256` when known", body = Vec<u8>),
+        (status = 400, description = "Invalid file name"),
+        (status = 404, description = "Unknown backup"),
+    )
+)]
 pub async fn download_backup(
     State(state): State<ApiState>,
     Path(filename): Path<String>,
@@ -260,7 +2415,7 @@ pub async fn download_backup(
     use tokio_util::io::ReaderStream;
 
     // Security check
-    if !filename.starts_with("backup_") || !filename.ends_with(".tar.xz") {
+    if !is_backup_filename(&filename) {
         return Err(StatusCode::BAD_REQUEST);
     }
 
@@ -273,37 +2428,765 @@ pub async fn download_backup(
     let body = Body::from_stream(stream);
 
     let content_disposition = format!("attachment; filename=\"{}\"", filename);
+    // Encrypted archives are served as-is
+    let content_type = if is_encrypted_backup(&filename) {
+        "application/octet-stream"
+    } else {
+        "application/x-xz"
+    };
 
-    Ok((
+    let mut response = (
         [
-            (header::CONTENT_TYPE, "application/x-xz".to_string()),
+            (header::CONTENT_TYPE, content_type.to_string()),
             (header::CONTENT_DISPOSITION, content_disposition),
         ],
         body,
-    ))
+    )
+        .into_response();
+    if let Some(checksum) = read_checksum(&file_path) {
+        if let Ok(value) = HeaderValue::from_str(&checksum) {
+            response.headers_mut().insert("x-checksum-sha256", value);
+        }
+    }
+    Ok(response)
 }
 
-/// GET /api/config
+/// GET /api/backup/:filename/contents - Archive listing without extracting.
+/// 206 with `X-Truncated: true` when cut at `max_manifest_entries`.
+#[utoipa::path(
+    get,
+    path = "/api/backup/{filename}/contents",
+    tag = "backups",
+    params(
+        ("filename" = String, Path, description = "Backup file name"),
+    ),
+    responses(
+        (status = 200, description = "Archive entries", body = Vec<ManifestEntry>),
+        (status = 206, description = "Listing cut at `max_manifest_entries`", body = Vec<ManifestEntry>),
+        (status = 400, description = "Invalid file name", body = String),
+        (status = 404, description = "Unknown backup", body = String),
+    )
+)]
+pub async fn get_backup_contents(
+    State(state): State<ApiState>,
+    Path(filename): Path<String>,
+) -> Result<Response, (StatusCode, String)> {
+    // Same security check as download_backup
+    if !is_backup_filename(&filename) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Invalid backup filename: {}", filename),
+        ));
+    }
+    let path = state.backup_path.join(&filename);
+    if !path.is_file() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Backup not found: {}", filename),
+        ));
+    }
+    if is_encrypted_backup(&filename) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Encrypted backups cannot be listed: {}", filename),
+        ));
+    }
+
+    let max_entries = state.config.read().backup.max_manifest_entries;
+    let (manifest, truncated) =
+        tokio::task::spawn_blocking(move || archive_manifest(&path, max_entries))
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if truncated {
+        let mut response = (StatusCode::PARTIAL_CONTENT, Json(manifest)).into_response();
+        response
+            .headers_mut()
+            .insert("x-truncated", HeaderValue::from_static("true"));
+        return Ok(response);
+    }
+    Ok(Json(manifest).into_response())
+}
+
+/// GET /api/backup/:filename/decrypt-test - Check the configured passphrase
+/// opens an encrypted archive, without decrypting all of it
+#[utoipa::path(
+    get,
+    path = "/api/backup/{filename}/decrypt-test",
+    tag = "backups",
+    params(
+        ("filename" = String, Path, description = "An encrypted backup"),
+    ),
+    responses(
+        (status = 200, description = "Whether the passphrase opens the backup", body = DecryptTestResponse),
+        (status = 400, description = "Not an encrypted backup", body = String),
+        (status = 404, description = "Unknown backup", body = String),
+        (status = 503, description = "No passphrase configured", body = String),
+    )
+)]
+pub async fn decrypt_test_backup(
+    State(state): State<ApiState>,
+    Path(filename): Path<String>,
+) -> Result<Json<DecryptTestResponse>, (StatusCode, String)> {
+    // Same security check as download_backup
+    if !is_backup_filename(&filename) || !is_encrypted_backup(&filename) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Not an encrypted backup: {}", filename),
+        ));
+    }
+    let Some(passphrase) = state
+        .config
+        .read()
+        .backup
+        .encryption_passphrase
+        .clone()
+        .filter(|p| !p.is_empty())
+    else {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "No encryption passphrase is configured".to_string(),
+        ));
+    };
+    let path = state.backup_path.join(&filename);
+    if !path.is_file() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Backup not found: {}", filename),
+        ));
+    }
+
+    let result = tokio::task::spawn_blocking(move || {
+        check_passphrase(&path, &passphrase).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(DecryptTestResponse {
+        filename,
+        valid: result.is_ok(),
+        error: result.err(),
+    }))
+}
+
+/// GET /api/config - Secrets are replaced by `***`
+#[utoipa::path(
+    get,
+    path = "/api/config",
+    tag = "config",
+    responses(
+        (status = 200, description = "Configuration with secrets redacted", body = Config),
+    )
+)]
 pub async fn get_config(State(state): State<ApiState>) -> Json<Config> {
+    let config = state.config.read().secrets_redacted();
+    Json(config)
+}
+
+/// GET /api/config/full - Unredacted, behind `require_superadmin`
+#[utoipa::path(
+    get,
+    path = "/api/config/full",
+    tag = "config",
+    responses(
+        (status = 200, description = "Configuration including secrets", body = Config),
+        (status = 401, description = "Missing or wrong token", body = String),
+        (status = 403, description = "Not the superadmin token", body = String),
+    ),
+    security(("bearerAuth" = []))
+)]
+pub async fn get_full_config(State(state): State<ApiState>) -> Json<Config> {
     let config = state.config.read().clone();
     Json(config)
 }
 
-/// PUT /api/config
+/// PUT /api/config - Behind `require_auth`; changing the web tokens
+/// additionally needs `web.superadmin_token`
+#[utoipa::path(
+    put,
+    path = "/api/config",
+    tag = "config",
+    request_body = Config,
+    responses(
+        (status = 200, description = "Configuration saved", body = SuccessResponse),
+        (status = 400, description = "Invalid configuration", body = String),
+        (status = 401, description = "Missing or wrong token", body = String),
+    ),
+    security(("bearerAuth" = []))
+)]
 pub async fn update_config(
     State(state): State<ApiState>,
-    Json(new_config): Json<Config>,
+    headers: HeaderMap,
+    uri: Uri,
+    Json(mut new_config): Json<Config>,
 ) -> Result<Json<SuccessResponse>, (StatusCode, String)> {
+    {
+        // Clients edit what GET /api/config returned; keep the real secrets
+        let current = state.config.read();
+        new_config.restore_secrets(&current);
+
+        // Otherwise anyone with dashboard access could set a superadmin
+        // token and read every secret through /api/config/full
+        let tokens_changed = new_config.web.auth_token != current.web.auth_token
+            || new_config.web.superadmin_token != current.web.superadmin_token;
+        let is_superadmin = match current.web.superadmin_token.as_deref() {
+            Some(expected) => token_from_parts(&headers, &uri).as_deref() == Some(expected),
+            None => false,
+        };
+        if tokens_changed && !is_superadmin {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "Changing web tokens requires web.superadmin_token".to_string(),
+            ));
+        }
+    }
+    new_config
+        .validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
     // Save to file
     new_config
-        .save("config-watcher.json")
+        .save(&state.config_path)
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
+    if let Some(ref tg) = state.telegram {
+        tg.set_server_name(new_config.identity.name.clone());
+    }
+
+    let changes = state.config.read().diff(&new_config);
+    let executable_changed = new_config.server.executable != state.config.read().server.executable;
+    let executable = new_config.server.executable.clone();
+
     // Update in memory
+    state
+        .app_state
+        .set_stats_interval_secs(new_config.resources.check_interval_seconds);
+    state.app_state.set_max_logs(new_config.state.max_logs);
+    state
+        .app_state
+        .set_log_dedup_enabled(new_config.state.log_dedup_enabled);
+    state
+        .app_state
+        .set_log_dedup_window_secs(new_config.state.log_dedup_window_secs);
     *state.config.write() = new_config;
+    if !changes.is_empty() {
+        state
+            .app_state
+            .add_watcher_log(format!("Config updated, changed: {}", changed_fields(&changes)));
+    }
+
+    if executable_changed {
+        refresh_java_version(&executable, &state.app_state).await;
+    }
 
     Ok(Json(SuccessResponse {
         success: true,
         message: Some("Config updated (restart required for some changes)".to_string()),
     }))
 }
+
+#[derive(Deserialize, ToSchema)]
+pub struct MaxLogsRequest {
+    pub max_logs: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MaxLogsResponse {
+    pub max_logs: usize,
+    pub current_log_count: usize,
+}
+
+/// PUT /api/config/max-logs - Resize the log buffer and save the new size
+#[utoipa::path(
+    put,
+    path = "/api/config/max-logs",
+    tag = "config",
+    request_body = MaxLogsRequest,
+    responses(
+        (status = 200, description = "New buffer size", body = MaxLogsResponse),
+        (status = 400, description = "`max_logs` must be > 0", body = String),
+        (status = 401, description = "Missing or wrong token", body = String),
+    ),
+    security(("bearerAuth" = []))
+)]
+pub async fn update_max_logs(
+    State(state): State<ApiState>,
+    Json(req): Json<MaxLogsRequest>,
+) -> Result<Json<MaxLogsResponse>, (StatusCode, String)> {
+    if req.max_logs == 0 {
+        return Err((StatusCode::BAD_REQUEST, "max_logs must be > 0".to_string()));
+    }
+
+    let mut new_config = state.config.read().clone();
+    new_config.state.max_logs = req.max_logs;
+    new_config
+        .save(&state.config_path)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    *state.config.write() = new_config;
+    state.app_state.set_max_logs(req.max_logs);
+
+    let snapshot = state.app_state.snapshot();
+    Ok(Json(MaxLogsResponse {
+        max_logs: snapshot.max_logs,
+        current_log_count: snapshot.current_log_count,
+    }))
+}
+
+/// GET /api/telegram/queue - Notifications waiting for a retry
+#[utoipa::path(
+    get,
+    path = "/api/telegram/queue",
+    tag = "config",
+    responses(
+        (status = 200, description = "Messages waiting to be retried", body = TelegramQueueResponse),
+    )
+)]
+pub async fn get_telegram_queue(State(state): State<ApiState>) -> Json<TelegramQueueResponse> {
+    let Some(ref tg) = state.telegram else {
+        return Json(TelegramQueueResponse {
+            depth: 0,
+            pending: vec![],
+        });
+    };
+
+    Json(TelegramQueueResponse {
+        depth: tg.queue_depth(),
+        pending: tg.pending_messages(10).into_iter().map(Into::into).collect(),
+    })
+}
+
+/// GET /api/identity
+#[utoipa::path(
+    get,
+    path = "/api/identity",
+    tag = "config",
+    responses(
+        (status = 200, description = "Server identity", body = IdentityConfig),
+    )
+)]
+pub async fn get_identity(State(state): State<ApiState>) -> Json<IdentityConfig> {
+    Json(state.config.read().identity.clone())
+}
+
+/// PUT /api/identity
+#[utoipa::path(
+    put,
+    path = "/api/identity",
+    tag = "config",
+    request_body = IdentityConfig,
+    responses(
+        (status = 200, description = "Saved identity", body = IdentityConfig),
+        (status = 400, description = "Invalid identity", body = String),
+        (status = 401, description = "Missing or wrong token", body = String),
+    ),
+    security(("bearerAuth" = []))
+)]
+pub async fn update_identity(
+    State(state): State<ApiState>,
+    Json(identity): Json<IdentityConfig>,
+) -> Result<Json<IdentityConfig>, (StatusCode, String)> {
+    save_identity(&state, |current| *current = identity).map(Json)
+}
+
+/// PATCH /api/identity
+#[utoipa::path(
+    patch,
+    path = "/api/identity",
+    tag = "config",
+    request_body = IdentityPatch,
+    responses(
+        (status = 200, description = "Saved identity", body = IdentityConfig),
+        (status = 400, description = "Invalid identity", body = String),
+        (status = 401, description = "Missing or wrong token", body = String),
+    ),
+    security(("bearerAuth" = []))
+)]
+pub async fn patch_identity(
+    State(state): State<ApiState>,
+    Json(patch): Json<IdentityPatch>,
+) -> Result<Json<IdentityConfig>, (StatusCode, String)> {
+    save_identity(&state, |identity| {
+        if let Some(name) = patch.name {
+            identity.name = name;
+        }
+        if let Some(description) = patch.description {
+            identity.description = Some(description);
+        }
+        if let Some(tags) = patch.tags {
+            identity.tags = tags;
+        }
+        if let Some(icon) = patch.icon {
+            identity.icon = Some(icon);
+        }
+    })
+    .map(Json)
+}
+
+/// Apply `update` to the identity and save the config. The write lock is
+/// held throughout so a concurrent config change cannot be lost.
+fn save_identity(
+    state: &ApiState,
+    update: impl FnOnce(&mut IdentityConfig),
+) -> Result<IdentityConfig, (StatusCode, String)> {
+    let identity = {
+        let mut config = state.config.write();
+        let mut new_config = config.clone();
+        update(&mut new_config.identity);
+        if new_config.identity.name.trim().is_empty() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "name must not be empty".to_string(),
+            ));
+        }
+        new_config
+            .save(&state.config_path)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        *config = new_config;
+        config.identity.clone()
+    };
+
+    if let Some(ref tg) = state.telegram {
+        tg.set_server_name(identity.name.clone());
+    }
+
+    Ok(identity)
+}
+
+/// Swagger UI from a CDN, pointed at `/api/docs`
+const SWAGGER_UI_HTML: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>Server Watcher API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.ui = SwaggerUIBundle({ url: "/api/docs", dom_id: "#swagger-ui" });
+  </script>
+</body>
+</html>
+"##;
+
+/// GET /api/docs - OpenAPI 3.0 specification
+#[utoipa::path(
+    get,
+    path = "/api/docs",
+    tag = "docs",
+    responses(
+        (status = 200, description = "This OpenAPI specification", body = Object),
+    )
+)]
+pub async fn get_api_docs() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// GET /api/docs/ui - Interactive documentation
+#[utoipa::path(
+    get,
+    path = "/api/docs/ui",
+    tag = "docs",
+    responses(
+        (status = 200, description = "Swagger UI page", body = String),
+    )
+)]
+pub async fn get_api_docs_ui() -> Html<&'static str> {
+    Html(SWAGGER_UI_HTML)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::S3Config;
+    use crate::web::server::router;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    const SECRETS: [&str; 6] = [
+        "tg-token-secret",
+        "chat-id-secret",
+        "auth-token-secret",
+        "superadmin-secret",
+        "s3-key-secret",
+        "env-secret",
+    ];
+
+    fn config_with_secrets() -> Config {
+        let mut config = Config::default_config();
+        config.telegram.token = SECRETS[0].to_string();
+        config.telegram.chat_id = SECRETS[1].to_string();
+        config.web.auth_token = Some(SECRETS[2].to_string());
+        config.web.superadmin_token = Some(SECRETS[3].to_string());
+        config.backup.remote_s3 = Some(S3Config {
+            endpoint: "https://s3.example.com".to_string(),
+            bucket: "backups".to_string(),
+            access_key: "access".to_string(),
+            secret_key: SECRETS[4].to_string(),
+            prefix: String::new(),
+            region: "us-east-1".to_string(),
+            remote_only: false,
+        });
+        config
+            .server
+            .env_vars
+            .insert("API_KEY".to_string(), SECRETS[5].to_string());
+        config
+    }
+
+    async fn body_string(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn get_config_hides_secrets() {
+        let app = router(ApiState::for_tests(config_with_secrets()));
+        let response = app
+            .oneshot(Request::get("/api/config").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = body_string(response).await;
+        for secret in SECRETS {
+            assert!(!body.contains(secret), "{} leaked: {}", secret, body);
+        }
+        assert!(body.contains("***"));
+    }
+
+    #[tokio::test]
+    async fn put_config_requires_auth() {
+        let app = router(ApiState::for_tests(config_with_secrets()));
+        let body = serde_json::to_string(&Config::default_config()).unwrap();
+        let response = app
+            .oneshot(
+                Request::put("/api/config")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn put_config_rejects_token_change_without_superadmin() {
+        let app = router(ApiState::for_tests(config_with_secrets()));
+        let mut new_config = config_with_secrets().secrets_redacted();
+        new_config.web.superadmin_token = Some("attacker".to_string());
+        let response = app
+            .oneshot(
+                Request::put("/api/config")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .header(header::AUTHORIZATION, format!("Bearer {}", SECRETS[2]))
+                    .body(Body::from(serde_json::to_string(&new_config).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn put_config_rejects_invalid_config() {
+        let app = router(ApiState::for_tests(config_with_secrets()));
+        let mut new_config = config_with_secrets().secrets_redacted();
+        new_config.state.max_logs = 0;
+        let response = app
+            .oneshot(
+                Request::put("/api/config")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .header(header::AUTHORIZATION, format!("Bearer {}", SECRETS[2]))
+                    .body(Body::from(serde_json::to_string(&new_config).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn send_command_requires_auth() {
+        let app = router(ApiState::for_tests(config_with_secrets()));
+        let response = app
+            .oneshot(
+                Request::post("/api/send-command")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(r#"{"command":"stop"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn checkpoint_file_names_stay_in_working_dir() {
+        assert!(is_plain_file_name("checkpoint.flag"));
+        for name in ["", ".", "..", "../x", "/etc/passwd", "a/b", "..\\x", "C:x"] {
+            assert!(!is_plain_file_name(name), "{:?} accepted", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn checkpoint_requires_auth() {
+        let app = router(ApiState::for_tests(config_with_secrets()));
+        let response = app
+            .oneshot(
+                Request::post("/api/checkpoint")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(r#"{"flag_file":"a","ack_file":"b"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn maintenance_rejects_huge_duration() {
+        let app = router(ApiState::for_tests(Config::default_config()));
+        let body = format!(r#"{{"enabled":true,"duration_secs":{}}}"#, u64::MAX);
+        let response = app
+            .oneshot(
+                Request::post("/api/maintenance")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn checked_add_secs_handles_overflow() {
+        let now = Local::now();
+        assert_eq!(
+            checked_add_secs(now, 60),
+            Some(now + chrono::Duration::seconds(60))
+        );
+        assert_eq!(checked_add_secs(now, u64::MAX), None);
+        assert_eq!(checked_add_secs(now, (i64::MAX / 1000) as u64), None);
+    }
+
+    #[tokio::test]
+    async fn put_max_logs_requires_auth_and_rejects_zero() {
+        let state = ApiState::for_tests(config_with_secrets());
+        let request = |token: Option<&str>| {
+            let mut builder = Request::put("/api/config/max-logs")
+                .header(header::CONTENT_TYPE, "application/json");
+            if let Some(token) = token {
+                builder = builder.header(header::AUTHORIZATION, format!("Bearer {}", token));
+            }
+            builder.body(Body::from(r#"{"max_logs":0}"#)).unwrap()
+        };
+
+        let response = router(state.clone()).oneshot(request(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = router(state.clone())
+            .oneshot(request(Some(SECRETS[2])))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(state.app_state.snapshot().max_logs > 0);
+    }
+
+    #[tokio::test]
+    async fn put_config_applies_stats_interval() {
+        let state = ApiState::for_tests(config_with_secrets());
+        let dir = state.config_path.parent().unwrap().to_path_buf();
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut new_config = config_with_secrets().secrets_redacted();
+        new_config.resources.check_interval_seconds = 42;
+
+        let response = router(state.clone())
+            .oneshot(
+                Request::put("/api/config")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .header(header::AUTHORIZATION, format!("Bearer {}", SECRETS[2]))
+                    .body(Body::from(serde_json::to_string(&new_config).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(state.app_state.stats_interval_secs(), 42);
+        let saved = Config::load(&state.config_path).unwrap();
+        assert_eq!(saved.resources.check_interval_seconds, 42);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn api_docs_lists_the_routes() {
+        let state = ApiState::for_tests(Config::default_config());
+        let response = router(state)
+            .oneshot(Request::get("/api/docs").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let spec: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let paths = spec["paths"].as_object().unwrap();
+        assert!(paths.contains_key("/api/status"));
+        assert!(paths["/api/config/max-logs"]["put"]["security"].is_array());
+        assert!(spec["components"]["securitySchemes"]["bearerAuth"].is_object());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn open_files_lists_a_file_held_by_the_server() {
+        let state = ApiState::for_tests(config_with_secrets());
+        let request = || {
+            Request::get("/api/process/open-files")
+                .header(header::AUTHORIZATION, format!("Bearer {}", SECRETS[2]))
+                .body(Body::empty())
+                .unwrap()
+        };
+        let response = router(state.clone()).oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let dir = std::env::temp_dir().join(format!("watcher-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let held = dir.join("world.dat");
+        std::fs::write(&held, b"data").unwrap();
+        let mut child = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("exec 7<'{}'; exec sleep 30", held.display()))
+            .spawn()
+            .unwrap();
+        state.app_state.set_pid(Some(child.id()));
+
+        let mut listed = false;
+        for _ in 0..50 {
+            let response = router(state.clone()).oneshot(request()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body: serde_json::Value =
+                serde_json::from_str(&body_string(response).await).unwrap();
+            listed = body["open_files"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .any(|f| f["path"] == held.to_string_lossy().as_ref() && f["type"] == "file");
+            if listed {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(listed);
+    }
+}
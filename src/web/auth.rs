@@ -1,14 +1,21 @@
 use axum::{
-    extract::Request,
-    http::StatusCode,
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode, Uri},
     middleware::Next,
     response::Response,
 };
 
+use super::api::ApiState;
+
 /// Extract token from Authorization header or query param
 pub fn extract_token(request: &Request) -> Option<String> {
+    token_from_parts(request.headers(), request.uri())
+}
+
+/// `extract_token` for handlers that only have the headers and URI
+pub fn token_from_parts(headers: &HeaderMap, uri: &Uri) -> Option<String> {
     // Try Authorization header first
-    if let Some(auth_header) = request.headers().get("Authorization") {
+    if let Some(auth_header) = headers.get("Authorization") {
         if let Ok(auth_str) = auth_header.to_str() {
             if let Some(token) = auth_str.strip_prefix("Bearer ") {
                 return Some(token.to_string());
@@ -17,7 +24,7 @@ pub fn extract_token(request: &Request) -> Option<String> {
     }
 
     // Try query param
-    if let Some(query) = request.uri().query() {
+    if let Some(query) = uri.query() {
         for pair in query.split('&') {
             if let Some(token) = pair.strip_prefix("token=") {
                 return Some(token.to_string());
@@ -62,3 +69,38 @@ pub fn validate_token(provided: Option<&str>, expected: Option<&str>) -> bool {
         (None, Some(_)) => false,
     }
 }
+
+/// Route-level middleware enforcing `web.auth_token` from the live config
+pub async fn require_auth(
+    State(state): State<ApiState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let expected = state.config.read().web.auth_token.clone();
+    let provided = extract_token(&request);
+
+    if validate_token(provided.as_deref(), expected.as_deref()) {
+        Ok(next.run(request).await)
+    } else {
+        Err(StatusCode::UNAUTHORIZED)
+    }
+}
+
+/// Route-level middleware for endpoints that expose secrets.
+/// Requires `web.superadmin_token`; without one configured they are off.
+pub async fn require_superadmin(
+    State(state): State<ApiState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let Some(expected) = state.config.read().web.superadmin_token.clone() else {
+        return Err(StatusCode::FORBIDDEN);
+    };
+    let provided = extract_token(&request);
+
+    if validate_token(provided.as_deref(), Some(&expected)) {
+        Ok(next.run(request).await)
+    } else {
+        Err(StatusCode::UNAUTHORIZED)
+    }
+}
@@ -0,0 +1,83 @@
+use crate::watcher::state::LogEntry;
+use serde::Deserialize;
+use std::fmt::Write;
+use utoipa::ToSchema;
+
+/// Output of `GET /api/logs/export`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LogExportFormat {
+    /// JSON Lines, one entry per line
+    #[default]
+    Json,
+    Csv,
+    Txt,
+}
+
+impl LogExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            LogExportFormat::Json => "application/x-ndjson",
+            LogExportFormat::Csv => "text/csv; charset=utf-8",
+            LogExportFormat::Txt => "text/plain; charset=utf-8",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            LogExportFormat::Json => "jsonl",
+            LogExportFormat::Csv => "csv",
+            LogExportFormat::Txt => "txt",
+        }
+    }
+}
+
+/// Quote a CSV field when it holds a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Render `entries` (oldest first) in `format`
+pub fn render_logs(entries: &[LogEntry], format: LogExportFormat) -> String {
+    let mut out = String::new();
+    match format {
+        LogExportFormat::Json => {
+            for entry in entries {
+                if let Ok(line) = serde_json::to_string(entry) {
+                    out.push_str(&line);
+                    out.push('\n');
+                }
+            }
+        }
+        LogExportFormat::Csv => {
+            out.push_str("timestamp,level,source,message\r\n");
+            for entry in entries {
+                let _ = write!(
+                    out,
+                    "{},{},{},{}\r\n",
+                    entry.timestamp.to_rfc3339(),
+                    format!("{:?}", entry.level).to_lowercase(),
+                    format!("{:?}", entry.source).to_lowercase(),
+                    csv_field(&entry.message)
+                );
+            }
+        }
+        LogExportFormat::Txt => {
+            for entry in entries {
+                let _ = writeln!(
+                    out,
+                    "{} [{:?}] [{:?}] {}",
+                    entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                    entry.level,
+                    entry.source,
+                    entry.message
+                );
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,50 @@
+use axum::{
+    extract::{Request, State},
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+use super::api::ApiState;
+
+pub static X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+pub static X_SERVER_NAME: HeaderName = HeaderName::from_static("x-server-name");
+
+/// Request ID available to handlers via `Extension<RequestId>`
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// Take `X-Request-ID` from the request (or generate one), record it on the
+/// current tracing span and echo it back in the response
+pub async fn request_id_middleware(mut request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(&X_REQUEST_ID)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty() && v.len() <= 128)
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    tracing::Span::current().record("request_id", id.as_str());
+    request.extensions_mut().insert(RequestId(id.clone()));
+
+    let mut response = next.run(request).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(X_REQUEST_ID.clone(), value);
+    }
+    response
+}
+
+/// Add `X-Server-Name` with the configured identity name to every response
+pub async fn server_name_middleware(
+    State(state): State<ApiState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+    let name = state.config.read().identity.name.clone();
+    if let Ok(value) = HeaderValue::from_bytes(name.as_bytes()) {
+        response.headers_mut().insert(X_SERVER_NAME.clone(), value);
+    }
+    response
+}
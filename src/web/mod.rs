@@ -2,5 +2,9 @@ pub mod server;
 pub mod api;
 pub mod websocket;
 pub mod auth;
+pub mod middleware;
+pub mod report;
+pub mod export;
+pub mod openapi;
 
 pub use server::*;
@@ -0,0 +1,188 @@
+//! OpenAPI document served at `/api/docs`, derived from the handler
+//! annotations in `api.rs` so it cannot drift from the routes.
+
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+use super::{api, websocket};
+use crate::config::*;
+use crate::watcher::analytics::CrashCorrelation;
+use crate::watcher::backup::{BackupDiff, ManifestEntry, RestoreReport, TestBackupReport};
+use crate::watcher::process::OpenFile;
+use crate::watcher::state::{
+    BackupUploadProgress, JvmStats, LogLevel, LogSource, ResourceAlertState, ResourceStats,
+    RestartReason, TimelineEvent,
+};
+use crate::web::export::LogExportFormat;
+use crate::web::report::ReportFormat;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Server Watcher API",
+        description = "Monitoring and control API of the server watcher. Routes with a lock need `Authorization: Bearer <web.auth_token>`."
+    ),
+    paths(
+        api::get_status,
+        api::get_stats,
+        api::get_stats_history,
+        api::get_active_alerts,
+        api::get_system,
+        api::get_uptime,
+        api::get_restart_history,
+        api::get_full_state,
+        api::get_timeline,
+        api::get_crash_report,
+        api::get_crash_correlation,
+        api::get_logs,
+        api::clear_logs,
+        api::get_log_categories,
+        api::get_all_logs,
+        api::get_remote_logs,
+        api::export_logs,
+        api::export_server_report,
+        api::test_error_pattern,
+        api::test_line_level,
+        api::get_backups,
+        api::trigger_backup,
+        api::restore_backup_handler,
+        api::get_backup_contents,
+        api::decrypt_test_backup,
+        api::test_backup_handler,
+        api::diff_backups_handler,
+        api::search_backups_handler,
+        api::get_backup_schedule,
+        api::get_backup_stats,
+        api::download_backup,
+        api::delete_backup_handler,
+        api::add_backup_tag_handler,
+        api::remove_backup_tag_handler,
+        api::restart_server,
+        api::cancel_restart,
+        api::start_server,
+        api::stop_server,
+        api::get_heap_dumps,
+        api::delete_heap_dump_handler,
+        api::get_maintenance,
+        api::set_maintenance,
+        api::checkpoint,
+        api::send_command,
+        api::send_signal,
+        api::get_open_files,
+        api::get_config,
+        api::update_config,
+        api::update_max_logs,
+        api::get_full_config,
+        api::get_telegram_queue,
+        api::get_identity,
+        api::update_identity,
+        api::patch_identity,
+        api::get_api_docs,
+        api::get_api_docs_ui,
+        websocket::ws_handler,
+    ),
+    components(schemas(
+        api::StatusResponse,
+        api::StatsResponse,
+        api::StatsHistoryEntry,
+        api::LogResponse,
+        api::LogPageResponse,
+        api::LogCategoryResponse,
+        api::ClearLogsResponse,
+        api::BackupResponse,
+        api::BackupTagRequest,
+        api::BackupTagsResponse,
+        api::BackupStatsResponse,
+        api::BackupScheduleResponse,
+        api::TriggerBackupResponse,
+        api::DecryptTestResponse,
+        api::MaintenanceRequest,
+        api::MaintenanceResponse,
+        api::HeapDumpResponse,
+        api::CheckpointRequest,
+        api::CheckpointResponse,
+        api::SendCommandRequest,
+        api::SignalRequest,
+        api::SignalSpec,
+        api::UptimeResponse,
+        api::SystemResponse,
+        api::SuccessResponse,
+        api::FullStateResponse,
+        api::PatternTestRequest,
+        api::PatternTestResponse,
+        api::LineLevelRequest,
+        api::LineLevelResponse,
+        api::CrashReportResponse,
+        api::OpenFilesResponse,
+        api::TimelineResponse,
+        api::RestartHistoryResponse,
+        api::PendingMessageResponse,
+        api::TelegramQueueResponse,
+        api::IdentityPatch,
+        api::MaxLogsRequest,
+        api::MaxLogsResponse,
+        Config,
+        IdentityConfig,
+        ServerConfig,
+        LogFileConfig,
+        LogRotationPolicy,
+        StdinTerminator,
+        PreLaunchCheck,
+        MaintenanceWindow,
+        TelegramConfig,
+        ResourceConfig,
+        CpuThresholdAction,
+        JvmMetricsConfig,
+        ErrorPatterns,
+        PatternEntry,
+        LogCategoryConfig,
+        RestartConfig,
+        BackupConfig,
+        S3Config,
+        WebConfig,
+        ConfigConfig,
+        StateConfig,
+        WatcherLogFileConfig,
+        RemoteServer,
+        LogLevel,
+        LogSource,
+        ResourceStats,
+        JvmStats,
+        ResourceAlertState,
+        BackupUploadProgress,
+        RestartReason,
+        TimelineEvent,
+        CrashCorrelation,
+        BackupDiff,
+        ManifestEntry,
+        RestoreReport,
+        TestBackupReport,
+        OpenFile,
+        LogExportFormat,
+        ReportFormat,
+    )),
+    modifiers(&SecurityAddon),
+    tags(
+        (name = "status", description = "Server state, resources and history"),
+        (name = "logs", description = "Log buffer, exports and pattern testing"),
+        (name = "backups", description = "Backup archives, tags and restores"),
+        (name = "control", description = "Start, stop, restart and talk to the server"),
+        (name = "config", description = "Configuration, identity and notifications"),
+        (name = "docs", description = "This specification"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Registers the `bearerAuth` scheme referenced by the `security` of
+/// the routes behind `require_auth`
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearerAuth",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
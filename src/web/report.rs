@@ -0,0 +1,262 @@
+use crate::config::Config;
+use crate::watcher::backup::format_bytes;
+use crate::watcher::state::AppState;
+use serde::Deserialize;
+use std::fmt::Write;
+use sysinfo::System;
+use utoipa::ToSchema;
+
+/// Output flavour of the diagnostic report
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    #[default]
+    Text,
+    Markdown,
+}
+
+const REPORT_LOG_LINES: usize = 100;
+const REPORT_RESTARTS: usize = 5;
+const REDACTED: &str = "<redacted>";
+
+/// Writes headers, key/value lines and preformatted blocks in either format
+struct ReportWriter {
+    format: ReportFormat,
+    out: String,
+}
+
+impl ReportWriter {
+    fn section(&mut self, title: &str) {
+        match self.format {
+            ReportFormat::Text => {
+                let _ = writeln!(self.out, "\n=== {} ===", title);
+            }
+            ReportFormat::Markdown => {
+                let _ = writeln!(self.out, "\n## {}\n", title);
+            }
+        }
+    }
+
+    fn field(&mut self, key: &str, value: impl std::fmt::Display) {
+        match self.format {
+            ReportFormat::Text => {
+                let _ = writeln!(self.out, "{:<24} {}", format!("{}:", key), value);
+            }
+            ReportFormat::Markdown => {
+                let _ = writeln!(self.out, "- **{}**: {}", key, value);
+            }
+        }
+    }
+
+    fn block(&mut self, lines: &[String]) {
+        if self.format == ReportFormat::Markdown {
+            self.out.push_str("```\n");
+        }
+        for line in lines {
+            self.out.push_str(line);
+            self.out.push('\n');
+        }
+        if self.format == ReportFormat::Markdown {
+            self.out.push_str("```\n");
+        }
+    }
+}
+
+fn secret(value: Option<&str>) -> &'static str {
+    match value {
+        Some(v) if !v.is_empty() => REDACTED,
+        _ => "(not set)",
+    }
+}
+
+fn or_none<T: std::fmt::Display>(value: Option<T>) -> String {
+    value
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// Build a human-readable diagnostic report. Secrets are never included.
+pub fn generate_report(app_state: &AppState, config: &Config, format: ReportFormat) -> String {
+    let mut w = ReportWriter {
+        format,
+        out: String::new(),
+    };
+
+    match format {
+        ReportFormat::Text => {
+            let _ = writeln!(w.out, "Server Watcher diagnostic report");
+        }
+        ReportFormat::Markdown => {
+            let _ = writeln!(w.out, "# Server Watcher diagnostic report");
+        }
+    }
+    w.field(
+        "Generated",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+    );
+    w.field("Watcher version", env!("CARGO_PKG_VERSION"));
+
+    // Server state
+    let snapshot = app_state.snapshot();
+    w.section("Server");
+    w.field("Name", &config.identity.name);
+    w.field("Status", snapshot.status.as_str());
+    w.field("PID", or_none(snapshot.pid));
+    w.field("Uptime (s)", snapshot.uptime_secs);
+    w.field("Restart count", snapshot.restart_count);
+    if let Some(exit) = app_state.last_exit() {
+        w.field(
+            "Last exit",
+            format!(
+                "{} (code {}) at {}",
+                exit.exit_reason,
+                or_none(exit.exit_code),
+                exit.timestamp.format("%Y-%m-%d %H:%M:%S")
+            ),
+        );
+    }
+
+    // Config summary
+    w.section("Configuration");
+    w.field("Executable", &config.server.executable);
+    w.field("Arguments", config.server.arguments.join(" "));
+    w.field(
+        "Working directory",
+        config.server.working_directory.as_deref().unwrap_or("."),
+    );
+    w.field("Restart delay (s)", config.server.error_restart_delay_secs);
+    w.field(
+        "Manual restart delay (s)",
+        config.server.manual_restart_delay_secs,
+    );
+    w.field("Max restarts", or_none(config.server.max_restarts));
+    w.field(
+        "Auto-restart interval (min)",
+        or_none(config.server.auto_restart_interval_minutes()),
+    );
+    w.field(
+        "Restart on",
+        format!(
+            "critical={} errors={} warnings={} process_exit={}",
+            config.restart_on.critical,
+            config.restart_on.errors,
+            config.restart_on.warnings,
+            config.restart_on.process_exit
+        ),
+    );
+    w.field("Telegram enabled", config.telegram.enabled);
+    w.field("Telegram token", secret(Some(&config.telegram.token)));
+    w.field("Web", format!("{}:{}", config.web.host, config.web.port));
+    w.field("Web auth token", secret(config.web.auth_token.as_deref()));
+    w.field("Remote servers", config.remote_servers.len());
+
+    // Resources
+    let stats = app_state.stats();
+    w.section("Resources");
+    w.field("CPU", format!("{:.1}%", stats.cpu_percent));
+    w.field(
+        "Memory",
+        format!("{} MB ({:.1}%)", stats.memory_mb, stats.memory_percent),
+    );
+    w.field(
+        "Network rx / tx",
+        format!(
+            "{}/s / {}/s",
+            format_bytes(stats.network_rx_speed),
+            format_bytes(stats.network_tx_speed)
+        ),
+    );
+    w.field(
+        "Disk read / write",
+        format!(
+            "{}/s / {}/s",
+            format_bytes(stats.disk_read_speed),
+            format_bytes(stats.disk_write_speed)
+        ),
+    );
+
+    // Backups
+    let backups = app_state.backups();
+    w.section("Backups");
+    w.field("Enabled", config.backup.enabled);
+    w.field(
+        "Interval / retention",
+        format!(
+            "{} h / {} days",
+            config.backup.interval_hours, config.backup.retention_days
+        ),
+    );
+    w.field(
+        "Encryption passphrase",
+        secret(config.backup.encryption_passphrase.as_deref()),
+    );
+    w.field(
+        "Last backup",
+        or_none(
+            app_state
+                .last_backup_time()
+                .map(|t| t.format("%Y-%m-%d %H:%M:%S")),
+        ),
+    );
+    w.field("Next backup in (s)", or_none(app_state.next_backup_secs()));
+    w.field("Archives", backups.len());
+    w.field(
+        "Total size",
+        format_bytes(backups.iter().map(|b| b.size_bytes).sum()),
+    );
+
+    // Restarts
+    w.section("Recent restarts");
+    let restarts: Vec<String> = app_state
+        .restart_history(REPORT_RESTARTS)
+        .iter()
+        .map(|r| format!("{}  {}", r.timestamp.format("%Y-%m-%d %H:%M:%S"), r.reason))
+        .collect();
+    if restarts.is_empty() {
+        w.out.push_str("(none)\n");
+    } else {
+        w.block(&restarts);
+    }
+
+    // System
+    let mut system = System::new();
+    system.refresh_memory();
+    w.section("System");
+    w.field(
+        "OS",
+        format!(
+            "{} {}",
+            System::name().unwrap_or_default(),
+            System::os_version().unwrap_or_default()
+        ),
+    );
+    w.field("Kernel", System::kernel_version().unwrap_or_default());
+    w.field("Architecture", std::env::consts::ARCH);
+    w.field(
+        "CPUs",
+        std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1),
+    );
+    w.field("Total memory", format_bytes(system.total_memory()));
+
+    // Logs, oldest first
+    w.section(&format!("Last {} log entries", REPORT_LOG_LINES));
+    let logs: Vec<String> = app_state
+        .logs(REPORT_LOG_LINES)
+        .iter()
+        .rev()
+        .map(|l| {
+            format!(
+                "{} [{:?}] [{:?}] {}",
+                l.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                l.level,
+                l.source,
+                l.message
+            )
+        })
+        .collect();
+    w.block(&logs);
+
+    w.out
+}
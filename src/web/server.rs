@@ -1,8 +1,12 @@
 use crate::config::Config;
-use crate::watcher::process::ProcessCommand;
+use crate::watcher::backup::BackupCommand;
+use crate::watcher::process::CommandSender;
 use crate::watcher::state::AppState;
+use crate::watcher::telegram::TelegramClient;
 use axum::{
-    http::{header, StatusCode, Uri},
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, StatusCode, Uri},
+    middleware::{from_fn, from_fn_with_state},
     response::{Html, IntoResponse, Response},
     routing::{delete, get, post, put},
     Router,
@@ -17,6 +21,8 @@ use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
 
 use super::api::{self, ApiState};
+use super::auth::{require_auth, require_superadmin};
+use super::middleware::{request_id_middleware, server_name_middleware};
 use super::websocket;
 
 /// Embedded static files from web-ui/dist
@@ -27,8 +33,11 @@ struct Assets;
 /// Start the web server
 pub async fn start_server(
     config: Arc<RwLock<Config>>,
+    config_path: PathBuf,
     app_state: Arc<AppState>,
-    process_tx: mpsc::Sender<ProcessCommand>,
+    process_tx: CommandSender,
+    backup_tx: mpsc::Sender<BackupCommand>,
+    telegram: Option<TelegramClient>,
     shutdown_rx: watch::Receiver<bool>,
 ) {
     let web_config = config.read().web.clone();
@@ -38,7 +47,7 @@ pub async fn start_server(
         return;
     }
 
-    let backup_path = {
+    let (backup_path, source_path) = {
         let cfg = config.read();
         let base = cfg
             .server
@@ -46,16 +55,55 @@ pub async fn start_server(
             .clone()
             .map(PathBuf::from)
             .unwrap_or_else(|| std::env::current_dir().unwrap_or_default());
-        base.join(&cfg.backup.backup_folder)
+        (
+            base.join(&cfg.backup.backup_folder),
+            base.join(&cfg.backup.source_folder),
+        )
     };
 
     let api_state = ApiState {
         app_state,
         config,
+        config_path,
         process_tx,
+        backup_tx,
         backup_path,
+        source_path,
+        telegram,
     };
 
+    let app = router(api_state);
+
+    let addr: SocketAddr = format!("{}:{}", web_config.host, web_config.port)
+        .parse()
+        .expect("Invalid address");
+
+    tracing::info!("Web server starting on http://{}", addr);
+
+    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+
+    // Run with graceful shutdown
+    let mut shutdown = shutdown_rx.clone();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        loop {
+            shutdown.changed().await.ok();
+            if *shutdown.borrow() {
+                break;
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    tracing::info!("Web server stopped");
+}
+
+/// All API, WebSocket and static routes with their middleware
+pub fn router(api_state: ApiState) -> Router {
     // CORS for development
     let cors = CorsLayer::new()
         .allow_origin(Any)
@@ -63,50 +111,243 @@ pub async fn start_server(
         .allow_headers(Any);
 
     // Build router
-    let app = Router::new()
+    Router::new()
         // API routes
         .route("/api/status", get(api::get_status))
         .route("/api/stats", get(api::get_stats))
-        .route("/api/logs", get(api::get_logs))
+        .route("/api/stats/alerts", get(api::get_active_alerts))
+        .route("/api/stats/history", get(api::get_stats_history))
+        .route("/api/system", get(api::get_system))
+        .route("/api/uptime", get(api::get_uptime))
+        .route("/api/history", get(api::get_restart_history))
+        .route(
+            "/api/logs",
+            get(api::get_logs).merge(
+                delete(api::clear_logs)
+                    .route_layer(from_fn_with_state(api_state.clone(), require_auth)),
+            ),
+        )
+        .route("/api/logs/categories", get(api::get_log_categories))
+        .route("/api/logs/all", get(api::get_all_logs))
+        .route("/api/logs/export", get(api::export_logs))
+        .route("/api/remote/:id/logs", get(api::get_remote_logs))
+        .route(
+            "/api/logs/export/server-report",
+            get(api::export_server_report),
+        )
         .route("/api/backups", get(api::get_backups))
+        .route(
+            "/api/backup/trigger",
+            post(api::trigger_backup)
+                .route_layer(from_fn_with_state(api_state.clone(), require_auth)),
+        )
+        .route(
+            "/api/backup/restore/:filename",
+            post(api::restore_backup_handler)
+                .route_layer(from_fn_with_state(api_state.clone(), require_auth)),
+        )
+        .route(
+            "/api/backup/:filename/contents",
+            get(api::get_backup_contents),
+        )
+        .route(
+            "/api/backup/:filename/decrypt-test",
+            get(api::decrypt_test_backup),
+        )
+        .route(
+            "/api/backups/test",
+            post(api::test_backup_handler)
+                .route_layer(from_fn_with_state(api_state.clone(), require_auth)),
+        )
+        .route("/api/backups/diff", get(api::diff_backups_handler))
+        .route("/api/backups/search", get(api::search_backups_handler))
+        .route("/api/backups/schedule", get(api::get_backup_schedule))
+        .route("/api/backups/stats", get(api::get_backup_stats))
         .route("/api/backups/:filename", get(api::download_backup))
         .route("/api/backups/:filename", delete(api::delete_backup_handler))
+        .route(
+            "/api/backups/:filename/tag",
+            post(api::add_backup_tag_handler)
+                .route_layer(from_fn_with_state(api_state.clone(), require_auth)),
+        )
+        .route(
+            "/api/backups/:filename/tag/:tag",
+            delete(api::remove_backup_tag_handler)
+                .route_layer(from_fn_with_state(api_state.clone(), require_auth)),
+        )
         .route("/api/state", get(api::get_full_state))
+        .route("/api/timeline", get(api::get_timeline))
+        .route("/api/crash-report", get(api::get_crash_report))
+        .route(
+            "/api/analytics/crash-correlation",
+            get(api::get_crash_correlation),
+        )
+        .route(
+            "/api/error-patterns/test",
+            post(api::test_error_pattern).put(api::test_error_pattern),
+        )
+        .route("/api/patterns/test", post(api::test_line_level))
         .route("/api/restart", post(api::restart_server))
+        .route(
+            "/api/restart/cancel",
+            post(api::cancel_restart)
+                .route_layer(from_fn_with_state(api_state.clone(), require_auth)),
+        )
+        .route(
+            "/api/start",
+            post(api::start_server)
+                .route_layer(from_fn_with_state(api_state.clone(), require_auth)),
+        )
         .route("/api/stop", post(api::stop_server))
+        .route("/api/heapdumps", get(api::get_heap_dumps))
+        .route(
+            "/api/heapdumps/:filename",
+            delete(api::delete_heap_dump_handler)
+                .route_layer(from_fn_with_state(api_state.clone(), require_auth)),
+        )
+        .route(
+            "/api/maintenance",
+            get(api::get_maintenance).merge(
+                post(api::set_maintenance)
+                    .route_layer(from_fn_with_state(api_state.clone(), require_auth)),
+            ),
+        )
+        .route(
+            "/api/checkpoint",
+            post(api::checkpoint).route_layer(from_fn_with_state(api_state.clone(), require_auth)),
+        )
+        .route(
+            "/api/send-command",
+            post(api::send_command)
+                .route_layer(from_fn_with_state(api_state.clone(), require_auth)),
+        )
+        .route(
+            "/api/signal",
+            post(api::send_signal).route_layer(from_fn_with_state(api_state.clone(), require_auth)),
+        )
+        .route(
+            "/api/process/open-files",
+            get(api::get_open_files)
+                .route_layer(from_fn_with_state(api_state.clone(), require_auth)),
+        )
         .route("/api/config", get(api::get_config))
-        .route("/api/config", put(api::update_config))
+        .route(
+            "/api/config",
+            put(api::update_config)
+                .route_layer(from_fn_with_state(api_state.clone(), require_auth)),
+        )
+        .route(
+            "/api/config/max-logs",
+            put(api::update_max_logs)
+                .route_layer(from_fn_with_state(api_state.clone(), require_auth)),
+        )
+        .route(
+            "/api/config/full",
+            get(api::get_full_config)
+                .route_layer(from_fn_with_state(api_state.clone(), require_superadmin)),
+        )
+        .route("/api/telegram/queue", get(api::get_telegram_queue))
+        .route("/api/identity", get(api::get_identity))
+        .route(
+            "/api/identity",
+            put(api::update_identity)
+                .patch(api::patch_identity)
+                .route_layer(from_fn_with_state(api_state.clone(), require_auth)),
+        )
+        .route("/api/docs", get(api::get_api_docs))
+        .route("/api/docs/ui", get(api::get_api_docs_ui))
         // WebSocket
         .route("/ws", get(websocket::ws_handler))
         // Static files (SPA)
-        .fallback(static_handler)
+        .fallback(cached_static_handler)
+        .layer(from_fn_with_state(
+            api_state.clone(),
+            server_name_middleware,
+        ))
         .with_state(api_state)
         .layer(cors)
-        .layer(TraceLayer::new_for_http());
+        .layer(from_fn(request_id_middleware))
+        .layer(
+            TraceLayer::new_for_http().make_span_with(|request: &Request| {
+                tracing::info_span!(
+                    "request",
+                    method = %request.method(),
+                    // The query string can carry `?token=`
+                    path = %request.uri().path(),
+                    request_id = tracing::field::Empty,
+                )
+            }),
+        )
+}
 
-    let addr: SocketAddr = format!("{}:{}", web_config.host, web_config.port)
-        .parse()
-        .expect("Invalid address");
+/// Cache-Control for assets whose filename carries a content hash
+const IMMUTABLE_CACHE: &str = "public, max-age=31536000, immutable";
 
-    tracing::info!("Web server starting on http://{}", addr);
+/// Wraps `static_handler` with ETag / Cache-Control headers and answers
+/// `304 Not Modified` when the browser already has the asset.
+async fn cached_static_handler(uri: Uri, headers: HeaderMap) -> Response<axum::body::Body> {
+    let path = uri.path().trim_start_matches('/');
 
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+    // Resolve the same asset static_handler will serve
+    let (asset_path, content) = match Assets::get(path) {
+        Some(content) => (path, content),
+        None => match Assets::get("index.html") {
+            Some(content) => ("index.html", content),
+            None => return static_handler(uri).await,
+        },
+    };
 
-    // Run with graceful shutdown
-    let mut shutdown = shutdown_rx.clone();
-    axum::serve(listener, app)
-        .with_graceful_shutdown(async move {
-            loop {
-                shutdown.changed().await.ok();
-                if *shutdown.borrow() {
-                    break;
-                }
-            }
-        })
-        .await
-        .unwrap();
+    // sha256 of the embedded data, computed at build time by rust-embed
+    let etag = format!("\"{}\"", hex::encode(content.metadata.sha256_hash()));
+    let cache_control = if is_hashed_asset(asset_path) {
+        IMMUTABLE_CACHE
+    } else {
+        "no-cache"
+    };
 
-    tracing::info!("Web server stopped");
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| etag_matches(v, &etag));
+
+    let mut response = if not_modified {
+        Response::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .body(axum::body::Body::empty())
+            .unwrap()
+    } else {
+        static_handler(uri).await
+    };
+
+    let response_headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response_headers.insert(header::ETAG, value);
+    }
+    response_headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static(cache_control),
+    );
+    response
+}
+
+/// Check an `If-None-Match` header value against our ETag
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+/// Bundler output like `index.3f9a1c2b.js` or `app-3f9a1c2b.css`
+fn is_hashed_asset(path: &str) -> bool {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    let Some((stem, _ext)) = file_name.rsplit_once('.') else {
+        return false;
+    };
+
+    stem.split(['.', '-', '_'])
+        .skip(1)
+        .any(|part| part.len() >= 8 && part.chars().all(|c| c.is_ascii_hexdigit()))
 }
 
 /// Serve static files from embedded assets
@@ -170,3 +411,119 @@ async fn static_handler(uri: Uri) -> Response<axum::body::Body> {
         ))
         .unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::web::middleware::X_REQUEST_ID;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tokio_tungstenite::tungstenite;
+    use tower::ServiceExt;
+
+    /// Serve the router on a free local port
+    async fn serve(config: Config) -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = router(ApiState::for_tests(config));
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+            .unwrap();
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn websocket_checks_token() {
+        let mut config = Config::default_config();
+        config.web.auth_token = Some("ws-secret".to_string());
+        let addr = serve(config).await;
+
+        let (mut socket, _) =
+            tokio_tungstenite::connect_async(format!("ws://{}/ws?token=ws-secret", addr))
+                .await
+                .unwrap();
+        socket.close(None).await.unwrap();
+
+        for url in [
+            format!("ws://{}/ws?token=wrong", addr),
+            format!("ws://{}/ws", addr),
+        ] {
+            match tokio_tungstenite::connect_async(url).await {
+                Err(tungstenite::Error::Http(response)) => {
+                    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+                }
+                other => panic!("expected 401, got {:?}", other.map(|(_, r)| r.status())),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn request_id_is_echoed() {
+        let app = router(ApiState::for_tests(Config::default_config()));
+        let request_id = |id: Option<&str>| {
+            let mut builder = Request::get("/api/status");
+            if let Some(id) = id {
+                builder = builder.header(&X_REQUEST_ID, id);
+            }
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(builder.body(Body::empty()).unwrap()).await.unwrap();
+                response.headers()[&X_REQUEST_ID].to_str().unwrap().to_string()
+            }
+        };
+
+        assert_eq!(request_id(Some("abc-123")).await, "abc-123");
+
+        // Missing or oversized IDs are replaced by a fresh UUID
+        let generated = request_id(None).await;
+        assert!(uuid::Uuid::parse_str(&generated).is_ok());
+        let long = "x".repeat(129);
+        let replaced = request_id(Some(&long)).await;
+        assert!(uuid::Uuid::parse_str(&replaced).is_ok());
+    }
+
+    #[tokio::test]
+    async fn changing_routes_require_auth() {
+        let mut config = Config::default_config();
+        config.web.auth_token = Some("secret".to_string());
+        let app = router(ApiState::for_tests(config));
+
+        for (method, uri) in [
+            ("POST", "/api/backup/restore/backup_20240101_000000.tar.xz"),
+            ("POST", "/api/signal"),
+            ("PUT", "/api/identity"),
+            ("PATCH", "/api/identity"),
+            ("POST", "/api/backup/trigger"),
+            ("POST", "/api/backups/backup_20240101_000000.tar.xz/tag"),
+            ("POST", "/api/maintenance"),
+            ("DELETE", "/api/heapdumps/java_pid1234.hprof"),
+            ("POST", "/api/start"),
+            ("POST", "/api/restart/cancel"),
+            ("POST", "/api/backups/test"),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method(method)
+                        .uri(uri)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(
+                response.status(),
+                StatusCode::UNAUTHORIZED,
+                "{} {}",
+                method,
+                uri
+            );
+        }
+    }
+}
@@ -1,17 +1,22 @@
-use crate::watcher::state::AppState;
+use crate::config::{Config, IdentityConfig};
+use crate::watcher::state::{AppState, LogEntry, ResourceAlertState, StateChangeEvent};
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
+        ConnectInfo, Query, State,
     },
-    response::Response,
+    http::StatusCode,
+    response::{IntoResponse, Response},
 };
 use futures_util::{SinkExt, StreamExt};
-use serde::Serialize;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::time::{interval, Duration};
 
 use super::api::ApiState;
+use super::auth::validate_token;
 
 /// WebSocket message types sent to clients
 #[derive(Serialize)]
@@ -19,6 +24,7 @@ use super::api::ApiState;
 pub enum WsMessage {
     #[serde(rename = "status")]
     Status {
+        identity: IdentityConfig,
         status: String,
         pid: Option<u32>,
         uptime_secs: u64,
@@ -36,91 +42,183 @@ pub enum WsMessage {
     },
     #[serde(rename = "log")]
     Log {
+        id: u64,
         timestamp: String,
         level: String,
         source: String,
         message: String,
+        /// Identical lines collapsed into this one. An entry is sent again
+        /// with the same id when its count grows.
+        repeat_count: u32,
     },
+    #[serde(rename = "event")]
+    Event(StateChangeEvent),
+    #[serde(rename = "alert")]
+    Alert { alerts: Vec<ResourceAlertState> },
+    #[serde(rename = "logs_cleared")]
+    LogsCleared { cleared_at: String },
     #[serde(rename = "ping")]
     Ping,
 }
 
-/// WebSocket upgrade handler
+/// Browsers cannot set headers on a WebSocket handshake, so the token
+/// comes in the query string
+#[derive(Deserialize)]
+pub struct WsQuery {
+    pub token: Option<String>,
+}
+
+/// WebSocket upgrade handler. Checks `web.auth_token` before upgrading,
+/// while a 401 can still be sent as a plain HTTP response.
+#[utoipa::path(
+    get,
+    path = "/ws",
+    tag = "status",
+    params(
+        ("token" = Option<String>, Query, description = "Required when `web.auth_token` is set"),
+    ),
+    responses(
+        (status = 101, description = "Switching to a WebSocket with live status, stats, logs and alerts"),
+        (status = 401, description = "Missing or wrong token"),
+    )
+)]
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
     State(state): State<ApiState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(query): Query<WsQuery>,
 ) -> Response {
-    ws.on_upgrade(move |socket| handle_socket(socket, state.app_state))
+    let expected = state.config.read().web.auth_token.clone();
+    if !validate_token(query.token.as_deref(), expected.as_deref()) {
+        tracing::warn!("Rejected WebSocket upgrade from {}: invalid token", addr);
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    ws.on_upgrade(move |socket| handle_socket(socket, state.app_state, state.config))
 }
 
-async fn handle_socket(socket: WebSocket, app_state: Arc<AppState>) {
+async fn handle_socket(socket: WebSocket, app_state: Arc<AppState>, config: Arc<RwLock<Config>>) {
     let (mut sender, mut receiver) = socket.split();
 
-    // Track last log count to detect new logs
-    let mut last_log_count = app_state.logs(1000).len();
+    // Only entries added after the client connected are pushed
+    let mut last_log_id = app_state.last_log_id();
 
     // Spawn task to send updates
     let state_clone = Arc::clone(&app_state);
+    let mut events_rx = app_state.subscribe();
     let send_task = tokio::spawn(async move {
         let mut ticker = interval(Duration::from_secs(1));
+        let mut last_sent_counts: Option<(u64, u64)> = None;
+        let mut last_alerts_generation = state_clone.alerts_generation();
+        let mut last_logs_cleared_at = state_clone.logs_cleared_at();
+        let mut last_repeat_count = 0;
 
         loop {
-            ticker.tick().await;
-
-            // Send status
-            let snapshot = state_clone.snapshot();
-            let status_msg = WsMessage::Status {
-                status: snapshot.status.as_str().to_string(),
-                pid: snapshot.pid,
-                uptime_secs: snapshot.uptime_secs,
-                restart_count: snapshot.restart_count,
-                auto_restart_remaining_secs: snapshot.auto_restart_remaining_secs,
-                next_backup_secs: snapshot.next_backup_secs,
-            };
-
-            if let Ok(json) = serde_json::to_string(&status_msg) {
-                if sender.send(Message::Text(json)).await.is_err() {
-                    break;
+            tokio::select! {
+                _ = ticker.tick() => {}
+                event = events_rx.recv() => {
+                    if let Ok(event) = event {
+                        if let Ok(json) = serde_json::to_string(&WsMessage::Event(event)) {
+                            if sender.send(Message::Text(json)).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    continue;
                 }
             }
 
-            // Send stats
-            let stats = state_clone.stats();
-            let stats_msg = WsMessage::Stats {
-                cpu_percent: stats.cpu_percent,
-                memory_mb: stats.memory_mb,
-                memory_percent: stats.memory_percent,
-                network_rx_speed: stats.network_rx_speed,
-                network_tx_speed: stats.network_tx_speed,
-            };
-
-            if let Ok(json) = serde_json::to_string(&stats_msg) {
-                if sender.send(Message::Text(json)).await.is_err() {
-                    break;
+            // Status and stats only when something changed since the last tick;
+            // logs, alerts and clears are checked every tick regardless
+            let counts = (state_clone.change_count(), state_clone.tick_count());
+            if last_sent_counts != Some(counts) {
+                last_sent_counts = Some(counts);
+
+                // Send status
+                let snapshot = state_clone.snapshot();
+                let status_msg = WsMessage::Status {
+                    identity: config.read().identity.clone(),
+                    status: snapshot.status.as_str().to_string(),
+                    pid: snapshot.pid,
+                    uptime_secs: snapshot.uptime_secs,
+                    restart_count: snapshot.restart_count,
+                    auto_restart_remaining_secs: snapshot.auto_restart_remaining_secs,
+                    next_backup_secs: snapshot.next_backup_secs,
+                };
+
+                if let Ok(json) = serde_json::to_string(&status_msg) {
+                    if sender.send(Message::Text(json)).await.is_err() {
+                        break;
+                    }
+                }
+
+                // Send stats
+                let stats = state_clone.stats();
+                let stats_msg = WsMessage::Stats {
+                    cpu_percent: stats.cpu_percent,
+                    memory_mb: stats.memory_mb,
+                    memory_percent: stats.memory_percent,
+                    network_rx_speed: stats.network_rx_speed,
+                    network_tx_speed: stats.network_tx_speed,
+                };
+
+                if let Ok(json) = serde_json::to_string(&stats_msg) {
+                    if sender.send(Message::Text(json)).await.is_err() {
+                        break;
+                    }
                 }
             }
 
-            // Send new logs
-            let logs = state_clone.logs(1000);
-            let current_count = logs.len();
-            if current_count > last_log_count {
-                // Send only new logs
-                let new_logs = &logs[..current_count - last_log_count];
-                for log in new_logs.iter().rev() {
-                    let log_msg = WsMessage::Log {
-                        timestamp: log.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
-                        level: format!("{:?}", log.level).to_lowercase(),
-                        source: format!("{:?}", log.source).to_lowercase(),
-                        message: log.message.clone(),
+            // Send the active alert list when an alert was raised or resolved
+            let alerts_generation = state_clone.alerts_generation();
+            if alerts_generation != last_alerts_generation {
+                last_alerts_generation = alerts_generation;
+                let alert_msg = WsMessage::Alert {
+                    alerts: state_clone.active_alerts(),
+                };
+                if let Ok(json) = serde_json::to_string(&alert_msg) {
+                    if sender.send(Message::Text(json)).await.is_err() {
+                        break;
+                    }
+                }
+            }
+
+            // Tell the client to drop its log view, then resend from scratch
+            let logs_cleared_at = state_clone.logs_cleared_at();
+            if logs_cleared_at != last_logs_cleared_at {
+                last_logs_cleared_at = logs_cleared_at;
+                if let Some(cleared_at) = logs_cleared_at {
+                    let cleared_msg = WsMessage::LogsCleared {
+                        cleared_at: cleared_at.format("%Y-%m-%d %H:%M:%S").to_string(),
                     };
+                    if let Ok(json) = serde_json::to_string(&cleared_msg) {
+                        if sender.send(Message::Text(json)).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
 
-                    if let Ok(json) = serde_json::to_string(&log_msg) {
+            // Send new logs
+            let (mut new_logs, _) = state_clone.logs_after(last_log_id, usize::MAX);
+            if new_logs.is_empty() {
+                // Resend the newest line if more repeats were collapsed into it
+                new_logs = state_clone
+                    .logs(1)
+                    .into_iter()
+                    .filter(|log| log.id == last_log_id && log.repeat_count != last_repeat_count)
+                    .collect();
+            }
+            if let Some(last) = new_logs.last() {
+                last_log_id = last.id;
+                last_repeat_count = last.repeat_count;
+                for log in &new_logs {
+                    if let Ok(json) = serde_json::to_string(&log_message(log)) {
                         if sender.send(Message::Text(json)).await.is_err() {
                             break;
                         }
                     }
                 }
-                last_log_count = current_count;
             }
         }
     });
@@ -149,3 +247,14 @@ async fn handle_socket(socket: WebSocket, app_state: Arc<AppState>) {
 
     tracing::debug!("WebSocket connection closed");
 }
+
+fn log_message(log: &LogEntry) -> WsMessage {
+    WsMessage::Log {
+        id: log.id,
+        timestamp: log.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+        level: format!("{:?}", log.level).to_lowercase(),
+        source: format!("{:?}", log.source).to_lowercase(),
+        message: log.message.clone(),
+        repeat_count: log.repeat_count,
+    }
+}
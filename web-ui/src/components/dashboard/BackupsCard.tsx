@@ -68,6 +68,18 @@ export const BackupsCard = component$<BackupsCardProps>(({ backups, onRefresh$ }
                     <div class="text-xs text-[var(--color-text-muted)]">
                       {backup.size} • {backup.created_at}
                     </div>
+                    {backup.tags && backup.tags.length > 0 && (
+                      <div class="flex flex-wrap gap-1 mt-1">
+                        {backup.tags.map((tag) => (
+                          <span
+                            key={tag}
+                            class="px-1.5 py-0.5 rounded text-xs bg-[var(--color-primary)]/20 text-[var(--color-primary)]"
+                          >
+                            {tag}
+                          </span>
+                        ))}
+                      </div>
+                    )}
                   </div>
                 </div>
 
@@ -196,9 +196,14 @@ export const LogViewer = component$<LogViewerProps>(({ logs }) => {
                 <span class={`text-xs ${sourceColors[log.source]} whitespace-nowrap`}>
                   [{log.source.slice(0, 3).toUpperCase()}]
                 </span>
-                <span class={`flex-1 ${levelColors[log.level]} break-all`}>
+                <span class={`flex-1 ${levelColors[log.level]} break-all whitespace-pre-wrap`}>
                   {log.message}
                 </span>
+                {!!log.repeat_count && (
+                  <span class="text-xs text-[var(--color-text-muted)] whitespace-nowrap">
+                    ×{log.repeat_count + 1}
+                  </span>
+                )}
               </div>
             ))}
           </div>
@@ -77,7 +77,7 @@ export const translations = {
     workingDirectory: "Working Directory",
     restartDelay: "Restart Delay (seconds)",
     maxRestarts: "Max Restarts",
-    autoRestartHourly: "Auto-restart hourly",
+    autoRestartInterval: "Auto-restart Interval (minutes)",
     restartWarningMessage: "Restart Warning Message",
 
     // Settings - Backup
@@ -232,7 +232,7 @@ export const translations = {
     workingDirectory: "Рабочая директория",
     restartDelay: "Задержка перезапуска (сек)",
     maxRestarts: "Макс. перезапусков",
-    autoRestartHourly: "Авто-рестарт каждый час",
+    autoRestartInterval: "Интервал авто-рестарта (мин)",
     restartWarningMessage: "Сообщение предупреждения",
 
     // Settings - Backup
@@ -29,10 +29,13 @@ export interface StatsData {
 }
 
 export interface LogEntry {
+  id?: number;
   timestamp: string;
   level: LogLevel;
   source: LogSource;
   message: string;
+  fields?: Record<string, string>;
+  repeat_count?: number;
 }
 
 export interface BackupEntry {
@@ -40,6 +43,8 @@ export interface BackupEntry {
   size: string;
   size_bytes: number;
   created_at: string;
+  tags?: string[];
+  checksum?: string | null;
 }
 
 export interface FullState {
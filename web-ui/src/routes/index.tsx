@@ -130,9 +130,16 @@ export default component$(() => {
               network_tx_speed: data.data.network_tx_speed,
             };
             break;
-          case "log":
-            state.logs = [...state.logs.slice(-999), data.data as LogEntry];
+          case "log": {
+            const log = data.data as LogEntry;
+            const last = state.logs[state.logs.length - 1];
+            // A grown repeat count arrives as the same entry again
+            state.logs =
+              log.id !== undefined && last?.id === log.id
+                ? [...state.logs.slice(0, -1), log]
+                : [...state.logs.slice(-999), log];
             break;
+          }
         }
       },
       () => {
@@ -4,14 +4,28 @@ import { useLanguageProvider } from "~/lib/i18n-context";
 import { type Language, translations, setStoredLanguage } from "~/lib/i18n";
 import { api } from "~/lib/api";
 
+// Plain strings are substring matches; regex entries are kept as objects
+type PatternEntry = string | { pattern: string; is_regex: boolean; extract_fields?: boolean };
+
+// Only plain patterns are edited here, regex entries are left as they are
+const plainPatterns = (entries: PatternEntry[]) =>
+  entries.filter((p): p is string => typeof p === "string").join(", ");
+
+const mergePatterns = (text: string, entries: PatternEntry[]): PatternEntry[] => [
+  ...entries.filter((p) => typeof p !== "string"),
+  ...text.split(",").map(s => s.trim()).filter(Boolean),
+];
+
 interface Config {
   server: {
     executable: string;
     arguments: string[];
     working_directory: string | null;
-    restart_delay_seconds: number;
+    error_restart_delay_secs: number;
     max_restarts: number | null;
+    /** Deprecated, read only to migrate it to the interval */
     auto_restart_hourly: boolean;
+    auto_restart_interval_minutes: number | null;
     restart_warning_message: string;
   };
   telegram: {
@@ -25,9 +39,9 @@ interface Config {
     check_interval_seconds: number;
   };
   error_patterns: {
-    critical: string[];
-    errors: string[];
-    warnings: string[];
+    critical: PatternEntry[];
+    errors: PatternEntry[];
+    warnings: PatternEntry[];
   };
   restart_on: {
     critical: boolean;
@@ -55,10 +69,11 @@ const defaultConfig: Config = {
     executable: "java",
     arguments: ["-Xms4G", "-Xmx8G", "-jar", "server.jar"],
     working_directory: null,
-    restart_delay_seconds: 30,
+    error_restart_delay_secs: 30,
     max_restarts: null,
     auto_restart_hourly: false,
-    restart_warning_message: "Server will restart in 1 minute!",
+    auto_restart_interval_minutes: null,
+    restart_warning_message: "Server will restart in {minutes} min!",
   },
   telegram: {
     enabled: false,
@@ -126,6 +141,11 @@ export default component$(() => {
 
       // Update store
       Object.assign(config.server, loadedConfig.server);
+      // The old flag means a 60 minute interval; saving drops it
+      if (config.server.auto_restart_hourly && config.server.auto_restart_interval_minutes == null) {
+        config.server.auto_restart_interval_minutes = 60;
+      }
+      config.server.auto_restart_hourly = false;
       Object.assign(config.telegram, loadedConfig.telegram);
       Object.assign(config.resources, loadedConfig.resources);
       Object.assign(config.error_patterns, loadedConfig.error_patterns);
@@ -137,9 +157,9 @@ export default component$(() => {
 
       // Set text fields
       argumentsText.value = config.server.arguments.join(" ");
-      criticalPatternsText.value = config.error_patterns.critical.join(", ");
-      errorPatternsText.value = config.error_patterns.errors.join(", ");
-      warningPatternsText.value = config.error_patterns.warnings.join(", ");
+      criticalPatternsText.value = plainPatterns(config.error_patterns.critical);
+      errorPatternsText.value = plainPatterns(config.error_patterns.errors);
+      warningPatternsText.value = plainPatterns(config.error_patterns.warnings);
     } catch (e) {
       console.error("Failed to load config:", e);
       const errorText = translations[language.value].configLoadError || translations.en.configLoadError;
@@ -156,9 +176,9 @@ export default component$(() => {
     try {
       // Parse text fields back to arrays
       config.server.arguments = argumentsText.value.split(/\s+/).filter(Boolean);
-      config.error_patterns.critical = criticalPatternsText.value.split(",").map(s => s.trim()).filter(Boolean);
-      config.error_patterns.errors = errorPatternsText.value.split(",").map(s => s.trim()).filter(Boolean);
-      config.error_patterns.warnings = warningPatternsText.value.split(",").map(s => s.trim()).filter(Boolean);
+      config.error_patterns.critical = mergePatterns(criticalPatternsText.value, config.error_patterns.critical);
+      config.error_patterns.errors = mergePatterns(errorPatternsText.value, config.error_patterns.errors);
+      config.error_patterns.warnings = mergePatterns(warningPatternsText.value, config.error_patterns.warnings);
 
       await api.updateConfig(config);
       const successText = translations[language.value].configSaved || translations.en.configSaved;
@@ -310,8 +330,8 @@ export default component$(() => {
                       <input
                         type="number"
                         class="w-full px-3 py-2 bg-[var(--color-bg)] border border-[var(--color-border)] rounded-lg focus:border-[var(--color-primary)] focus:outline-none"
-                        value={config.server.restart_delay_seconds}
-                        onInput$={(e) => (config.server.restart_delay_seconds = parseInt((e.target as HTMLInputElement).value) || 30)}
+                        value={config.server.error_restart_delay_secs}
+                        onInput$={(e) => (config.server.error_restart_delay_secs = parseInt((e.target as HTMLInputElement).value) || 30)}
                       />
                     </div>
                     <div>
@@ -330,22 +350,21 @@ export default component$(() => {
                       />
                     </div>
                   </div>
-                  <div class="flex items-center justify-between">
-                    <label class="font-medium">{t("autoRestartHourly")}</label>
-                    <button
-                      class={`w-12 h-6 rounded-full relative transition-colors ${
-                        config.server.auto_restart_hourly
-                          ? "bg-[var(--color-primary)]"
-                          : "bg-[var(--color-bg)] border border-[var(--color-border)]"
-                      }`}
-                      onClick$={() => (config.server.auto_restart_hourly = !config.server.auto_restart_hourly)}
-                    >
-                      <span class={`absolute top-1 w-4 h-4 rounded-full transition-all ${
-                        config.server.auto_restart_hourly
-                          ? "right-1 bg-white"
-                          : "left-1 bg-[var(--color-text-muted)]"
-                      }`}></span>
-                    </button>
+                  <div>
+                    <label class="block text-sm text-[var(--color-text-muted)] mb-1">
+                      {t("autoRestartInterval")}
+                    </label>
+                    <input
+                      type="number"
+                      min="1"
+                      class="w-full px-3 py-2 bg-[var(--color-bg)] border border-[var(--color-border)] rounded-lg focus:border-[var(--color-primary)] focus:outline-none"
+                      placeholder={t("disabled")}
+                      value={config.server.auto_restart_interval_minutes ?? ""}
+                      onInput$={(e) => {
+                        const val = parseInt((e.target as HTMLInputElement).value);
+                        config.server.auto_restart_interval_minutes = val > 0 ? val : null;
+                      }}
+                    />
                   </div>
                   <div>
                     <label class="block text-sm text-[var(--color-text-muted)] mb-1">